@@ -0,0 +1,522 @@
+use crate::parallel::{derive_seed, parallel_map, SplitMix64};
+use crate::virtual_machine::uses_pspace;
+use crate::{
+    Instruction, MatchSettings, PSpace, SettingsError, VirtualMachine, WarriorLimits,
+    WarriorStatistics,
+};
+
+/// The result of a single round of a match
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundResult {
+    /// The ids of the warriors that were still alive at the end of the round
+    pub survivors: Vec<usize>,
+    /// The id of the warrior that executed the first instruction of the round
+    pub first_warrior: usize,
+    /// The number of cycles that were run before the round ended
+    pub cycles: usize,
+    /// The seed that was used to place the warriors
+    pub seed: u64,
+    /// What each warrior did during the round, indexed in the same order as the programs given to
+    /// the round. This is only collected if `MatchSettings::statistics` is set.
+    pub statistics: Option<Vec<WarriorStatistics>>,
+}
+
+/// The wins, losses and ties of a single warrior over a match
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WarriorScore {
+    /// The number of rounds where this warrior was the only survivor
+    pub wins: usize,
+    /// The number of rounds where this warrior was killed
+    pub losses: usize,
+    /// The number of rounds where this warrior survived alongside at least one other warrior
+    pub ties: usize,
+}
+
+impl WarriorScore {
+    /// The standard score of a warrior: 3 points per win and 1 point per tie
+    pub fn score(&self) -> usize {
+        3 * self.wins + self.ties
+    }
+}
+
+/// The result of a multi-round match
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchResult {
+    /// The result of each individual round in the order they were played
+    pub rounds: Vec<RoundResult>,
+    /// The score of each warrior, indexed in the same order as the programs given to the match
+    pub scores: Vec<WarriorScore>,
+    /// The seed that the seed of each round was derived from
+    pub seed: u64,
+    /// The limits each warrior played with after its handicap was applied
+    pub limits: Vec<WarriorLimits>,
+    /// The team of each warrior
+    pub teams: Vec<usize>,
+}
+
+/// The wins, losses and ties of a team over a match
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeamScore {
+    /// The id of the team
+    pub team: usize,
+    /// The warriors in the team
+    pub warriors: Vec<usize>,
+    /// A win is a round where only this team had survivors, a loss is a round where every warrior
+    /// in the team was killed, and a tie is a round where another team also had survivors
+    pub score: WarriorScore,
+    /// The total number of warriors of the team that survived each round
+    pub survivors: usize,
+    /// The statistics of the team's warriors added together over every round (see
+    /// `WarriorStatistics::add`). This is only collected if `MatchSettings::statistics` is set.
+    pub statistics: Option<WarriorStatistics>,
+}
+
+impl MatchResult {
+    /// The melee score of each warrior, where each round the survivors share `W² - 1` points
+    /// between them (`W` being the number of warriors in the match). For two warriors this is 3
+    /// points for a win and 1.5 points for a tie.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, run_match, MatchSettings};
+    ///
+    /// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    /// let dat = create_program! { DAT(F, 0, Immediate, 0, Immediate) };
+    ///
+    /// let settings = MatchSettings { rounds: 3, max_cycles: 1000, ..Default::default() };
+    /// let result = run_match(&[imp.clone(), imp, dat], &settings).unwrap();
+    ///
+    /// // The imps survive every round so they share 8 points each round
+    /// assert_eq!(result.melee_scores(), vec![12.0, 12.0, 0.0]);
+    /// ```
+    pub fn melee_scores(&self) -> Vec<f64> {
+        let warriors = self.scores.len() as f64;
+        let mut scores = vec![0.0; self.scores.len()];
+
+        for round in &self.rounds {
+            for survivor in &round.survivors {
+                scores[*survivor] += (warriors * warriors - 1.0) / round.survivors.len() as f64;
+            }
+        }
+
+        scores
+    }
+
+    /// The score of each team, ordered by team id
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, run_match, MatchSettings};
+    ///
+    /// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    /// let dat = create_program! { DAT(F, 0, Immediate, 0, Immediate) };
+    ///
+    /// let settings = MatchSettings { rounds: 2, teams: vec![0, 0, 1], ..Default::default() };
+    /// let result = run_match(&[imp, dat.clone(), dat], &settings).unwrap();
+    ///
+    /// let teams = result.team_scores();
+    /// assert_eq!(teams[0].warriors, vec![0, 1]);
+    /// assert_eq!(teams[0].score.wins, 2);
+    /// assert_eq!(teams[1].score.losses, 2);
+    /// ```
+    pub fn team_scores(&self) -> Vec<TeamScore> {
+        let mut teams: Vec<TeamScore> = Vec::new();
+
+        for (warrior, team) in self.teams.iter().enumerate() {
+            match teams.iter_mut().find(|score| score.team == *team) {
+                Some(score) => score.warriors.push(warrior),
+                None => teams.push(TeamScore {
+                    team: *team,
+                    warriors: vec![warrior],
+                    score: WarriorScore::default(),
+                    survivors: 0,
+                    statistics: None,
+                }),
+            }
+        }
+        teams.sort_by_key(|score| score.team);
+
+        for round in &self.rounds {
+            let surviving_teams = teams
+                .iter()
+                .filter(|score| {
+                    score
+                        .warriors
+                        .iter()
+                        .any(|warrior| round.survivors.contains(warrior))
+                })
+                .count();
+
+            for score in &mut teams {
+                let survivors = score
+                    .warriors
+                    .iter()
+                    .filter(|warrior| round.survivors.contains(warrior))
+                    .count();
+                score.survivors += survivors;
+
+                if survivors == 0 {
+                    score.score.losses += 1;
+                } else if surviving_teams == 1 {
+                    score.score.wins += 1;
+                } else {
+                    score.score.ties += 1;
+                }
+            }
+        }
+
+        if self.rounds.iter().all(|round| round.statistics.is_some()) {
+            for score in &mut teams {
+                for round in &self.rounds {
+                    let warriors = round.statistics.as_ref().unwrap();
+
+                    for warrior in &score.warriors {
+                        match &mut score.statistics {
+                            Some(statistics) => statistics.add(&warriors[*warrior]),
+                            None => score.statistics = Some(warriors[*warrior].clone()),
+                        }
+                    }
+                }
+            }
+        }
+
+        teams
+    }
+}
+
+/// Runs the VM until fewer than two warriors are alive (or every warrior is dead for a single
+/// warrior battle), or until `max_cycles` cycles have been run.
+/// Returns the number of cycles that were run.
+pub fn run_battle(vm: &mut VirtualMachine, max_cycles: usize) -> usize {
+    let teams: Vec<usize> = (0..vm.get_users_pcs().len()).collect();
+    run_team_battle_with(vm, &teams, max_cycles, |_| {})
+}
+
+/// Runs the VM like `run_battle` where user `i` is on team `teams[i]`, so the battle ends once
+/// fewer than two teams have warriors alive (or every warrior is dead if there is only one team).
+/// Returns the number of cycles that were run, or an error if there isn't a team for every user.
+pub fn run_team_battle(
+    vm: &mut VirtualMachine,
+    teams: &[usize],
+    max_cycles: usize,
+) -> Result<usize, SettingsError> {
+    let users = vm.get_users_pcs().len();
+    if teams.len() != users {
+        return Err(SettingsError::Teams(teams.len(), users));
+    }
+
+    Ok(run_team_battle_with(vm, teams, max_cycles, |_| {}))
+}
+
+/// Runs a battle like `run_team_battle`, calling `after_cycle` after every call to
+/// `VirtualMachine::cycle`
+pub(crate) fn run_team_battle_with(
+    vm: &mut VirtualMachine,
+    teams: &[usize],
+    max_cycles: usize,
+    mut after_cycle: impl FnMut(&VirtualMachine),
+) -> usize {
+    assert_eq!(
+        teams.len(),
+        vm.get_users_pcs().len(),
+        "Every warrior needs a team"
+    );
+
+    // A lone team "survives" until it is killed, otherwise the battle ends once there is a
+    // single team with survivors
+    let single_team = teams.iter().all(|team| *team == teams[0]);
+
+    for cycle in 0..max_cycles {
+        let alive = vm.alive_warriors();
+
+        let ended = match alive.first() {
+            Some(first) => !single_team && alive.iter().all(|user| teams[*user] == teams[*first]),
+            None => true,
+        };
+        if ended {
+            return cycle;
+        }
+
+        // Each cycle every living warrior executes as many instructions as its speed
+        let steps: usize = alive.iter().map(|user| vm.get_speed(*user)).sum();
+        for _ in 0..steps {
+            // The last warriors can die part way through a cycle
+            if vm.alive_warriors().is_empty() {
+                return cycle + 1;
+            }
+
+            vm.cycle();
+            after_cycle(vm);
+        }
+    }
+
+    max_cycles
+}
+
+/// Runs a single round where the warrior `first_warrior` moves first and the warriors are placed
+/// using `seed`. Each warrior starts with an empty P-space.
+pub fn run_round(
+    programs: &[Vec<Instruction>],
+    settings: &MatchSettings,
+    first_warrior: usize,
+    seed: u64,
+) -> RoundResult {
+    play_round(
+        programs,
+        settings,
+        first_warrior,
+        seed,
+        &mut settings.pspace(programs.len()),
+    )
+}
+
+/// Runs a single round like `run_round`, where the warriors use `pspace` as their P-space.
+/// Location 0 of each warrior's P-space is set to the result of the round afterwards.
+/// Returns an error if the match can't be played with the settings, or if `pspace` wasn't made
+/// for the warriors and `MatchSettings::pspace_size`.
+pub fn run_round_with_pspace(
+    programs: &[Vec<Instruction>],
+    settings: &MatchSettings,
+    first_warrior: usize,
+    seed: u64,
+    pspace: &mut PSpace,
+) -> Result<RoundResult, SettingsError> {
+    settings.validate_match(programs)?;
+    check_pspace(pspace, programs.len(), settings)?;
+
+    Ok(play_round(programs, settings, first_warrior, seed, pspace))
+}
+
+/// Checks that `pspace` has a P-space of `MatchSettings::pspace_size` for each of the `warriors`
+fn check_pspace(
+    pspace: &PSpace,
+    warriors: usize,
+    settings: &MatchSettings,
+) -> Result<(), SettingsError> {
+    if pspace.get_warriors() != warriors {
+        Err(SettingsError::PSpaceWarriors(
+            pspace.get_warriors(),
+            warriors,
+        ))
+    } else if pspace.get_size() != settings.pspace_size {
+        Err(SettingsError::PSpaceSize(
+            pspace.get_size(),
+            settings.pspace_size,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Runs a round like `run_round_with_pspace` without checking the settings or the P-space
+fn play_round(
+    programs: &[Vec<Instruction>],
+    settings: &MatchSettings,
+    first_warrior: usize,
+    seed: u64,
+    pspace: &mut PSpace,
+) -> RoundResult {
+    let warriors = programs.len();
+
+    // The VM always starts with user 0, so rotate the programs so that `first_warrior` is user 0.
+    // User `i` in the VM is therefore warrior `(i + first_warrior) % warriors`.
+    let rotated: Vec<Vec<Instruction>> = (0..warriors)
+        .map(|i| programs[(i + first_warrior) % warriors].clone())
+        .collect();
+
+    let order: Vec<usize> = (0..warriors)
+        .map(|i| (i + first_warrior) % warriors)
+        .collect();
+    let rotated_settings = settings.select_warriors(&order);
+
+    let mut vm = VirtualMachine::new_battle_with_rng(
+        &rotated,
+        &rotated_settings,
+        &mut SplitMix64::new(seed),
+    );
+
+    if settings.statistics {
+        vm.track_statistics();
+    }
+
+    // The P-space is rotated in the same way as the programs while the VM uses it
+    pspace.rotate_left(first_warrior);
+    std::mem::swap(vm.get_pspace_mut(), pspace);
+
+    let teams: Vec<usize> = (0..warriors)
+        .map(|user| rotated_settings.warrior_team(user))
+        .collect();
+    let cycles = run_team_battle_with(&mut vm, &teams, settings.max_cycles, |_| {});
+
+    std::mem::swap(vm.get_pspace_mut(), pspace);
+    pspace.rotate_right(first_warrior);
+
+    let mut survivors: Vec<usize> = vm
+        .alive_warriors()
+        .iter()
+        .map(|user| (user + first_warrior) % warriors)
+        .collect();
+    survivors.sort();
+
+    for warrior in 0..warriors {
+        let result = if survivors.contains(&warrior) {
+            survivors.len() as isize
+        } else {
+            0
+        };
+        pspace.set_result(warrior, result);
+    }
+
+    // The statistics are un-rotated so that they are indexed by warrior
+    let statistics = vm.take_statistics().map(|statistics| {
+        let mut warriors = statistics.into_warriors();
+        warriors.rotate_right(first_warrior);
+        warriors
+    });
+
+    RoundResult {
+        survivors,
+        first_warrior,
+        cycles,
+        seed,
+        statistics,
+    }
+}
+
+/// Returns the seed of each round of a match, which are all derived from the match's seed
+pub(crate) fn round_seeds(match_seed: u64, rounds: usize) -> Vec<u64> {
+    (0..rounds)
+        .map(|round| derive_seed(match_seed, round as u64))
+        .collect()
+}
+
+/// The limits each of the `warriors` of a match plays with
+pub(crate) fn match_limits(warriors: usize, settings: &MatchSettings) -> Vec<WarriorLimits> {
+    (0..warriors)
+        .map(|warrior| settings.warrior_limits(warrior))
+        .collect()
+}
+
+/// The team of each of the `warriors` of a match
+pub(crate) fn match_teams(warriors: usize, settings: &MatchSettings) -> Vec<usize> {
+    (0..warriors)
+        .map(|warrior| settings.warrior_team(warrior))
+        .collect()
+}
+
+/// Totals the wins, losses and ties of each of the `warriors` over the rounds
+pub(crate) fn tally_rounds(warriors: usize, rounds: &[RoundResult]) -> Vec<WarriorScore> {
+    let mut scores = vec![WarriorScore::default(); warriors];
+
+    for round in rounds {
+        for (warrior, score) in scores.iter_mut().enumerate() {
+            if !round.survivors.contains(&warrior) {
+                score.losses += 1;
+            } else if round.survivors.len() == 1 {
+                score.wins += 1;
+            } else {
+                score.ties += 1;
+            }
+        }
+    }
+
+    scores
+}
+
+/// Runs `settings.rounds` rounds between the programs, using fresh placements for each round and
+/// rotating which warrior moves first.
+/// The rounds are spread over `settings.threads` threads. Each round is placed using a seed derived
+/// from the match's seed, so the result only depends on the seed and not on the number of threads.
+/// Returns an error if the match can't be played with the settings.
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, run_match, MatchSettings};
+///
+/// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+/// let dat = create_program! { DAT(None, 0, Immediate, 0, Immediate) };
+///
+/// let result = run_match(&[imp, dat], &MatchSettings { rounds: 4, ..Default::default() }).unwrap();
+///
+/// assert_eq!(result.scores[0].wins, 4);
+/// assert_eq!(result.scores[0].score(), 12);
+/// assert_eq!(result.scores[1].losses, 4);
+/// ```
+pub fn run_match(
+    programs: &[Vec<Instruction>],
+    settings: &MatchSettings,
+) -> Result<MatchResult, SettingsError> {
+    settings.validate_match(programs)?;
+
+    if programs.iter().any(|program| uses_pspace(program)) {
+        // Each round depends on the P-space left by the previous round so they can't be spread
+        // over threads
+        return run_match_with_pspace(programs, settings, &mut settings.pspace(programs.len()));
+    }
+
+    let seed = settings.seed.unwrap_or_else(rand::random);
+    let seeds = round_seeds(seed, settings.rounds);
+
+    let rounds = parallel_map(settings.rounds, settings.threads, |round| {
+        run_round(programs, settings, round % programs.len(), seeds[round])
+    });
+
+    Ok(MatchResult {
+        scores: tally_rounds(programs.len(), &rounds),
+        rounds,
+        seed,
+        limits: match_limits(programs.len(), settings),
+        teams: match_teams(programs.len(), settings),
+    })
+}
+
+/// Runs a match like `run_match` where the warriors keep `pspace` as their P-space from one round
+/// to the next. The rounds are always run one after the other on the current thread.
+/// `MatchSettings::pspace` creates an empty P-space that is shared by warriors with the same PIN.
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, run_match_with_pspace, MatchSettings, PSpace};
+///
+/// // Stores 1 in P-space location 1 and then dies
+/// let program = create_program! {
+///     STP(AB, 1, Immediate, 1, Immediate)
+///     DAT(F, 0, Immediate, 0, Immediate)
+/// };
+///
+/// let settings = MatchSettings { rounds: 2, ..Default::default() };
+/// let mut pspace = PSpace::new(settings.pspace_size, 1);
+/// run_match_with_pspace(&[program], &settings, &mut pspace).unwrap();
+///
+/// assert_eq!(pspace.load(0, 1), 1);
+/// // The warrior was killed in the last round
+/// assert_eq!(pspace.load(0, 0), 0);
+/// ```
+pub fn run_match_with_pspace(
+    programs: &[Vec<Instruction>],
+    settings: &MatchSettings,
+    pspace: &mut PSpace,
+) -> Result<MatchResult, SettingsError> {
+    settings.validate_match(programs)?;
+    check_pspace(pspace, programs.len(), settings)?;
+
+    let seed = settings.seed.unwrap_or_else(rand::random);
+
+    let rounds: Vec<RoundResult> = round_seeds(seed, settings.rounds)
+        .into_iter()
+        .enumerate()
+        .map(|(round, round_seed)| {
+            play_round(
+                programs,
+                settings,
+                round % programs.len(),
+                round_seed,
+                pspace,
+            )
+        })
+        .collect();
+
+    Ok(MatchResult {
+        scores: tally_rounds(programs.len(), &rounds),
+        rounds,
+        seed,
+        limits: match_limits(programs.len(), settings),
+        teams: match_teams(programs.len(), settings),
+    })
+}
@@ -0,0 +1,178 @@
+use darwin_lib::{compile_program, run_match, Instruction, MatchSettings};
+
+use std::process;
+
+const USAGE: &str = "\
+Usage: darwin-mars [options] <warrior.red>...
+
+Runs a match between the warriors and prints the scores like pMARS.
+
+Options:
+  -r <rounds>     the number of rounds to play (default 1)
+  -s <size>       the size of the core (default 8000)
+  -c <cycles>     the number of cycles before a round is a tie (default 80000)
+  -p <processes>  the maximum number of processes of each warrior (default 8000)
+  -l <length>     the maximum length of a warrior (default 100)
+  -d <distance>   the minimum distance between warriors (default 100)
+  -F <position>   the fixed distance from the first warrior to the second
+  -b              brief mode, don't list the warriors";
+
+/// A warrior that was read from a file
+struct Warrior {
+    name: String,
+    author: String,
+    program: Vec<Instruction>,
+    /// The PIN of the warrior, from a `PIN` line
+    pin: Option<u64>,
+}
+
+/// Prints `message` and the usage, then exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("darwin-mars: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Prints `message` and exits
+fn error(message: &str) -> ! {
+    eprintln!("darwin-mars: {}", message);
+    process::exit(1);
+}
+
+/// The value of a `;name` or `;author` comment in `source`
+fn metadata(source: &str, key: &str) -> Option<String> {
+    source.lines().find_map(|line| {
+        let comment = line.trim().strip_prefix(';')?.trim_start();
+        let value = comment.strip_prefix(key)?;
+        if value.starts_with(char::is_whitespace) {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Reads, compiles and validates the warrior at `path`, exiting if it can't
+fn load_warrior(path: &str, settings: &MatchSettings) -> Warrior {
+    let source = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| error(&format!("can't read {}: {}", path, e)));
+    let compilation = compile_program(&source);
+    if let Some(e) = compilation.errors.first() {
+        error(&format!("{}: {}", path, e));
+    }
+    let program = compilation.program();
+    if let Err(e) = settings.validate_warrior(&program) {
+        error(&format!("{}: {}", path, e));
+    }
+
+    Warrior {
+        name: metadata(&source, "name").unwrap_or_else(|| "Unknown".to_string()),
+        author: metadata(&source, "author").unwrap_or_else(|| "Anonymous".to_string()),
+        program,
+        pin: compilation.pin,
+    }
+}
+
+fn main() {
+    let mut settings = MatchSettings::default();
+    let mut brief = false;
+    let mut paths = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-b" {
+            brief = true;
+            continue;
+        }
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            return;
+        }
+        if !arg.starts_with('-') || arg.len() < 2 {
+            paths.push(arg);
+            continue;
+        }
+
+        // Values can either be part of the option (-r10) or the next argument (-r 10)
+        let (option, attached) = arg.split_at(2);
+        let value = if attached.is_empty() {
+            args.next()
+                .unwrap_or_else(|| usage_error(&format!("{} needs a value", option)))
+        } else {
+            attached.to_string()
+        };
+        let value: usize = value
+            .parse()
+            .unwrap_or_else(|_| usage_error(&format!("invalid value for {}: {}", option, value)));
+
+        match option {
+            "-r" => settings.rounds = value,
+            "-s" => settings.core_size = value,
+            "-c" => settings.max_cycles = value,
+            "-p" => settings.max_processes = value,
+            "-l" => settings.max_length = value,
+            "-d" => settings.min_separation = value,
+            "-F" => settings.fixed_position = Some(value),
+            _ => usage_error(&format!("unknown option {}", option)),
+        }
+    }
+
+    if paths.is_empty() {
+        usage_error("no warriors were given");
+    }
+    if settings.core_size == 0 || settings.rounds == 0 {
+        usage_error("the core size and the number of rounds must be at least 1");
+    }
+
+    let warriors: Vec<Warrior> = paths
+        .iter()
+        .map(|path| load_warrior(path, &settings))
+        .collect();
+    let programs: Vec<Vec<Instruction>> = warriors
+        .iter()
+        .map(|warrior| warrior.program.clone())
+        .collect();
+    settings.pins = warriors.iter().map(|warrior| warrior.pin).collect();
+    if let Err(error) = settings.validate_match(&programs) {
+        usage_error(&error.to_string());
+    }
+
+    if !brief {
+        for warrior in &warriors {
+            println!(
+                "Program \"{}\" (length {}) by \"{}\"\n",
+                warrior.name,
+                warrior.program.len(),
+                warrior.author
+            );
+            for instruction in &warrior.program {
+                println!("       {}", instruction);
+            }
+            println!();
+        }
+    }
+
+    let result =
+        run_match(&programs, &settings).unwrap_or_else(|error| usage_error(&error.to_string()));
+
+    // Two warriors get 3 points for a win and 1 for a tie, more use the melee score
+    let scores: Vec<usize> = if warriors.len() <= 2 {
+        result.scores.iter().map(|score| score.score()).collect()
+    } else {
+        result
+            .melee_scores()
+            .iter()
+            .map(|score| score.round() as usize)
+            .collect()
+    };
+
+    for (warrior, score) in warriors.iter().zip(&scores) {
+        println!("{} by {} scores {}", warrior.name, warrior.author, score);
+    }
+
+    if warriors.len() == 2 {
+        println!(
+            "Results: {} {} {}",
+            result.scores[0].wins, result.scores[1].wins, result.scores[0].ties
+        );
+    }
+}
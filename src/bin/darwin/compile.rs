@@ -0,0 +1,155 @@
+use darwin_lib::{compile_program, Compilation};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "\
+Usage: darwin compile [options] <warrior.red>...
+
+Compiles the warriors, printing every error that is found. Exits with status 1 if any warrior
+has an error.
+
+Options:
+  -l, --listing   write an assembly listing instead of a load file
+  -o <file>       write the output to a file instead of standard output
+  -q, --quiet     only check the warriors, don't write any output";
+
+/// What to write for each warrior that compiled
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    LoadFile,
+    Listing,
+    Nothing,
+}
+
+/// Prints `message` and the usage, then exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("darwin compile: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Writes a load file, which only has the compiled instructions (and the PIN, if there is one) and
+/// can be compiled again
+fn write_load_file<W: Write>(
+    path: &str,
+    compilation: &Compilation,
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(writer, ";redcode")?;
+    writeln!(writer, "; compiled from {}", path)?;
+    if let Some(pin) = compilation.pin {
+        writeln!(writer, "PIN {}", pin)?;
+    }
+    for line in &compilation.lines {
+        writeln!(writer, "{}", line.instruction)?;
+    }
+    writeln!(writer, "END")
+}
+
+/// Writes the address, instruction, line number and source of each line of a program
+fn write_listing<W: Write>(
+    path: &str,
+    compilation: &Compilation,
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(writer, "; {}", path)?;
+    for (address, line) in compilation.lines.iter().enumerate() {
+        writeln!(
+            writer,
+            "{:04}  {:<20} {:>4}  {}",
+            address,
+            line.instruction.to_string(),
+            line.line_num,
+            line.source.trim_end()
+        )?;
+    }
+    Ok(())
+}
+
+/// Opens the file at `destination` for the output, or standard output if there isn't one,
+/// exiting if it can't be created
+fn open_output(destination: Option<&str>) -> Box<dyn Write> {
+    match destination {
+        Some(destination) => match File::create(destination) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("darwin compile: can't create {}: {}", destination, e);
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout()),
+    }
+}
+
+/// Runs `darwin compile` with the arguments after the subcommand
+pub fn main(mut args: impl Iterator<Item = String>) {
+    let mut output = Output::LoadFile;
+    let mut destination = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "-l" | "--listing" => output = Output::Listing,
+            "-q" | "--quiet" => output = Output::Nothing,
+            "-o" => {
+                destination = Some(
+                    args.next()
+                        .unwrap_or_else(|| usage_error("-o needs a file")),
+                )
+            }
+            option if option.starts_with('-') => usage_error(&format!("unknown option {}", option)),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        usage_error("no warriors were given");
+    }
+
+    // The output is only opened once a warrior has compiled, so a file isn't left behind if
+    // none of them do
+    let mut writer: Option<Box<dyn Write>> = None;
+    let mut failed = false;
+    for path in &paths {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}: can't read the file: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        let compilation = compile_program(&source);
+        for error in &compilation.errors {
+            eprintln!("{}: {}", path, error);
+        }
+        if compilation.lines.is_empty() && compilation.errors.is_empty() {
+            eprintln!("{}: the warrior has no instructions", path);
+        }
+        if !compilation.errors.is_empty() || compilation.lines.is_empty() {
+            failed = true;
+            continue;
+        }
+
+        let write = match output {
+            Output::LoadFile => write_load_file,
+            Output::Listing => write_listing,
+            Output::Nothing => continue,
+        };
+        let writer = writer.get_or_insert_with(|| open_output(destination.as_deref()));
+        if let Err(e) = write(path, &compilation, writer).and_then(|_| writer.flush()) {
+            eprintln!("darwin compile: can't write the output: {}", e);
+            process::exit(1);
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,119 @@
+mod compile;
+mod terminal;
+mod viewer;
+
+use darwin_lib::{parse_program, Instruction, MatchSettings, SplitMix64, VirtualMachine};
+
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "\
+Usage: darwin [options] <warrior.red> <warrior.red>...
+       darwin compile [options] <warrior.red>...
+
+Shows a battle between the warriors in the terminal, or compiles warriors (see
+`darwin compile --help`).
+
+Options:
+  -s <size>       the size of the core (default 8000)
+  -c <cycles>     the number of cycles before the battle is a tie (default 80000)
+  -p <processes>  the maximum number of processes of each warrior (default 8000)
+  -d <distance>   the minimum distance between warriors (default 100)
+  -S <seed>       the seed used to place the warriors
+
+Keys:
+  space           play or pause
+  s, right        run one instruction
+  n, down         run as many instructions as a frame at the current speed
+  +, up           speed up
+  -, left         slow down
+  q, ctrl-c       quit";
+
+/// Prints `message` and the usage, then exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("darwin: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Reads and compiles the warrior at `path`, exiting if it can't
+fn load_warrior(path: &str) -> Vec<Instruction> {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("darwin: can't read {}: {}", path, error);
+        process::exit(1);
+    });
+
+    match parse_program(&source) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("darwin: {}: {}", path, error);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let mut settings = MatchSettings::default();
+    let mut seed = None;
+    let mut paths = Vec::new();
+
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("compile") {
+        args.next();
+        compile::main(args);
+        return;
+    }
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> usize {
+            let value = args
+                .next()
+                .unwrap_or_else(|| usage_error(&format!("{} needs a value", name)));
+            value
+                .parse()
+                .unwrap_or_else(|_| usage_error(&format!("invalid value for {}: {}", name, value)))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "-s" => settings.core_size = value("-s"),
+            "-c" => settings.max_cycles = value("-c"),
+            "-p" => settings.max_processes = value("-p"),
+            "-d" => settings.min_separation = value("-d"),
+            "-S" => seed = Some(value("-S") as u64),
+            option if option.starts_with('-') => usage_error(&format!("unknown option {}", option)),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.len() < 2 {
+        usage_error("at least two warriors are needed");
+    }
+
+    let programs: Vec<Vec<Instruction>> = paths.iter().map(|path| load_warrior(path)).collect();
+    let names = paths
+        .iter()
+        .map(|path| {
+            Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.clone())
+        })
+        .collect();
+
+    if let Err(error) = settings.validate_match(&programs) {
+        usage_error(&error.to_string());
+    }
+
+    // Placed like a round of a match with the same seed
+    let mut rng = SplitMix64::new(seed.unwrap_or_else(rand::random));
+    let mut vm = VirtualMachine::new_battle_with_rng(&programs, &settings, &mut rng);
+    vm.track_ownership();
+
+    if let Err(error) = viewer::Viewer::new(vm, names, &settings).run() {
+        eprintln!("darwin: {}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,164 @@
+use std::cell::Cell;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the terminal is asked for its size, in case it has been resized
+const SIZE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A key that was pressed. Escape sequences other than the arrow keys are read as `Escape`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Escape,
+}
+
+/// Runs `stty` on the controlling terminal, returning what it printed
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed, is stdin a terminal?"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Reads a byte from the terminal, or `None` if no byte arrived before the read timed out
+fn read_byte<R: Read>(reader: &mut R) -> Option<u8> {
+    let mut byte = [0];
+    match reader.read(&mut byte) {
+        Ok(1) => Some(byte[0]),
+        _ => None,
+    }
+}
+
+/// Reads the rest of a control sequence after `ESC [`. Arrow keys are returned as keys, and
+/// cursor position reports (`ESC [ rows ; columns R`) are stored in `size`.
+fn read_control_sequence<R: Read>(reader: &mut R, size: &Mutex<(usize, usize)>) -> Option<Key> {
+    let mut parameters = String::new();
+
+    loop {
+        match read_byte(reader) {
+            Some(b'A') if parameters.is_empty() => return Some(Key::Up),
+            Some(b'B') if parameters.is_empty() => return Some(Key::Down),
+            Some(b'C') if parameters.is_empty() => return Some(Key::Right),
+            Some(b'D') if parameters.is_empty() => return Some(Key::Left),
+            Some(b'R') => {
+                let mut parts = parameters.split(';').map(str::parse);
+                if let (Some(Ok(rows)), Some(Ok(columns))) = (parts.next(), parts.next()) {
+                    *size.lock().unwrap() = (rows, columns);
+                    return None;
+                }
+                return Some(Key::Escape);
+            }
+            // Any other final byte ends a sequence this doesn't understand
+            Some(0x40..=0x7e) | None => return Some(Key::Escape),
+            Some(byte) => parameters.push(byte as char),
+        }
+    }
+}
+
+/// Puts the terminal into a full screen mode that reads single key presses without echoing them,
+/// which is undone when it's dropped
+pub struct Terminal {
+    /// The settings of the terminal before it was changed, from `stty -g`
+    saved: String,
+    keys: Receiver<Key>,
+    /// The number of rows and columns, which is updated whenever the terminal reports its size
+    size: Arc<Mutex<(usize, usize)>>,
+    /// When the terminal was last asked for its size
+    size_requested: Cell<Instant>,
+}
+
+impl Terminal {
+    pub fn new() -> io::Result<Terminal> {
+        let saved = stty(&["-g"])?;
+        let size = stty(&["size"])
+            .ok()
+            .and_then(|size| {
+                let mut parts = size.split_whitespace().map(str::parse);
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(rows)), Some(Ok(columns))) => Some((rows, columns)),
+                    _ => None,
+                }
+            })
+            .unwrap_or((24, 80));
+        // Interrupts are read as keys so that the terminal is always restored. Reads time out
+        // after a tenth of a second so that a lone escape key can be told apart from a sequence.
+        stty(&["-icanon", "-echo", "-isig", "min", "0", "time", "1"])?;
+
+        let mut stdout = io::stdout();
+        // Switch to the alternate screen and hide the cursor
+        write!(stdout, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+
+        let size = Arc::new(Mutex::new(size));
+        let reported_size = Arc::clone(&size);
+        let (sender, keys) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdin = io::stdin().lock();
+            loop {
+                let key = match read_byte(&mut stdin) {
+                    Some(0x1b) => match read_byte(&mut stdin) {
+                        Some(b'[') => read_control_sequence(&mut stdin, &reported_size),
+                        _ => Some(Key::Escape),
+                    },
+                    Some(byte) => Some(Key::Char(byte as char)),
+                    None => None,
+                };
+
+                if let Some(key) = key {
+                    if sender.send(key).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Terminal {
+            saved,
+            keys,
+            size,
+            size_requested: Cell::new(Instant::now()),
+        })
+    }
+
+    /// The number of rows and columns of the terminal.
+    /// Every so often this asks the terminal to report its size, which is read with the keys.
+    pub fn size(&self) -> (usize, usize) {
+        if self.size_requested.get().elapsed() >= SIZE_INTERVAL {
+            // Move the cursor as far as it can go and ask where it is
+            let mut stdout = io::stdout();
+            let _ = write!(stdout, "\x1b7\x1b[999;999H\x1b[6n\x1b8");
+            let _ = stdout.flush();
+            self.size_requested.set(Instant::now());
+        }
+
+        *self.size.lock().unwrap()
+    }
+
+    /// The keys that were pressed
+    pub fn keys(&self) -> &Receiver<Key> {
+        &self.keys
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::terminal::{Key, Terminal};
+
+use darwin_lib::{CoreRenderer, MatchSettings, VirtualMachine};
+
+use std::io::{self, Write};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+/// The time between frames while the battle is playing
+const FRAME: Duration = Duration::from_millis(40);
+/// The number of instructions run each frame at each speed
+const SPEEDS: [usize; 10] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000];
+/// The width of the side panel
+const PANEL: usize = 34;
+
+/// Shows a battle in the terminal
+pub struct Viewer {
+    vm: VirtualMachine,
+    names: Vec<String>,
+    max_cycles: usize,
+    /// The number of cycles that have started, counted like `run_battle` where each cycle every
+    /// living warrior executes as many instructions as its speed
+    cycles: usize,
+    /// The number of instructions left to run in the current cycle
+    steps_left: usize,
+    playing: bool,
+    speed: usize,
+    /// How the battle ended, once it has
+    result: Option<String>,
+    /// The size of the terminal when the last frame was drawn
+    size: (usize, usize),
+}
+
+impl Viewer {
+    pub fn new(vm: VirtualMachine, names: Vec<String>, settings: &MatchSettings) -> Viewer {
+        Viewer {
+            max_cycles: settings.max_cycles,
+            cycles: 0,
+            steps_left: 0,
+            vm,
+            names,
+            playing: false,
+            speed: 3,
+            result: None,
+            size: (0, 0),
+        }
+    }
+
+    /// Runs the viewer until the user quits
+    pub fn run(&mut self) -> io::Result<()> {
+        let terminal = Terminal::new()?;
+
+        loop {
+            self.draw(&terminal)?;
+
+            let timeout = if self.playing {
+                FRAME
+            } else {
+                // Redraw occasionally in case the terminal is resized
+                Duration::from_millis(500)
+            };
+
+            match terminal.keys().recv_timeout(timeout) {
+                Ok(Key::Char('q')) | Ok(Key::Char('\x03')) => return Ok(()),
+                Ok(Key::Char(' ')) => self.playing = !self.playing && self.result.is_none(),
+                Ok(Key::Char('s')) | Ok(Key::Char('.')) | Ok(Key::Right) => {
+                    self.playing = false;
+                    self.advance(1);
+                }
+                Ok(Key::Char('n')) | Ok(Key::Down) => {
+                    self.playing = false;
+                    self.advance(SPEEDS[self.speed]);
+                }
+                Ok(Key::Char('+')) | Ok(Key::Char('=')) | Ok(Key::Up) => {
+                    self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+                }
+                Ok(Key::Char('-')) | Ok(Key::Left) => self.speed = self.speed.saturating_sub(1),
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.playing {
+                        self.advance(SPEEDS[self.speed]);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+
+    /// Runs up to `instructions` instructions, stopping if the battle ends
+    fn advance(&mut self, instructions: usize) {
+        for _ in 0..instructions {
+            if self.result.is_some() {
+                break;
+            }
+
+            if self.steps_left == 0 {
+                self.steps_left = self
+                    .vm
+                    .alive_warriors()
+                    .iter()
+                    .map(|user| self.vm.get_speed(*user))
+                    .sum();
+                self.cycles += 1;
+            }
+
+            self.vm.cycle();
+            self.steps_left -= 1;
+
+            let alive = self.vm.alive_warriors();
+            self.result = if alive.is_empty() {
+                Some("all warriors died".to_string())
+            } else if alive.len() == 1 && self.names.len() > 1 {
+                Some(format!("{} {} wins", alive[0], self.names[alive[0]]))
+            } else if self.steps_left == 0 && self.cycles >= self.max_cycles {
+                Some("tie".to_string())
+            } else {
+                None
+            };
+        }
+
+        if self.result.is_some() {
+            self.playing = false;
+        }
+    }
+
+    fn draw(&mut self, terminal: &Terminal) -> io::Result<()> {
+        let size = terminal.size();
+        let (rows, columns) = size;
+        let core_width = columns.saturating_sub(PANEL + 1).max(10);
+        // The core is followed by the status line and the help line
+        let core_rows = rows.saturating_sub(2).max(1);
+
+        let renderer = CoreRenderer {
+            width: core_width,
+            height: Some(core_rows),
+            colour: true,
+        };
+        let mut core = Vec::new();
+        renderer.render(&self.vm, &mut core)?;
+        let core = String::from_utf8_lossy(&core);
+
+        let mut frame = String::new();
+        if size != self.size {
+            frame.push_str("\x1b[2J");
+            self.size = size;
+        }
+        frame.push_str("\x1b[H");
+        for line in core.lines() {
+            frame.push_str(line);
+            frame.push_str("\x1b[K\n");
+        }
+
+        let state = match &self.result {
+            Some(result) => format!("finished: {}", result),
+            None if self.playing => format!("playing x{}", SPEEDS[self.speed]),
+            None => format!("paused x{}", SPEEDS[self.speed]),
+        };
+        frame.push_str(&format!(
+            "space play/pause  s step  n step x{}  +/- speed  q quit  [{}]\x1b[K",
+            SPEEDS[self.speed], state
+        ));
+
+        for (row, line) in self.panel(rows).iter().enumerate() {
+            frame.push_str(&format!(
+                "\x1b[{};{}H\x1b[0m{:width$.width$}",
+                row + 1,
+                core_width + 2,
+                line,
+                width = PANEL
+            ));
+        }
+
+        let mut stdout = io::stdout();
+        stdout.write_all(frame.as_bytes())?;
+        stdout.flush()
+    }
+
+    /// The lines of the side panel, which lists each warrior's queue
+    fn panel(&self, rows: usize) -> Vec<String> {
+        let queues = self.vm.get_users_pcs();
+        let memory = self.vm.get_memory();
+        let rows_each = (rows / self.names.len().max(1)).max(2);
+
+        let mut lines = Vec::new();
+        for (user, name) in self.names.iter().enumerate() {
+            let queue = &queues[user];
+            let state = if self.vm.is_alive(user) {
+                format!("{} processes", queue.len())
+            } else {
+                "dead".to_string()
+            };
+            lines.push(format!("{} {} ({})", user, name, state));
+
+            // The queue is listed in the order the processes will run
+            let shown = queue.len().min(rows_each - 2);
+            for pc in queue.iter().take(shown) {
+                lines.push(format!("  {:>6} {}", pc, memory[*pc]));
+            }
+            if shown < queue.len() {
+                lines.push(format!("  ... {} more", queue.len() - shown));
+            }
+            while lines.len() < rows_each * (user + 1) {
+                lines.push(String::new());
+            }
+        }
+
+        lines.truncate(rows);
+        lines
+    }
+}
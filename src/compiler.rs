@@ -1,12 +1,13 @@
 use crate::{AddressMode, Instruction, Modifier, OpCode};
 
 mod tokenizer;
+pub(crate) use tokenizer::get_default_modifier;
 use tokenizer::{tokenize_line, TokenizedLine};
 
 use std::fmt;
 
 /// An enum for the different types of error that could occur while compiling a program.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseError<'a> {
     /// When an instruction uses a modifier that is not compatable with the opcode.
     /// Holds the line num where the error was found.
@@ -26,6 +27,21 @@ pub enum ParseError<'a> {
     /// Holds the line number where the error was found and the value that caused the error.
     UnknownValue((usize, &'a str)),
 }
+
+impl ParseError<'_> {
+    /// The line number where the error was found
+    pub fn line_num(&self) -> usize {
+        match self {
+            ParseError::InvalidModifier(l)
+            | ParseError::NotEnoughArgumets(l)
+            | ParseError::UnexpectedArgument(l) => *l,
+            ParseError::UnknownOpCode((l, _))
+            | ParseError::UnknownModifier((l, _))
+            | ParseError::UnknownValue((l, _)) => *l,
+        }
+    }
+}
+
 impl fmt::Display for ParseError<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -54,22 +70,146 @@ impl fmt::Display for ParseError<'_> {
 }
 
 /// Takes in a program as an &str, returns a vector of instructions or a ParseError.
+/// Everything after a `;` is a comment, lines without an instruction are skipped and a line
+/// starting with `END` ends the program. Operands can be separated by a comma.
 /// # Example
 /// ```
 /// use darwin_lib::{Instruction, create_program, parse_program};
 /// assert_eq!(
 ///     parse_program("MOV.I 0 1").unwrap(),
 ///     create_program!(MOV(I, 0, Direct, 1, Direct))
-/// )
+/// );
+/// assert_eq!(
+///     parse_program(";name Imp\n\nMOV.I 0, 1 ; the imp\nEND\nnot part of the program").unwrap(),
+///     create_program!(MOV(I, 0, Direct, 1, Direct))
+/// );
 /// ```
+#[allow(mismatched_lifetime_syntaxes)]
 pub fn parse_program(program: &str) -> Result<Vec<Instruction>, ParseError> {
-    let mut parsed_program: Vec<Instruction> = Vec::new();
-    for (i, line) in program.lines().enumerate() {
-        parsed_program.push(parse_line(&line, i + 1)?);
+    let mut compilation = compile_program(program);
+    if compilation.errors.is_empty() {
+        Ok(compilation.program())
+    } else {
+        Err(compilation.errors.remove(0))
     }
-    Ok(parsed_program)
 }
 
+/// An instruction of a compiled program and the line it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledLine<'a> {
+    /// The line number of the instruction, starting at 1
+    pub line_num: usize,
+    /// The source of the line, including any comment
+    pub source: &'a str,
+    pub instruction: Instruction,
+}
+
+/// Everything the compiler found in a program, see `compile_program`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compilation<'a> {
+    /// Each line that compiled to an instruction, in order
+    pub lines: Vec<CompiledLine<'a>>,
+    /// Every error in the program, in order
+    pub errors: Vec<ParseError<'a>>,
+    /// The PIN given by a `PIN` line, warriors with the same PIN share their P-space
+    /// (see `MatchSettings::pins`)
+    pub pin: Option<u64>,
+}
+
+impl Compilation<'_> {
+    /// The instructions of the program
+    pub fn program(&self) -> Vec<Instruction> {
+        self.lines.iter().map(|line| line.instruction).collect()
+    }
+}
+
+/// Compiles a program like `parse_program` but carries on after errors, so that every error is
+/// found, and keeps the line each instruction came from. A `PIN <number>` line sets the PIN of the
+/// warrior.
+/// # Example
+/// ```
+/// use darwin_lib::{compile_program, ParseError};
+///
+/// let compilation = compile_program("MOV.I 0 1\nABC 0 0\nDAT #0\nJMP -1");
+/// assert_eq!(compilation.lines.len(), 2);
+/// assert_eq!(compilation.lines[1].source, "JMP -1");
+/// assert_eq!(
+///     compilation.errors,
+///     vec![ParseError::UnknownOpCode((2, "ABC")), ParseError::NotEnoughArgumets(3)]
+/// );
+///
+/// assert_eq!(compile_program("PIN 42\nJMP 0").pin, Some(42));
+/// ```
+pub fn compile_program(program: &str) -> Compilation<'_> {
+    let mut compilation = Compilation {
+        lines: Vec::new(),
+        errors: Vec::new(),
+        pin: None,
+    };
+
+    for (i, source) in program.lines().enumerate() {
+        let line = strip_comment(source);
+        if line.trim().is_empty() {
+            continue;
+        }
+        if is_end(line) {
+            break;
+        }
+        if is_pin(line) {
+            match parse_pin(line, i + 1) {
+                Ok(pin) => compilation.pin = Some(pin),
+                Err(error) => compilation.errors.push(error),
+            }
+            continue;
+        }
+
+        match parse_line(line, i + 1) {
+            Ok(instruction) => compilation.lines.push(CompiledLine {
+                line_num: i + 1,
+                source,
+                instruction,
+            }),
+            Err(error) => compilation.errors.push(error),
+        }
+    }
+
+    compilation
+}
+
+/// The part of a line before any comment
+fn strip_comment(line: &str) -> &str {
+    line.split(';').next().unwrap_or("")
+}
+
+/// Whether a line is the `END` of a program
+fn is_end(line: &str) -> bool {
+    line.split_whitespace()
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case("END"))
+}
+
+/// Whether a line is a `PIN` line
+fn is_pin(line: &str) -> bool {
+    line.split_whitespace()
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case("PIN"))
+}
+
+/// Reads the number of a `PIN` line
+fn parse_pin(line: &str, line_num: usize) -> Result<u64, ParseError<'_>> {
+    let mut words = line.split_whitespace().skip(1);
+    let pin = words
+        .next()
+        .ok_or(ParseError::NotEnoughArgumets(line_num))?;
+    if words.next().is_some() {
+        return Err(ParseError::UnexpectedArgument(line_num));
+    }
+
+    pin.parse()
+        .map_err(|_| ParseError::UnknownValue((line_num, pin)))
+}
+
+#[allow(mismatched_lifetime_syntaxes)]
 fn parse_line(line: &str, line_num: usize) -> Result<Instruction, ParseError> {
     let tokenized_line = tokenize_line(line, line_num)?;
     let (op_code, modifier, reg_a, mode_a, reg_b, mode_b) = match tokenized_line {
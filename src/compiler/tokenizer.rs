@@ -8,8 +8,12 @@ pub enum TokenizedLine {
     Double(OpCode, Modifier, isize, AddressMode, isize, AddressMode),
 }
 
+#[allow(mismatched_lifetime_syntaxes)]
 pub fn tokenize_line(line: &str, line_num: usize) -> Result<TokenizedLine, ParseError> {
-    let words: Vec<&str> = line.split_whitespace().collect();
+    let words: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
     if words.len() < 2 {
         return Err(ParseError::NotEnoughArgumets(line_num));
     }
@@ -51,6 +55,7 @@ pub fn tokenize_line(line: &str, line_num: usize) -> Result<TokenizedLine, Parse
     }
 }
 
+#[allow(mismatched_lifetime_syntaxes)]
 fn parse_register(word: &str, line_num: usize) -> Result<(AddressMode, isize), ParseError> {
     match get_addressing_mode(&word[..1]) {
         Ok(v) => Ok((
@@ -67,6 +72,7 @@ fn parse_register(word: &str, line_num: usize) -> Result<(AddressMode, isize), P
     }
 }
 
+#[allow(mismatched_lifetime_syntaxes)]
 fn get_opcode(opcode: &str, line_num: usize) -> Result<OpCode, ParseError> {
     use OpCode::*;
     match opcode {
@@ -86,11 +92,14 @@ fn get_opcode(opcode: &str, line_num: usize) -> Result<OpCode, ParseError> {
         "SEQ" => Ok(SEQ),
         "SNE" => Ok(SNE),
         "SLT" => Ok(SLT),
+        "LDP" => Ok(LDP),
+        "STP" => Ok(STP),
         // Anything else is an error:
         _ => Err(ParseError::UnknownOpCode((line_num, opcode))),
     }
 }
 
+#[allow(mismatched_lifetime_syntaxes)]
 fn get_modifier(modifier: &str, line_num: usize) -> Result<Modifier, ParseError> {
     use Modifier::*;
     match modifier {
@@ -122,7 +131,12 @@ fn get_addressing_mode(addressing_mode: &str) -> Result<AddressMode, ()> {
     }
 }
 
-fn get_default_modifier(opcode: OpCode, mode_a: AddressMode, mode_b: AddressMode) -> Modifier {
+/// The modifier an instruction has when none is specified
+pub(crate) fn get_default_modifier(
+    opcode: OpCode,
+    mode_a: AddressMode,
+    mode_b: AddressMode,
+) -> Modifier {
     use OpCode as o;
     match opcode {
         o::DAT | o::NOP => Modifier::F,
@@ -144,7 +158,7 @@ fn get_default_modifier(opcode: OpCode, mode_a: AddressMode, mode_b: AddressMode
                 Modifier::F
             }
         }
-        o::SLT => {
+        o::SLT | o::LDP | o::STP => {
             if mode_a == AddressMode::Immediate {
                 Modifier::AB
             } else {
@@ -0,0 +1,262 @@
+use crate::{Actor, Instruction, Observer, VirtualMachine};
+
+/// Stops execution before an instruction is executed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breakpoint {
+    /// The address of the instruction
+    pub address: usize,
+    /// Only stop when this user executes the instruction, or any user if this is `None`
+    pub user: Option<usize>,
+}
+
+/// The part of a cell that a watchpoint watches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchField {
+    /// Every write to the cell, even if it doesn't change the cell
+    Cell,
+    /// Writes that change the A field
+    A,
+    /// Writes that change the B field
+    B,
+}
+
+/// Stops execution after an instruction writes to a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    /// The address of the cell
+    pub address: usize,
+    /// Which writes to the cell stop execution
+    pub field: WatchField,
+}
+
+/// Stops execution when a user's number of processes reaches a limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    /// The user (first) has at least the given number of processes
+    ProcessesAtLeast(usize, usize),
+    /// The user (first) has at most the given number of processes, so `ProcessesAtMost(user, 0)`
+    /// stops when the user is killed
+    ProcessesAtMost(usize, usize),
+}
+
+/// Why the debugger stopped running the VM
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// The requested number of cycles were run
+    Done,
+    /// The instruction at `address` is about to be executed by `user`
+    Breakpoint { user: usize, address: usize },
+    /// The cell at `address` was written by `actor`, changing it from `old` to `new`
+    Watchpoint {
+        actor: Actor,
+        address: usize,
+        old: Instruction,
+        new: Instruction,
+    },
+    /// A condition was met
+    Condition(Condition),
+    /// The user given to `Debugger::step_warrior` has been killed
+    WarriorKilled(usize),
+    /// Every user has been killed so there is nothing left to run
+    AllKilled,
+}
+
+/// Wraps a VM to run it until a breakpoint, watchpoint or condition is hit
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, Breakpoint, Debugger, StopReason, VirtualMachine};
+///
+/// let vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+/// let mut debugger = Debugger::new(vm);
+/// debugger.add_breakpoint(Breakpoint { address: 5, user: None });
+///
+/// assert_eq!(debugger.run(100), StopReason::Breakpoint { user: 0, address: 5 });
+/// assert_eq!(debugger.get_vm().get_cycle_count(), 5);
+/// ```
+#[derive(Debug)]
+pub struct Debugger {
+    vm: VirtualMachine,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    conditions: Vec<Condition>,
+}
+
+/// Finds the first watchpoint that was hit during a cycle
+struct WatchpointObserver<'a> {
+    watchpoints: &'a [Watchpoint],
+    hit: Option<StopReason>,
+}
+
+impl Observer for WatchpointObserver<'_> {
+    fn write(&mut self, actor: Actor, address: usize, old: &Instruction, new: &Instruction) {
+        if self.hit.is_some() {
+            return;
+        }
+
+        let hit = self.watchpoints.iter().any(|w| {
+            w.address == address
+                && match w.field {
+                    WatchField::Cell => true,
+                    WatchField::A => old.a_reg != new.a_reg,
+                    WatchField::B => old.b_reg != new.b_reg,
+                }
+        });
+
+        if hit {
+            self.hit = Some(StopReason::Watchpoint {
+                actor,
+                address,
+                old: *old,
+                new: *new,
+            });
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new(vm: VirtualMachine) -> Debugger {
+        Debugger {
+            vm,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+        }
+    }
+
+    pub fn get_vm(&self) -> &VirtualMachine {
+        &self.vm
+    }
+
+    pub fn get_vm_mut(&mut self) -> &mut VirtualMachine {
+        &mut self.vm
+    }
+
+    pub fn into_vm(self) -> VirtualMachine {
+        self.vm
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    /// Removes every breakpoint at the given address
+    pub fn remove_breakpoint(&mut self, address: usize) {
+        self.breakpoints.retain(|b| b.address != address);
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Removes every watchpoint at the given address
+    pub fn remove_watchpoint(&mut self, address: usize) {
+        self.watchpoints.retain(|w| w.address != address);
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    pub fn add_condition(&mut self, condition: Condition) {
+        self.conditions.push(condition);
+    }
+
+    pub fn remove_condition(&mut self, condition: Condition) {
+        self.conditions.retain(|c| *c != condition);
+    }
+
+    /// Runs a single cycle, ignoring breakpoints
+    pub fn step(&mut self) -> StopReason {
+        self.run_cycles(1, None)
+    }
+
+    /// Runs cycles until `user` has executed one instruction. The other users carry on as normal
+    /// in between, so this stops early if they hit a breakpoint, watchpoint or condition.
+    pub fn step_warrior(&mut self, user: usize) -> StopReason {
+        assert!(
+            user < self.vm.get_users_pcs().len(),
+            "There is no user {}",
+            user
+        );
+
+        self.run_cycles(usize::MAX, Some(user))
+    }
+
+    /// Runs up to `max_cycles` cycles, stopping before any instruction with a breakpoint
+    /// (apart from the first, so that running from a breakpoint continues past it)
+    pub fn run(&mut self, max_cycles: usize) -> StopReason {
+        self.run_cycles(max_cycles, None)
+    }
+
+    /// Runs up to `max_cycles` cycles, or until `until_user` has executed an instruction
+    fn run_cycles(&mut self, max_cycles: usize, until_user: Option<usize>) -> StopReason {
+        for cycle in 0..max_cycles {
+            if self.vm.alive_warriors().is_empty() {
+                return StopReason::AllKilled;
+            }
+
+            if let Some(until_user) = until_user {
+                if !self.vm.is_alive(until_user) {
+                    return StopReason::WarriorKilled(until_user);
+                }
+            }
+
+            let user = self.vm.get_cur_user();
+            let address = self.vm.get_users_pcs()[user][0];
+
+            let breakpoint = self
+                .breakpoints
+                .iter()
+                .any(|b| b.address == address && b.user.map_or(true, |u| u == user));
+
+            // The first instruction is always run so that running again continues
+            if breakpoint && cycle > 0 {
+                return StopReason::Breakpoint { user, address };
+            }
+
+            if self.watchpoints.is_empty() {
+                self.vm.cycle();
+            } else {
+                let mut observer = WatchpointObserver {
+                    watchpoints: &self.watchpoints,
+                    hit: None,
+                };
+                self.vm.cycle_with_observer(&mut observer);
+
+                if let Some(hit) = observer.hit {
+                    return hit;
+                }
+            }
+
+            if let Some(condition) = self.met_condition() {
+                return StopReason::Condition(condition);
+            }
+
+            if until_user == Some(user) {
+                return StopReason::Done;
+            }
+        }
+
+        StopReason::Done
+    }
+
+    fn met_condition(&self) -> Option<Condition> {
+        let pcs = self.vm.get_users_pcs();
+
+        self.conditions
+            .iter()
+            .copied()
+            .find(|condition| match condition {
+                Condition::ProcessesAtLeast(user, count) => pcs[*user].len() >= *count,
+                Condition::ProcessesAtMost(user, count) => pcs[*user].len() <= *count,
+            })
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{parse_program, run_match, Instruction, MatchSettings, SettingsError};
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+/// A warrior that is currently on the hill
+#[derive(Debug, Clone, PartialEq)]
+pub struct HillWarrior {
+    /// The name of the warrior
+    pub name: String,
+    /// The instructions of the warrior
+    pub program: Vec<Instruction>,
+    /// The number of challenges this warrior has survived
+    pub age: usize,
+}
+
+/// The outcome of a challenge
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChallengeResult {
+    /// The rank (0 is the top of the hill) of the challenger, or `None` if it didn't make it onto
+    /// the hill
+    pub rank: Option<usize>,
+    /// The score of the challenger against each incumbent, in the order the incumbents were ranked
+    /// before the challenge
+    pub scores: Vec<usize>,
+    /// The warrior that was pushed off the hill (this can be the challenger)
+    pub pushed_off: Option<HillWarrior>,
+}
+
+/// An error that occurred while restoring a hill
+#[derive(Debug)]
+pub enum HillError {
+    /// The hill couldn't be read
+    Io(io::Error),
+    /// A line of the file was not in the expected format. Holds the line number.
+    InvalidLine(usize),
+    /// An instruction of a warrior couldn't be parsed.
+    /// Holds the line number and the description of the parse error.
+    InvalidInstruction(usize, String),
+}
+
+impl fmt::Display for HillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HillError::Io(e) => write!(f, "Couldn't read the hill: {}", e),
+            HillError::InvalidLine(l) => write!(f, "Line {} of the hill is not valid", l),
+            HillError::InvalidInstruction(l, e) => {
+                write!(f, "Invalid instruction on line {} of the hill: {}", l, e)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for HillError {
+    fn from(e: io::Error) -> HillError {
+        HillError::Io(e)
+    }
+}
+
+/// A king of the hill: a fixed number of warriors ranked by their total score against each other.
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, Hill, MatchSettings};
+///
+/// let mut hill = Hill::new(2, MatchSettings { max_cycles: 100, ..Default::default() });
+///
+/// hill.challenge("dat", create_program! { DAT(None, 0, Immediate, 0, Immediate) }).unwrap();
+/// hill.challenge("imp", create_program! { MOV(I, 0, Direct, 1, Direct) }).unwrap();
+///
+/// // The imp beats the DAT so is at the top of the hill
+/// assert_eq!(hill.warriors()[0].name, "imp");
+/// assert_eq!(hill.warriors()[1].age, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Hill {
+    /// The maximum number of warriors on the hill
+    size: usize,
+    /// The settings used for every match on the hill
+    settings: MatchSettings,
+    /// The warriors ordered by rank
+    warriors: Vec<HillWarrior>,
+    /// `results[a][b]` is the score warrior `a` got in its match against warrior `b`
+    results: Vec<Vec<usize>>,
+}
+
+impl Hill {
+    /// Creates an empty hill that holds at most `size` warriors
+    pub fn new(size: usize, settings: MatchSettings) -> Hill {
+        assert!(size > 0, "A hill must hold at least one warrior");
+
+        Hill {
+            size,
+            settings,
+            warriors: Vec::new(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get_settings(&self) -> &MatchSettings {
+        &self.settings
+    }
+
+    /// The warriors on the hill ordered by rank
+    pub fn warriors(&self) -> &[HillWarrior] {
+        &self.warriors
+    }
+
+    /// The total score of the warrior with the given rank against every other warrior on the hill
+    pub fn score(&self, rank: usize) -> usize {
+        self.results[rank].iter().sum()
+    }
+
+    /// The score the warrior ranked `a` got in its match against the warrior ranked `b`
+    pub fn result(&self, a: usize, b: usize) -> usize {
+        self.results[a][b]
+    }
+
+    /// Fights the challenger against every warrior on the hill, re-ranks the hill, and pushes off
+    /// the lowest ranked warrior if the hill is over its size.
+    /// When the challenger ties for last place with an incumbent, the challenger is pushed off.
+    /// Returns an error, leaving the hill unchanged, if the challenger can't play a match against
+    /// the incumbents with the settings of the hill.
+    pub fn challenge(
+        &mut self,
+        name: &str,
+        program: Vec<Instruction>,
+    ) -> Result<ChallengeResult, SettingsError> {
+        // The challenger is checked on its own too, as there are no matches on an empty hill
+        self.settings
+            .validate_warrior(&program)
+            .map_err(|e| SettingsError::Warrior(0, e))?;
+
+        let results = self
+            .warriors
+            .iter()
+            .map(|incumbent| {
+                run_match(
+                    &[program.clone(), incumbent.program.clone()],
+                    &self.settings,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut challenger_scores = Vec::with_capacity(self.warriors.len());
+        for (rank, result) in results.iter().enumerate() {
+            challenger_scores.push(result.scores[0].score());
+            self.results[rank].push(result.scores[1].score());
+        }
+
+        let mut challenger_results = challenger_scores.clone();
+        challenger_results.push(0);
+        self.results.push(challenger_results);
+
+        self.warriors.push(HillWarrior {
+            name: name.to_string(),
+            program,
+            age: 0,
+        });
+
+        let challenger = self.warriors.len() - 1;
+        let order = self.rerank();
+        let mut rank = order.iter().position(|i| *i == challenger);
+
+        let pushed_off = if self.warriors.len() > self.size {
+            let last = self.warriors.len() - 1;
+            let pushed_off = self.remove(last);
+
+            // Removing a warrior changes the total score of every other warrior, so re-rank
+            rank = match rank {
+                Some(rank) if rank != last => {
+                    let order = self.rerank();
+                    order.iter().position(|i| *i == rank)
+                }
+                _ => {
+                    self.rerank();
+                    None
+                }
+            };
+
+            Some(pushed_off)
+        } else {
+            None
+        };
+
+        // Every incumbent still on the hill has survived another challenge
+        for (i, warrior) in self.warriors.iter_mut().enumerate() {
+            if Some(i) != rank {
+                warrior.age += 1;
+            }
+        }
+
+        Ok(ChallengeResult {
+            rank,
+            scores: challenger_scores,
+            pushed_off,
+        })
+    }
+
+    /// Sorts the warriors by their total score.
+    /// Returns the previous rank of each warrior in the new order.
+    fn rerank(&mut self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.warriors.len()).collect();
+        // The sort is stable so a warrior stays ahead of any warrior with an equal score that
+        // joined the hill after it
+        order.sort_by_key(|i| Reverse(self.score(*i)));
+
+        self.warriors = order.iter().map(|i| self.warriors[*i].clone()).collect();
+        self.results = order
+            .iter()
+            .map(|a| order.iter().map(|b| self.results[*a][*b]).collect())
+            .collect();
+
+        order
+    }
+
+    /// Removes the warrior with the given rank (and its results)
+    fn remove(&mut self, rank: usize) -> HillWarrior {
+        self.results.remove(rank);
+        for row in &mut self.results {
+            row.remove(rank);
+        }
+
+        self.warriors.remove(rank)
+    }
+
+    /// Writes the hill in a plain text format that can be restored with `Hill::load`.
+    ///
+    /// The format is a `size` line followed by each warrior in rank order. Each warrior is a
+    /// `warrior <age> <name>` line, a `results` line holding the warrior's score against every
+    /// warrior in rank order, then one instruction per line, then an `end` line.
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "size {}", self.size)?;
+
+        for (warrior, results) in self.warriors.iter().zip(&self.results) {
+            writeln!(writer, "warrior {} {}", warrior.age, warrior.name)?;
+
+            let results: Vec<String> = results.iter().map(|r| r.to_string()).collect();
+            writeln!(writer, "results {}", results.join(" "))?;
+
+            // An instruction without a modifier would be given the compiler's default when it is
+            // loaded, so the modifier it is executed with is written instead
+            for instruction in &warrior.program {
+                let modifier = self.settings.execution_mode.resolve_modifier(instruction);
+                writeln!(
+                    writer,
+                    "{}",
+                    Instruction {
+                        modifier,
+                        ..*instruction
+                    }
+                )?;
+            }
+
+            writeln!(writer, "end")?;
+        }
+
+        Ok(())
+    }
+
+    /// Restores a hill written by `Hill::save`, using `settings` for all future challenges
+    pub fn load<R: BufRead>(reader: R, settings: MatchSettings) -> Result<Hill, HillError> {
+        let mut lines = reader.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+        let size = match lines.next() {
+            Some((line_num, line)) => {
+                let line = line?;
+                line.strip_prefix("size ")
+                    .and_then(|size| size.trim().parse().ok())
+                    .ok_or(HillError::InvalidLine(line_num))?
+            }
+            None => return Err(HillError::InvalidLine(1)),
+        };
+
+        if size == 0 {
+            return Err(HillError::InvalidLine(1));
+        }
+        let mut hill = Hill::new(size, settings);
+        // The line number of each warrior's results, to report results of the wrong length
+        let mut results_lines = Vec::new();
+
+        while let Some((line_num, line)) = lines.next() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // A hill never holds more warriors than its size
+            if hill.warriors.len() == size {
+                return Err(HillError::InvalidLine(line_num));
+            }
+
+            // warrior <age> <name>
+            let mut header = line
+                .strip_prefix("warrior ")
+                .ok_or(HillError::InvalidLine(line_num))?
+                .splitn(2, ' ');
+            let age = header
+                .next()
+                .and_then(|age| age.parse().ok())
+                .ok_or(HillError::InvalidLine(line_num))?;
+            let name = header.next().unwrap_or("").to_string();
+
+            // results <score> <score> ...
+            let (line_num, line) = lines.next().ok_or(HillError::InvalidLine(line_num + 1))?;
+            let line = line?;
+            let results = line
+                .strip_prefix("results")
+                .ok_or(HillError::InvalidLine(line_num))?
+                .split_whitespace()
+                .map(|r| r.parse().map_err(|_| HillError::InvalidLine(line_num)))
+                .collect::<Result<Vec<usize>, _>>()?;
+            results_lines.push(line_num);
+
+            let mut program = Vec::new();
+            loop {
+                let (line_num, line) = lines.next().ok_or(HillError::InvalidLine(line_num + 1))?;
+                let line = line?;
+
+                if line == "end" {
+                    break;
+                }
+
+                let instruction = parse_program(&line)
+                    .map_err(|e| HillError::InvalidInstruction(line_num, e.to_string()))?;
+                program.extend(instruction);
+            }
+
+            hill.warriors.push(HillWarrior { name, program, age });
+            hill.results.push(results);
+        }
+
+        // Every warrior needs a result against every warrior on the hill
+        if let Some(i) = hill
+            .results
+            .iter()
+            .position(|r| r.len() != hill.warriors.len())
+        {
+            return Err(HillError::InvalidLine(results_lines[i]));
+        }
+
+        Ok(hill)
+    }
+
+    /// Writes the hill to a file, see `Hill::save`
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        self.save(&mut file)
+    }
+
+    /// Restores a hill from a file, see `Hill::load`
+    pub fn load_from_file<P: AsRef<Path>>(
+        path: P,
+        settings: MatchSettings,
+    ) -> Result<Hill, HillError> {
+        Hill::load(BufReader::new(File::open(path)?), settings)
+    }
+}
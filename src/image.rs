@@ -0,0 +1,314 @@
+use crate::render::process_owners;
+use crate::{cell_char, VirtualMachine, WarriorStatistics};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The colour of each user, repeating for more users. This is the same order as the colours of
+/// `CoreRenderer`: six bright colours followed by darker versions of them.
+const PALETTE: [(u8, u8, u8); 12] = [
+    (230, 60, 60),
+    (60, 200, 60),
+    (230, 200, 40),
+    (70, 110, 240),
+    (210, 70, 210),
+    (50, 200, 210),
+    (150, 35, 35),
+    (35, 130, 35),
+    (150, 130, 25),
+    (45, 70, 160),
+    (140, 45, 140),
+    (30, 130, 140),
+];
+
+/// The brightness of a cell from its class (see `cell_char`), `owned` is whether any user has
+/// written to the cell so that owned empty cells are still visible
+fn shade(character: char, owned: bool) -> u8 {
+    match character {
+        '.' if owned => 48,
+        '.' => 0,
+        'd' => 96,
+        'n' => 128,
+        'p' => 144,
+        'c' => 160,
+        'j' => 184,
+        'a' => 208,
+        's' => 232,
+        _ => 255,
+    }
+}
+
+/// The colour of `user` as an SVG colour
+fn svg_colour(user: usize) -> String {
+    let (r, g, b) = PALETTE[user % PALETTE.len()];
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// The format of an image of the core. Both are binary Netpbm formats which most image tools can
+/// read or convert.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    /// A colour image (P6) where each cell has the colour of the user that last wrote to it and
+    /// the brightness of the class of its instruction
+    Ppm,
+    /// A greyscale image (P5) where each cell has the brightness of the class of its instruction
+    Pgm,
+}
+
+/// Draws the core of a VM as an image, one square per cell. Cells with a live process are white.
+/// Ownership is only known if it is being tracked (see `VirtualMachine::track_ownership`),
+/// otherwise every cell is grey.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoreImage {
+    /// The number of cells in each row
+    pub width: usize,
+    /// The width and height in pixels of each cell
+    pub scale: usize,
+    pub format: ImageFormat,
+}
+
+impl Default for CoreImage {
+    fn default() -> CoreImage {
+        CoreImage {
+            width: 100,
+            scale: 4,
+            format: ImageFormat::Ppm,
+        }
+    }
+}
+
+impl CoreImage {
+    /// The width and height in pixels of the image of a core of `size` cells
+    pub fn dimensions(&self, size: usize) -> (usize, usize) {
+        assert!(self.width > 0, "The width must be at least 1");
+        assert!(self.scale > 0, "The scale must be at least 1");
+
+        (
+            self.width * self.scale,
+            size.div_ceil(self.width) * self.scale,
+        )
+    }
+
+    /// Writes the image of the core of `vm`. Any space after the last cell is black.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, CoreImage, ImageFormat, VirtualMachine};
+    ///
+    /// let vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+    ///
+    /// let image = CoreImage { width: 5, scale: 1, format: ImageFormat::Pgm };
+    /// let mut bytes = Vec::new();
+    /// image.write(&vm, &mut bytes).unwrap();
+    ///
+    /// assert!(bytes.starts_with(b"P5\n5 2\n255\n"));
+    /// assert_eq!(bytes.len(), 11 + 10);
+    /// ```
+    pub fn write<W: Write>(&self, vm: &VirtualMachine, writer: &mut W) -> io::Result<()> {
+        let memory = vm.get_memory();
+        let (width, height) = self.dimensions(memory.len());
+        let processes = process_owners(vm);
+        let writers = vm.get_ownership().map(|ownership| ownership.writers());
+
+        let magic = match self.format {
+            ImageFormat::Ppm => "P6",
+            ImageFormat::Pgm => "P5",
+        };
+        write!(writer, "{}\n{} {}\n255\n", magic, width, height)?;
+
+        let pixel = |cell: usize| -> (u8, u8, u8) {
+            if cell >= memory.len() {
+                return (0, 0, 0);
+            }
+            if processes[cell].is_some() {
+                return (255, 255, 255);
+            }
+
+            let owner = writers.and_then(|writers| writers[cell]);
+            let shade = shade(cell_char(&memory[cell]), owner.is_some());
+            match (self.format, owner) {
+                (ImageFormat::Ppm, Some(owner)) => {
+                    let (r, g, b) = PALETTE[owner % PALETTE.len()];
+                    let scale = |channel: u8| (channel as usize * shade as usize / 255) as u8;
+                    (scale(r), scale(g), scale(b))
+                }
+                _ => (shade, shade, shade),
+            }
+        };
+
+        let mut row = Vec::with_capacity(width * 3);
+        for cell_row in 0..height / self.scale {
+            row.clear();
+            for cell in cell_row * self.width..(cell_row + 1) * self.width {
+                let (r, g, b) = pixel(cell);
+                for _ in 0..self.scale {
+                    match self.format {
+                        ImageFormat::Ppm => row.extend_from_slice(&[r, g, b]),
+                        ImageFormat::Pgm => row.push(r),
+                    }
+                }
+            }
+
+            for _ in 0..self.scale {
+                writer.write_all(&row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the image of the core of `vm` into a file at `path`, see `write`
+    pub fn save_to_file<P: AsRef<Path>>(&self, vm: &VirtualMachine, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(vm, &mut writer)?;
+        writer.flush()
+    }
+}
+
+/// Draws the number of processes of each warrior over a battle as an SVG line chart, using
+/// `WarriorStatistics::process_counts`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessChart {
+    /// The width of the chart in pixels
+    pub width: usize,
+    /// The height of the chart in pixels
+    pub height: usize,
+}
+
+impl Default for ProcessChart {
+    fn default() -> ProcessChart {
+        ProcessChart {
+            width: 640,
+            height: 320,
+        }
+    }
+}
+
+impl ProcessChart {
+    const LEFT: f64 = 50.0;
+    const RIGHT: f64 = 110.0;
+    const TOP: f64 = 20.0;
+    const BOTTOM: f64 = 30.0;
+
+    /// Writes the chart of the statistics of each warrior in a battle (e.g.
+    /// `RoundResult::statistics`). The chart ends at the last cycle any of the warriors executed.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, run_round, MatchSettings, ProcessChart};
+    ///
+    /// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    /// let spl = create_program! {
+    ///     SPL(B, 0, Direct, 0, Immediate)
+    ///     MOV(I, 0, Direct, 1, PostIncrementIndirectA)
+    /// };
+    /// let settings = MatchSettings { statistics: true, max_cycles: 1000, ..Default::default() };
+    /// let round = run_round(&[imp, spl], &settings, 0, 1);
+    ///
+    /// let svg = ProcessChart::default().to_svg(&round.statistics.unwrap());
+    /// assert_eq!(svg.matches("<polyline").count(), 2);
+    /// ```
+    pub fn write_svg<W: Write>(
+        &self,
+        warriors: &[WarriorStatistics],
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let end = warriors
+            .iter()
+            .map(|warrior| warrior.last_cycle)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let peak = warriors
+            .iter()
+            .flat_map(|warrior| warrior.process_counts.iter().map(|(_, count)| *count))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let (width, height) = (self.width as f64, self.height as f64);
+        let plot_width = (width - Self::LEFT - Self::RIGHT).max(1.0);
+        let plot_height = (height - Self::TOP - Self::BOTTOM).max(1.0);
+        let x = |cycle: usize| Self::LEFT + cycle as f64 / end as f64 * plot_width;
+        let y = |count: usize| Self::TOP + plot_height - count as f64 / peak as f64 * plot_height;
+        let (left, right, top, bottom) = (x(0), x(end), y(peak), y(0));
+
+        writeln!(
+            writer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="monospace" font-size="11">"#,
+            self.width, self.height, self.width, self.height
+        )?;
+        writeln!(
+            writer,
+            r#"<rect width="{}" height="{}" fill="white"/>"#,
+            self.width, self.height
+        )?;
+        writeln!(
+            writer,
+            r#"<path d="M{:.1} {:.1}V{:.1}H{:.1}" fill="none" stroke="black"/>"#,
+            left, top, bottom, right
+        )?;
+        writeln!(
+            writer,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="end">{}</text>"#,
+            left - 4.0,
+            top + 4.0,
+            peak
+        )?;
+        writeln!(
+            writer,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="end">0</text>"#,
+            left - 4.0,
+            bottom
+        )?;
+        writeln!(
+            writer,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="end">{} cycles</text>"#,
+            right,
+            bottom + 16.0,
+            end
+        )?;
+
+        for (user, warrior) in warriors.iter().enumerate() {
+            let colour = svg_colour(user);
+
+            // The count only changes at the recorded cycles so the line is drawn as steps
+            let mut points = Vec::new();
+            let mut previous = None;
+            for (cycle, count) in &warrior.process_counts {
+                if let Some(previous) = previous {
+                    points.push(format!("{:.1},{:.1}", x(*cycle), y(previous)));
+                }
+                points.push(format!("{:.1},{:.1}", x(*cycle), y(*count)));
+                previous = Some(*count);
+            }
+            if let Some(previous) = previous {
+                points.push(format!("{:.1},{:.1}", right, y(previous)));
+            }
+
+            writeln!(
+                writer,
+                r#"<polyline points="{}" fill="none" stroke="{}"/>"#,
+                points.join(" "),
+                colour
+            )?;
+            writeln!(
+                writer,
+                r#"<text x="{:.1}" y="{:.1}" fill="{}">warrior {}</text>"#,
+                right + 10.0,
+                top + 8.0 + 14.0 * user as f64,
+                colour,
+                user
+            )?;
+        }
+
+        writeln!(writer, "</svg>")
+    }
+
+    /// Creates the chart as a string, see `write_svg`
+    pub fn to_svg(&self, warriors: &[WarriorStatistics]) -> String {
+        let mut bytes = Vec::new();
+        self.write_svg(warriors, &mut bytes)
+            .expect("Writing to a Vec can't fail");
+        String::from_utf8(bytes).expect("The chart is valid UTF-8")
+    }
+}
@@ -60,6 +60,10 @@ pub enum OpCode {
     SEQ,
     SNE,
     SLT,
+    /// Loads a value from P-space
+    LDP,
+    /// Stores a value in P-space
+    STP,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
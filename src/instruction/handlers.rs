@@ -9,6 +9,9 @@ pub use general::*;
 mod operators;
 pub use operators::*;
 
+mod pspace;
+pub use pspace::*;
+
 #[inline]
 pub fn relative_address(max: usize, a: usize, b: isize) -> usize {
     let max = max as isize;
@@ -18,6 +21,51 @@ pub fn relative_address(max: usize, a: usize, b: isize) -> usize {
     ((((a + b) % max) + max) % max) as usize
 }
 
+/// The furthest distances from the current instruction that reads and writes can reach.
+/// A limit equal to the size of the core means there is no limit.
+///
+/// The handlers use the write limit for the B operand of instructions that write through it
+/// (and for the pointers of increments and decrements), and the read limit everywhere else.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub read: usize,
+    pub write: usize,
+}
+
+impl Limits {
+    /// Limits that allow reading and writing anywhere in a core of size `max`
+    pub fn unlimited(max: usize) -> Limits {
+        Limits {
+            read: max,
+            write: max,
+        }
+    }
+}
+
+/// Folds a relative offset into the window of `limit` instructions around the current instruction
+/// following the ICWS '94 draft. Like pMARS the offset is first reduced to the core, so offsets
+/// that are the same cell fold to the same cell even if `limit` doesn't divide `max`.
+/// The result is an offset in the range `0..max`.
+/// # Example
+/// ```
+/// use darwin_lib::handlers::fold;
+/// // With a limit of 400 in a core of 8000, an offset of 250 becomes -150
+/// assert_eq!(fold(250, 400, 8000), 8000 - 150);
+/// assert_eq!(fold(150, 400, 8000), 150);
+/// // Without a limit the offset is unchanged
+/// assert_eq!(fold(250, 8000, 8000), 250);
+/// ```
+#[inline]
+pub fn fold(offset: isize, limit: usize, max: usize) -> usize {
+    let result = offset.rem_euclid(max as isize) as usize % limit;
+
+    if result > limit / 2 {
+        result + max - limit
+    } else {
+        result
+    }
+}
+
 #[inline]
 pub fn follow_address(
     reg: isize,
@@ -25,23 +73,39 @@ pub fn follow_address(
     cur_address: usize,
     max: usize,
     memory: &[Instruction],
+) -> usize {
+    follow_address_with_limit(reg, mode, cur_address, max, max, memory)
+}
+
+/// Behaves like `follow_address`, but the addresses are folded into the window of `limit`
+/// instructions around the current instruction
+#[inline]
+pub fn follow_address_with_limit(
+    reg: isize,
+    mode: AddressMode,
+    cur_address: usize,
+    max: usize,
+    limit: usize,
+    memory: &[Instruction],
 ) -> usize {
     use AddressMode::*;
 
+    // The offset of the pointer from the current instruction
+    let offset = fold(reg, limit, max);
+    let index = (cur_address + offset) % max;
+
     match mode {
         // Direct will always return an insutrction
-        Direct => relative_address(max, cur_address, reg),
+        Direct => index,
         // Immediate will always return a value
         Immediate => cur_address,
         IndirectA | PreDecrementIndirectA | PostIncrementIndirectA => {
-            let index = relative_address(max, cur_address, reg);
-            let instruction = memory[index];
-            relative_address(max, index, instruction.a_reg)
+            let offset = fold(offset as isize + memory[index].a_reg, limit, max);
+            (cur_address + offset) % max
         }
         IndirectB | PreDecrementIndirectB | PostIncrementIndirectB => {
-            let index = relative_address(max, cur_address, reg);
-            let instruction = memory[index];
-            relative_address(max, index, instruction.b_reg)
+            let offset = fold(offset as isize + memory[index].b_reg, limit, max);
+            (cur_address + offset) % max
         }
     }
 }
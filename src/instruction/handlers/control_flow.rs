@@ -1,23 +1,25 @@
 use crate::{Instruction, Modifier};
 
-use super::follow_address;
+use super::{follow_address_with_limit, Limits};
 
 pub fn jmp(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &[Instruction],
 ) -> usize {
     // We completely ignore the modifier and the b mode
     let Instruction { a_reg, a_mode, .. } = instruction;
 
-    follow_address(a_reg, a_mode, cur_address, max, memory)
+    follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory)
 }
 
 pub fn jmz(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &[Instruction],
 ) -> Option<usize> {
     use Modifier as m;
@@ -32,7 +34,8 @@ pub fn jmz(
     } = instruction;
 
     // The index of the address that will be tested (b reg)
-    let test_index = follow_address(b_reg, b_mode, cur_address, max, memory);
+    let test_index =
+        follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.read, memory);
 
     // Match arm will return true if it should jump (false otherwise)
     if match modifier {
@@ -41,7 +44,14 @@ pub fn jmz(
         m::F | m::X | m::I => memory[test_index].a_reg == 0 && memory[test_index].b_reg == 0,
         m::None => panic!("Invalid modifier `None` for JMZ"),
     } {
-        Some(follow_address(a_reg, a_mode, cur_address, max, memory))
+        Some(follow_address_with_limit(
+            a_reg,
+            a_mode,
+            cur_address,
+            max,
+            limits.read,
+            memory,
+        ))
     } else {
         None
     }
@@ -51,6 +61,7 @@ pub fn jmn(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &[Instruction],
 ) -> Option<usize> {
     use Modifier as m;
@@ -65,7 +76,8 @@ pub fn jmn(
     } = instruction;
 
     // The index of the address that will be tested (b reg)
-    let test_index = follow_address(b_reg, b_mode, cur_address, max, memory);
+    let test_index =
+        follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.read, memory);
 
     // Match arm will return true if it should jump (false otherwise)
     if match modifier {
@@ -75,7 +87,14 @@ pub fn jmn(
         m::F | m::X | m::I => memory[test_index].a_reg != 0 && memory[test_index].b_reg != 0,
         m::None => panic!("Invalid modifier `None` for JMN"),
     } {
-        Some(follow_address(a_reg, a_mode, cur_address, max, memory))
+        Some(follow_address_with_limit(
+            a_reg,
+            a_mode,
+            cur_address,
+            max,
+            limits.read,
+            memory,
+        ))
     } else {
         None
     }
@@ -85,6 +104,7 @@ pub fn djn(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut [Instruction],
 ) -> Option<usize> {
     use Modifier as m;
@@ -99,7 +119,9 @@ pub fn djn(
     } = instruction;
 
     // The index of the address that will be tested (b reg)
-    let test_index = follow_address(b_reg, b_mode, cur_address, max, memory);
+    // The tested instruction is decremented so it uses the write limit
+    let test_index =
+        follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
     // Match arm will return true if it should jump (false otherwise)
     if match modifier {
@@ -119,7 +141,14 @@ pub fn djn(
         }
         m::None => panic!("Invalid modifier `None` for DJN"),
     } {
-        Some(follow_address(a_reg, a_mode, cur_address, max, memory))
+        Some(follow_address_with_limit(
+            a_reg,
+            a_mode,
+            cur_address,
+            max,
+            limits.read,
+            memory,
+        ))
     } else {
         None
     }
@@ -129,6 +158,7 @@ pub fn seq(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &[Instruction],
 ) -> bool {
     use Modifier as m;
@@ -143,8 +173,8 @@ pub fn seq(
     } = instruction;
 
     // The indices of the registers to check
-    let a_index = follow_address(a_reg, a_mode, cur_address, max, memory);
-    let b_index = follow_address(b_reg, b_mode, cur_address, max, memory);
+    let a_index = follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+    let b_index = follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.read, memory);
 
     // Match arm will return true if it should skip (false otherwise)
     match modifier {
@@ -171,6 +201,7 @@ pub fn sne(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &[Instruction],
 ) -> bool {
     use Modifier as m;
@@ -185,8 +216,8 @@ pub fn sne(
     } = instruction;
 
     // The indices of the registers to check
-    let a_index = follow_address(a_reg, a_mode, cur_address, max, memory);
-    let b_index = follow_address(b_reg, b_mode, cur_address, max, memory);
+    let a_index = follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+    let b_index = follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.read, memory);
 
     // Match arm will return true if it should skip (false otherwise)
     match modifier {
@@ -211,6 +242,7 @@ pub fn slt(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &[Instruction],
 ) -> bool {
     use Modifier as m;
@@ -225,8 +257,8 @@ pub fn slt(
     } = instruction;
 
     // The indices of the registers to check
-    let a_index = follow_address(a_reg, a_mode, cur_address, max, memory);
-    let b_index = follow_address(b_reg, b_mode, cur_address, max, memory);
+    let a_index = follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+    let b_index = follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.read, memory);
 
     // Match arm will return true if it should skip (false otherwise)
     match modifier {
@@ -251,10 +283,11 @@ pub fn spl(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &[Instruction],
 ) -> usize {
     // We completely ignore the modifier and the b mode
     let Instruction { a_reg, a_mode, .. } = instruction;
 
-    follow_address(a_reg, a_mode, cur_address, max, memory)
+    follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory)
 }
@@ -1,11 +1,13 @@
 use crate::{Instruction, Modifier};
 
-use super::follow_address;
+use super::{follow_address_with_limit, Limits};
 
+#[allow(clippy::ptr_arg)]
 pub fn mov(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut Vec<Instruction>,
 ) {
     use Modifier as m;
@@ -21,45 +23,59 @@ pub fn mov(
 
     match modifier {
         m::I => {
-            let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-            let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+            let source =
+                follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+            let destination =
+                follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
             memory[destination] = memory[source];
         }
         m::A => {
-            let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-            let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+            let source =
+                follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+            let destination =
+                follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
             memory[destination].a_reg = memory[source].a_reg;
         }
         m::B => {
-            let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-            let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+            let source =
+                follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+            let destination =
+                follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
             memory[destination].b_reg = memory[source].b_reg;
         }
         m::AB => {
-            let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-            let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+            let source =
+                follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+            let destination =
+                follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
             memory[destination].b_reg = memory[source].a_reg;
         }
         m::BA => {
-            let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-            let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+            let source =
+                follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+            let destination =
+                follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
             memory[destination].a_reg = memory[source].b_reg;
         }
         m::F => {
-            let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-            let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+            let source =
+                follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+            let destination =
+                follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
             memory[destination].a_reg = memory[source].a_reg;
             memory[destination].b_reg = memory[source].b_reg;
         }
         m::X => {
-            let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-            let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+            let source =
+                follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+            let destination =
+                follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
 
             memory[destination].a_reg = memory[source].b_reg;
             memory[destination].b_reg = memory[source].a_reg;
@@ -1,6 +1,6 @@
 use crate::{Instruction, Modifier};
 
-use super::follow_address;
+use super::{follow_address_with_limit, Limits};
 
 /// Performs an arithmetic operation between two memory locations. Should not be used if operation
 /// can result in division by zero.
@@ -50,10 +50,12 @@ macro_rules! try_op {
 }
 
 /// Helper function that returns the source and destination addresses as a tuple
+#[allow(clippy::ptr_arg)]
 fn get_source_destination(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut Vec<Instruction>,
 ) -> (usize, usize) {
     let Instruction {
@@ -64,8 +66,10 @@ fn get_source_destination(
         ..
     } = instruction;
 
-    let source = follow_address(a_reg, a_mode, cur_address, max, memory);
-    let destination = follow_address(b_reg, b_mode, cur_address, max, memory);
+    // The destination is written to so it uses the write limit
+    let source = follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+    let destination =
+        follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
     (source, destination)
 }
 
@@ -73,9 +77,11 @@ pub fn add(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut Vec<Instruction>,
 ) {
-    let (source, destination) = get_source_destination(instruction, cur_address, max, memory);
+    let (source, destination) =
+        get_source_destination(instruction, cur_address, max, limits, memory);
     perform_operation!(
         instruction.modifier,
         memory[source],
@@ -89,9 +95,11 @@ pub fn sub(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut Vec<Instruction>,
 ) {
-    let (source, destination) = get_source_destination(instruction, cur_address, max, memory);
+    let (source, destination) =
+        get_source_destination(instruction, cur_address, max, limits, memory);
     perform_operation!(
         instruction.modifier,
         memory[source],
@@ -105,9 +113,11 @@ pub fn mul(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut Vec<Instruction>,
 ) {
-    let (source, destination) = get_source_destination(instruction, cur_address, max, memory);
+    let (source, destination) =
+        get_source_destination(instruction, cur_address, max, limits, memory);
 
     perform_operation!(
         instruction.modifier,
@@ -118,13 +128,16 @@ pub fn mul(
     );
 }
 
+#[allow(clippy::partialeq_to_none)]
 pub fn div(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut Vec<Instruction>,
 ) -> bool {
-    let (source, destination) = get_source_destination(instruction, cur_address, max, memory);
+    let (source, destination) =
+        get_source_destination(instruction, cur_address, max, limits, memory);
     use Modifier as m;
     match instruction.modifier {
         m::A => {
@@ -175,13 +188,16 @@ pub fn div(
     true
 }
 
+#[allow(clippy::partialeq_to_none)]
 pub fn modulo(
     instruction: Instruction,
     cur_address: usize,
     max: usize,
+    limits: Limits,
     memory: &mut Vec<Instruction>,
 ) -> bool {
-    let (source, destination) = get_source_destination(instruction, cur_address, max, memory);
+    let (source, destination) =
+        get_source_destination(instruction, cur_address, max, limits, memory);
     use Modifier as m;
     match instruction.modifier {
         m::A => {
@@ -0,0 +1,78 @@
+use crate::{Instruction, Modifier, PSpace};
+
+use super::{follow_address_with_limit, Limits};
+
+pub fn ldp(
+    instruction: Instruction,
+    cur_address: usize,
+    max: usize,
+    limits: Limits,
+    memory: &mut [Instruction],
+    pspace: &PSpace,
+    user: usize,
+) {
+    use Modifier as m;
+
+    let Instruction {
+        a_reg,
+        a_mode,
+        b_reg,
+        b_mode,
+        modifier,
+        ..
+    } = instruction;
+
+    // The A value is the index of the P-space location that is loaded into the B target
+    let source = follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+    let destination =
+        follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.write, memory);
+
+    let load = |index: isize| pspace.load(user, index).rem_euclid(max as isize);
+
+    match modifier {
+        m::A => memory[destination].a_reg = load(memory[source].a_reg),
+        // F, X and I behave like B
+        m::B | m::F | m::X | m::I => memory[destination].b_reg = load(memory[source].b_reg),
+        m::AB => memory[destination].b_reg = load(memory[source].a_reg),
+        m::BA => memory[destination].a_reg = load(memory[source].b_reg),
+        m::None => panic!("Invalid modifier `None` for LDP"),
+    }
+}
+
+pub fn stp(
+    instruction: Instruction,
+    cur_address: usize,
+    max: usize,
+    limits: Limits,
+    memory: &[Instruction],
+    pspace: &mut PSpace,
+    user: usize,
+) {
+    use Modifier as m;
+
+    let Instruction {
+        a_reg,
+        a_mode,
+        b_reg,
+        b_mode,
+        modifier,
+        ..
+    } = instruction;
+
+    // The A value is stored in the P-space location given by the B value
+    let source = follow_address_with_limit(a_reg, a_mode, cur_address, max, limits.read, memory);
+    // The B value is only read so it uses the read limit
+    let destination =
+        follow_address_with_limit(b_reg, b_mode, cur_address, max, limits.read, memory);
+
+    let (value, index) = match modifier {
+        m::A => (memory[source].a_reg, memory[destination].a_reg),
+        // F, X and I behave like B
+        m::B | m::F | m::X | m::I => (memory[source].b_reg, memory[destination].b_reg),
+        m::AB => (memory[source].a_reg, memory[destination].b_reg),
+        m::BA => (memory[source].b_reg, memory[destination].a_reg),
+        m::None => panic!("Invalid modifier `None` for STP"),
+    };
+
+    pspace.store(user, index, value.rem_euclid(max as isize));
+}
@@ -1,7 +1,23 @@
+mod battle;
 mod compiler;
+mod debugger;
+mod hill;
+mod image;
 mod instruction;
+mod parallel;
+mod render;
+mod replay;
+mod tournament;
 mod virtual_machine;
 
+pub use battle::*;
 pub use compiler::*;
+pub use debugger::*;
+pub use hill::*;
+pub use image::*;
 pub use instruction::*;
+pub use parallel::SplitMix64;
+pub use render::*;
+pub use replay::*;
+pub use tournament::*;
 pub use virtual_machine::*;
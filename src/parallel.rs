@@ -0,0 +1,117 @@
+use rand::RngCore;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Runs `f` for every job in `0..jobs`, spreading the jobs over `threads` threads.
+/// The results are returned in job order regardless of which thread ran each job.
+pub(crate) fn parallel_map<T, F>(jobs: usize, threads: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let threads = threads.max(1).min(jobs);
+
+    if threads <= 1 {
+        return (0..jobs).map(f).collect();
+    }
+
+    // Threads take the next job from this counter until there are none left, so a few slow jobs
+    // don't hold up a whole thread's share of the work
+    let next_job = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, T)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+
+                    loop {
+                        let job = next_job.fetch_add(1, Ordering::Relaxed);
+                        if job >= jobs {
+                            return results;
+                        }
+
+                        results.push((job, f(job)));
+                    }
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("A worker thread panicked"))
+            .collect()
+    });
+
+    results.sort_by_key(|(job, _)| *job);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// The amount the state of `SplitMix64` increases by for each number
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// The splitmix64 mixing function, which turns a state into an unrelated looking number
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Derives an independent seed for the sub task `index` (such as a round) from `seed`.
+/// This is number `index` that `SplitMix64::new(seed)` generates, so neighbouring indices produce
+/// unrelated seeds.
+pub(crate) fn derive_seed(seed: u64, index: u64) -> u64 {
+    mix(seed.wrapping_add(index.wrapping_add(1).wrapping_mul(GOLDEN_GAMMA)))
+}
+
+/// The splitmix64 random number generator, which is what warriors are placed with when a seed is
+/// given. `StdRng` can change its algorithm between versions of rand, but this generates the same
+/// numbers from a seed forever, so a seed always places warriors in the same places.
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, MatchSettings, Replay, SplitMix64, VirtualMachine};
+///
+/// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+/// let programs = [imp.clone(), imp];
+/// let settings = MatchSettings { max_cycles: 10, ..Default::default() };
+///
+/// // The warriors are placed the same way as a recorded round with the same seed
+/// let vm = VirtualMachine::new_battle_with_rng(&programs, &settings, &mut SplitMix64::new(5));
+/// let replay = Replay::record(&programs, &settings, 5, 1);
+/// assert_eq!(vm.get_users_pcs()[1][0], replay.placements[1]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a generator that starts from `seed`
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+}
+
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix(self.state)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{Instruction, OpCode, VirtualMachine};
+
+use std::io::{self, Write};
+
+/// The ANSI foreground colour of each user's cells, repeating for more users
+const COLOURS: [u8; 12] = [91, 92, 93, 94, 95, 96, 31, 32, 33, 34, 35, 36];
+const RESET: &str = "\x1b[0m";
+const REVERSE: &str = "\x1b[7m";
+
+/// The character of a cell, which shows the class of its instruction:
+///
+/// | Class               | Op codes                | Character |
+/// |---------------------|-------------------------|-----------|
+/// | Empty               | `DAT 0, 0`              | `.`       |
+/// | Data (bombs)        | `DAT`                   | `d`       |
+/// | Moves               | `MOV`                   | `m`       |
+/// | Arithmetic          | `ADD SUB MUL DIV MOD`   | `a`       |
+/// | Jumps               | `JMP JMZ JMN DJN`       | `j`       |
+/// | Splits              | `SPL`                   | `s`       |
+/// | Comparisons         | `SEQ SNE SLT`           | `c`       |
+/// | No-ops              | `NOP`                   | `n`       |
+/// | P-space             | `LDP STP`               | `p`       |
+pub fn cell_char(instruction: &Instruction) -> char {
+    use OpCode::*;
+
+    match instruction.op_code {
+        DAT if instruction.a_reg == 0 && instruction.b_reg == 0 => '.',
+        DAT => 'd',
+        MOV => 'm',
+        ADD | SUB | MUL | DIV | MOD => 'a',
+        JMP | JMZ | JMN | DJN => 'j',
+        SPL => 's',
+        SEQ | SNE | SLT => 'c',
+        NOP => 'n',
+        LDP | STP => 'p',
+    }
+}
+
+/// The user with a live process at each cell of the core of `vm`
+pub(crate) fn process_owners(vm: &VirtualMachine) -> Vec<Option<usize>> {
+    let mut processes = vec![None; vm.get_memory().len()];
+    for (user, queue) in vm.get_users_pcs().iter().enumerate() {
+        for pc in queue {
+            processes[*pc] = Some(user);
+        }
+    }
+    processes
+}
+
+/// Draws the core of a VM as a grid of characters, see `cell_char`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoreRenderer {
+    /// The number of characters in each row
+    pub width: usize,
+    /// The maximum number of rows, `None` for as many as are needed to show one cell per
+    /// character. If the core doesn't fit then each character shows several cells.
+    pub height: Option<usize>,
+    /// Whether to use ANSI escape codes. Cells are coloured by the user that last wrote to them
+    /// (see `VirtualMachine::track_ownership`) and cells with a live process are shown in reverse.
+    /// Without colour the cells with a live process are shown in upper case (or `*` if empty).
+    pub colour: bool,
+}
+
+impl Default for CoreRenderer {
+    fn default() -> CoreRenderer {
+        CoreRenderer {
+            width: 80,
+            height: None,
+            colour: true,
+        }
+    }
+}
+
+/// What a single character of the grid shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Glyph {
+    character: char,
+    owner: Option<usize>,
+    process: bool,
+}
+
+impl CoreRenderer {
+    /// The number of cells each character shows for a core of `size` cells
+    pub fn cells_per_char(&self, size: usize) -> usize {
+        assert!(self.width > 0, "The width must be at least 1");
+
+        match self.height {
+            Some(height) => {
+                assert!(height > 0, "The height must be at least 1");
+                let chars = self.width * height;
+                size.div_ceil(chars).max(1)
+            }
+            None => 1,
+        }
+    }
+
+    /// Writes the grid followed by a status line with the cycle count and the number of processes
+    /// of each user
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, CoreRenderer, VirtualMachine};
+    ///
+    /// let mut vm = VirtualMachine::new_simple(20, create_program! { MOV(I, 0, Direct, 1, Direct) });
+    /// vm.cycle();
+    ///
+    /// let renderer = CoreRenderer { width: 10, height: None, colour: false };
+    /// assert_eq!(
+    ///     renderer.render_to_string(&vm),
+    ///     "mM........\n..........\ncycle 1  0:1\n"
+    /// );
+    /// ```
+    pub fn render<W: Write>(&self, vm: &VirtualMachine, writer: &mut W) -> io::Result<()> {
+        let glyphs = self.glyphs(vm);
+
+        for row in glyphs.chunks(self.width) {
+            let mut colour = None;
+
+            for glyph in row {
+                if self.colour {
+                    let code = glyph.owner.map(|owner| COLOURS[owner % COLOURS.len()]);
+                    let style = (code, glyph.process);
+
+                    if colour != Some(style) {
+                        write!(writer, "{}", RESET)?;
+                        if let Some(code) = code {
+                            write!(writer, "\x1b[{}m", code)?;
+                        }
+                        if glyph.process {
+                            write!(writer, "{}", REVERSE)?;
+                        }
+                        colour = Some(style);
+                    }
+
+                    write!(writer, "{}", glyph.character)?;
+                } else if glyph.process {
+                    let character = match glyph.character {
+                        '.' => '*',
+                        character => character.to_ascii_uppercase(),
+                    };
+                    write!(writer, "{}", character)?;
+                } else {
+                    write!(writer, "{}", glyph.character)?;
+                }
+            }
+
+            if self.colour {
+                write!(writer, "{}", RESET)?;
+            }
+            writeln!(writer)?;
+        }
+
+        self.render_status(vm, writer)
+    }
+
+    /// Writes the status line on its own, see `render`
+    pub fn render_status<W: Write>(&self, vm: &VirtualMachine, writer: &mut W) -> io::Result<()> {
+        write!(writer, "cycle {}", vm.get_cycle_count())?;
+
+        for (user, queue) in vm.get_users_pcs().iter().enumerate() {
+            if self.colour {
+                write!(
+                    writer,
+                    "  \x1b[{}m{}:{}{}",
+                    COLOURS[user % COLOURS.len()],
+                    user,
+                    queue.len(),
+                    RESET
+                )?;
+            } else {
+                write!(writer, "  {}:{}", user, queue.len())?;
+            }
+        }
+
+        writeln!(writer)
+    }
+
+    /// Renders the VM into a string, see `render`
+    pub fn render_to_string(&self, vm: &VirtualMachine) -> String {
+        let mut bytes = Vec::new();
+        self.render(vm, &mut bytes)
+            .expect("Writing to a Vec can't fail");
+        String::from_utf8(bytes).expect("The grid is valid UTF-8")
+    }
+
+    /// Works out what each character of the grid shows
+    fn glyphs(&self, vm: &VirtualMachine) -> Vec<Glyph> {
+        let memory = vm.get_memory();
+        let scale = self.cells_per_char(memory.len());
+
+        let processes = process_owners(vm);
+
+        let ownership = vm.get_ownership();
+
+        (0..memory.len())
+            .step_by(scale)
+            .map(|start| {
+                let cells = start..(start + scale).min(memory.len());
+
+                // A character that shows several cells shows the most interesting one: a cell
+                // with a process, otherwise the most recently touched cell that isn't empty
+                let cell = cells
+                    .clone()
+                    .find(|cell| processes[*cell].is_some())
+                    .or_else(|| {
+                        let touched = |cell: &usize| {
+                            ownership.and_then(|ownership| ownership.last_touched()[*cell])
+                        };
+                        cells
+                            .clone()
+                            .filter(|cell| cell_char(&memory[*cell]) != '.')
+                            .max_by_key(touched)
+                    })
+                    .unwrap_or(start);
+
+                Glyph {
+                    character: cell_char(&memory[cell]),
+                    owner: ownership
+                        .and_then(|ownership| ownership.writers()[cell])
+                        .or(processes[cell]),
+                    process: processes[cell].is_some(),
+                }
+            })
+            .collect()
+    }
+}
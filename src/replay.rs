@@ -0,0 +1,394 @@
+use crate::battle::{match_teams, run_team_battle_with};
+use crate::parallel::SplitMix64;
+use crate::virtual_machine::{Encode, SnapshotError};
+use crate::{ExecutionMode, Handicap, Instruction, MatchSettings, VirtualMachine};
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// The bytes every replay file starts with
+const MAGIC: &[u8; 4] = b"DWRP";
+/// The version of the replay format
+const VERSION: u8 = 2;
+
+/// An error that occurred while loading a replay
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The replay couldn't be read
+    Io(io::Error),
+    /// The data is not a replay
+    NotAReplay,
+    /// The replay was written by an unsupported version of the format. Holds the version.
+    UnsupportedVersion(u8),
+    /// The replay is corrupt. Holds a description of the problem.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "Couldn't read the replay: {}", e),
+            ReplayError::NotAReplay => write!(f, "The data is not a battle replay"),
+            ReplayError::UnsupportedVersion(v) => {
+                write!(f, "Version {} of the replay format is not supported", v)
+            }
+            ReplayError::Invalid(e) => write!(f, "The replay is invalid: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> ReplayError {
+        ReplayError::Io(e)
+    }
+}
+
+impl From<SnapshotError> for ReplayError {
+    fn from(e: SnapshotError) -> ReplayError {
+        match e {
+            SnapshotError::Io(e) => ReplayError::Io(e),
+            SnapshotError::Invalid(e) => ReplayError::Invalid(e),
+            _ => ReplayError::Invalid("unexpected header"),
+        }
+    }
+}
+
+/// A replayed battle ran differently to the recorded battle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    /// The cycle (see `VirtualMachine::get_cycle_count`) where the difference was found
+    pub cycle: usize,
+    /// The state hash that was recorded
+    pub expected: u64,
+    /// The state hash of the replayed battle
+    pub found: u64,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The replay diverged at cycle {}: expected state {:016x} but found {:016x}",
+            self.cycle, self.expected, self.found
+        )
+    }
+}
+
+/// Everything needed to run a battle again exactly as it was recorded, see `Replay::record`
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// The settings of the battle. Only the settings that affect a single battle are saved.
+    pub settings: MatchSettings,
+    /// The seed that was used to place the warriors
+    pub seed: u64,
+    /// The programs of the warriors
+    pub warriors: Vec<Vec<Instruction>>,
+    /// The address each warrior was placed at
+    pub placements: Vec<usize>,
+    /// The number of VM cycles between each state hash
+    pub hash_interval: usize,
+    /// The state hash (see `VirtualMachine::state_hash`) after every `hash_interval` VM cycles
+    pub hashes: Vec<u64>,
+    /// The number of VM cycles (instructions) that were run
+    pub instructions: usize,
+    /// The number of battle cycles that were run, see `run_battle`
+    pub cycles: usize,
+    /// The warriors that survived the battle
+    pub survivors: Vec<usize>,
+    /// The state hash at the end of the battle
+    pub final_hash: u64,
+}
+
+impl Replay {
+    /// Runs a battle between the programs, recording it so that it can be replayed. The battle is
+    /// the same as `run_round(programs, settings, 0, seed)`.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, MatchSettings, Replay, ReplayPlayer};
+    ///
+    /// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    /// let dat = create_program! { DAT(F, 0, Immediate, 0, Immediate) };
+    ///
+    /// let replay = Replay::record(&[imp, dat], &MatchSettings::default(), 42, 100);
+    /// assert_eq!(replay.survivors, vec![0]);
+    ///
+    /// let bytes = replay.to_bytes();
+    /// let mut player = ReplayPlayer::new(Replay::from_bytes(&bytes).unwrap());
+    /// assert_eq!(player.play(), Ok(()));
+    /// ```
+    pub fn record(
+        programs: &[Vec<Instruction>],
+        settings: &MatchSettings,
+        seed: u64,
+        hash_interval: usize,
+    ) -> Replay {
+        assert!(hash_interval > 0, "The hash interval must be at least 1");
+
+        let mut vm =
+            VirtualMachine::new_battle_with_rng(programs, settings, &mut SplitMix64::new(seed));
+        let placements = vm.get_users_pcs().iter().map(|queue| queue[0]).collect();
+
+        let mut hashes = Vec::new();
+        let mut last_cycle = 0;
+        let teams = match_teams(programs.len(), settings);
+        let cycles = run_team_battle_with(&mut vm, &teams, settings.max_cycles, |vm| {
+            // The cycle count doesn't advance once every warrior is dead
+            let cycle = vm.get_cycle_count();
+            if cycle != last_cycle && cycle % hash_interval == 0 {
+                hashes.push(vm.state_hash());
+            }
+            last_cycle = cycle;
+        });
+
+        Replay {
+            settings: settings.clone(),
+            seed,
+            warriors: programs.to_vec(),
+            placements,
+            hash_interval,
+            hashes,
+            instructions: vm.get_cycle_count(),
+            cycles,
+            survivors: vm.alive_warriors().to_vec(),
+            final_hash: vm.state_hash(),
+        }
+    }
+
+    /// Writes the replay in a compact binary format that can be read by `Replay::load`
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        let settings = &self.settings;
+        settings.core_size.encode(writer)?;
+        settings.min_separation.encode(writer)?;
+        settings.max_processes.encode(writer)?;
+        settings.max_length.encode(writer)?;
+        settings.max_cycles.encode(writer)?;
+        settings.execution_mode.encode(writer)?;
+        settings.pspace_size.encode(writer)?;
+        settings.read_limit.encode(writer)?;
+        settings.write_limit.encode(writer)?;
+        settings
+            .handicaps
+            .iter()
+            .map(|handicap| (handicap.max_processes, handicap.speed))
+            .collect::<Vec<_>>()
+            .encode(writer)?;
+        settings.teams.encode(writer)?;
+        settings.pins.encode(writer)?;
+
+        self.seed.encode(writer)?;
+        self.warriors.encode(writer)?;
+        self.placements.encode(writer)?;
+        self.hash_interval.encode(writer)?;
+        self.hashes.encode(writer)?;
+        self.instructions.encode(writer)?;
+        self.cycles.encode(writer)?;
+        self.survivors.encode(writer)?;
+        self.final_hash.encode(writer)
+    }
+
+    /// Reads a replay written by `Replay::save`
+    pub fn load<R: Read>(reader: &mut R) -> Result<Replay, ReplayError> {
+        let mut header = [0; 5];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| ReplayError::NotAReplay)?;
+
+        if &header[..4] != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        if header[4] != VERSION {
+            return Err(ReplayError::UnsupportedVersion(header[4]));
+        }
+
+        let settings = MatchSettings {
+            core_size: usize::decode(reader)?,
+            min_separation: usize::decode(reader)?,
+            max_processes: usize::decode(reader)?,
+            max_length: usize::decode(reader)?,
+            max_cycles: usize::decode(reader)?,
+            execution_mode: ExecutionMode::decode(reader)?,
+            pspace_size: usize::decode(reader)?,
+            read_limit: Encode::decode(reader)?,
+            write_limit: Encode::decode(reader)?,
+            handicaps: Vec::<(Option<usize>, usize)>::decode(reader)?
+                .into_iter()
+                .map(|(max_processes, speed)| Handicap {
+                    max_processes,
+                    speed,
+                })
+                .collect(),
+            teams: Vec::decode(reader)?,
+            pins: Vec::decode(reader)?,
+            ..Default::default()
+        };
+
+        let replay = Replay {
+            settings,
+            seed: u64::decode(reader)?,
+            warriors: Vec::decode(reader)?,
+            placements: Vec::decode(reader)?,
+            hash_interval: usize::decode(reader)?,
+            hashes: Vec::decode(reader)?,
+            instructions: usize::decode(reader)?,
+            cycles: usize::decode(reader)?,
+            survivors: Vec::decode(reader)?,
+            final_hash: u64::decode(reader)?,
+        };
+
+        let settings = &replay.settings;
+        let valid_limit =
+            |limit: Option<usize>| limit.map_or(true, |l| l > 0 && l <= settings.core_size);
+
+        if settings.core_size == 0 || replay.warriors.is_empty() {
+            return Err(ReplayError::Invalid(
+                "the battle has no core or no warriors",
+            ));
+        }
+        if replay.placements.len() != replay.warriors.len()
+            || replay
+                .placements
+                .iter()
+                .any(|placement| *placement >= settings.core_size)
+        {
+            return Err(ReplayError::Invalid(
+                "the placements don't match the warriors",
+            ));
+        }
+        if !valid_limit(settings.read_limit) || !valid_limit(settings.write_limit) {
+            return Err(ReplayError::Invalid("the read or write limit is invalid"));
+        }
+        if settings
+            .handicaps
+            .iter()
+            .any(|handicap| handicap.speed == 0)
+        {
+            return Err(ReplayError::Invalid("a warrior has a speed of 0"));
+        }
+        if settings.max_processes == 0
+            || settings
+                .handicaps
+                .iter()
+                .any(|handicap| handicap.max_processes == Some(0))
+        {
+            return Err(ReplayError::Invalid("a warrior can't have any processes"));
+        }
+        if replay
+            .warriors
+            .iter()
+            .any(|warrior| warrior.is_empty() || warrior.len() > settings.core_size)
+        {
+            return Err(ReplayError::Invalid(
+                "a warrior is empty or larger than the core",
+            ));
+        }
+        if replay.hash_interval == 0 {
+            return Err(ReplayError::Invalid("the hash interval is 0"));
+        }
+
+        Ok(replay)
+    }
+
+    /// The replay in the format written by `Replay::save`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.save(&mut bytes).expect("Writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Reads a replay from the bytes written by `Replay::save`
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Replay, ReplayError> {
+        Replay::load(&mut bytes)
+    }
+
+    /// Writes the replay to a file, see `Replay::save`
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Reads a replay from a file, see `Replay::load`
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Replay, ReplayError> {
+        Replay::load(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// Runs a recorded battle again, checking that it runs exactly as it did when it was recorded
+pub struct ReplayPlayer {
+    replay: Replay,
+    vm: VirtualMachine,
+}
+
+impl ReplayPlayer {
+    /// Sets up the battle of the replay, ready to run its first cycle
+    pub fn new(replay: Replay) -> ReplayPlayer {
+        let vm =
+            VirtualMachine::new_battle_at(&replay.warriors, &replay.settings, &replay.placements);
+
+        ReplayPlayer { replay, vm }
+    }
+
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// The VM that is running the battle, which can be inspected between steps
+    pub fn get_vm(&self) -> &VirtualMachine {
+        &self.vm
+    }
+
+    /// Whether every cycle of the battle has been run
+    pub fn is_finished(&self) -> bool {
+        self.vm.get_cycle_count() >= self.replay.instructions
+    }
+
+    /// Runs the next VM cycle, checking the state hash if one was recorded for that cycle.
+    /// Returns false if the battle has already finished.
+    pub fn step(&mut self) -> Result<bool, Divergence> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+
+        self.vm.cycle();
+
+        let cycle = self.vm.get_cycle_count();
+        if cycle % self.replay.hash_interval == 0 {
+            let expected = self
+                .replay
+                .hashes
+                .get(cycle / self.replay.hash_interval - 1);
+            self.check(expected.copied())?;
+        }
+
+        if self.is_finished() {
+            self.check(Some(self.replay.final_hash))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Runs the rest of the battle, stopping at the first cycle where it runs differently
+    pub fn play(&mut self) -> Result<(), Divergence> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    fn check(&self, expected: Option<u64>) -> Result<(), Divergence> {
+        let found = self.vm.state_hash();
+
+        match expected {
+            Some(expected) if expected == found => Ok(()),
+            // A missing hash means the recorded battle ended before this cycle
+            expected => Err(Divergence {
+                cycle: self.vm.get_cycle_count(),
+                expected: expected.unwrap_or(0),
+                found,
+            }),
+        }
+    }
+}
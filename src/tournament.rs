@@ -0,0 +1,213 @@
+use crate::battle::{match_limits, match_teams, round_seeds, tally_rounds};
+use crate::parallel::{derive_seed, parallel_map};
+use crate::virtual_machine::uses_pspace;
+use crate::{
+    run_match_with_pspace, run_round, Instruction, MatchResult, MatchSettings, SettingsError,
+    WarriorScore,
+};
+
+/// A single match that was played as part of a tournament
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentMatch {
+    /// The ids of the warriors that took part, in the order they were given to the match
+    pub warriors: Vec<usize>,
+    /// The result of the match, where warrior `i` of the match is `warriors[i]`
+    pub result: MatchResult,
+}
+
+/// The result of a tournament
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentResult {
+    /// Every match that was played
+    pub matches: Vec<TournamentMatch>,
+    /// `score_matrix[a][b]` is the total score warrior `a` earned in matches where warrior `b`
+    /// also took part. For a tournament of pairs this is the score of `a` against `b`.
+    pub score_matrix: Vec<Vec<usize>>,
+    /// The combined wins, losses and ties of each warrior over the whole tournament
+    pub totals: Vec<WarriorScore>,
+    /// The seed that the seed of each match was derived from
+    pub seed: u64,
+}
+
+impl TournamentResult {
+    /// The ids of the warriors ordered from the highest total score to the lowest.
+    /// Warriors with equal scores are ordered by id.
+    pub fn ranking(&self) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.totals.len()).collect();
+        // The sort is stable so equal scores stay ordered by id
+        ranking.sort_by(|a, b| self.totals[*b].score().cmp(&self.totals[*a].score()));
+        ranking
+    }
+}
+
+/// Returns every combination of `k` distinct ids out of `0..n` in lexicographic order
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k > n {
+        return Vec::new();
+    }
+
+    let mut result = Vec::new();
+    let mut current: Vec<usize> = (0..k).collect();
+
+    loop {
+        result.push(current.clone());
+
+        // Find the right-most id that can still be increased
+        let mut i = k;
+        while i > 0 && current[i - 1] == n - k + (i - 1) {
+            i -= 1;
+        }
+
+        if i == 0 {
+            return result;
+        }
+
+        current[i - 1] += 1;
+        for j in i..k {
+            current[j] = current[j - 1] + 1;
+        }
+    }
+}
+
+/// Runs a round robin tournament where every pair of warriors plays a match using `settings`
+pub fn run_tournament(
+    warriors: &[Vec<Instruction>],
+    settings: &MatchSettings,
+) -> Result<TournamentResult, SettingsError> {
+    run_tournament_with_group_size(warriors, settings, 2)
+}
+
+/// Runs a tournament where every group of `group_size` distinct warriors plays a match using
+/// `settings`.
+/// The rounds of every match are spread over `settings.threads` threads, and the result only
+/// depends on `settings.seed`.
+/// Returns an error if any of the matches can't be played with the settings.
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, run_tournament_with_group_size, MatchSettings};
+///
+/// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+/// let dat = create_program! { DAT(None, 0, Immediate, 0, Immediate) };
+///
+/// let result = run_tournament_with_group_size(
+///     &[imp.clone(), dat, imp],
+///     &MatchSettings { max_cycles: 100, ..Default::default() },
+///     3,
+/// )
+/// .unwrap();
+///
+/// // With 3 warriors there is only a single group of 3
+/// assert_eq!(result.matches.len(), 1);
+/// assert_eq!(result.totals[1].losses, 1);
+/// ```
+pub fn run_tournament_with_group_size(
+    warriors: &[Vec<Instruction>],
+    settings: &MatchSettings,
+    group_size: usize,
+) -> Result<TournamentResult, SettingsError> {
+    assert!(group_size > 0, "Each match needs at least one warrior");
+
+    let mut score_matrix = vec![vec![0; warriors.len()]; warriors.len()];
+    let mut totals = vec![WarriorScore::default(); warriors.len()];
+
+    let seed = settings.seed.unwrap_or_else(rand::random);
+
+    let groups = combinations(warriors.len(), group_size);
+    let group_programs: Vec<Vec<Vec<Instruction>>> = groups
+        .iter()
+        .map(|group| group.iter().map(|id| warriors[*id].clone()).collect())
+        .collect();
+    // Handicaps are given by tournament id, but each match needs them in the order of its warriors
+    let group_settings: Vec<MatchSettings> = groups
+        .iter()
+        .map(|group| settings.select_warriors(group))
+        .collect();
+    for (programs, settings) in group_programs.iter().zip(&group_settings) {
+        settings.validate_match(programs)?;
+    }
+    let match_seeds: Vec<u64> = (0..groups.len())
+        .map(|i| derive_seed(seed, i as u64))
+        .collect();
+
+    let results: Vec<MatchResult> = if warriors.iter().any(|warrior| uses_pspace(warrior)) {
+        // Rounds that use P-space depend on the previous round, so only whole matches can be
+        // spread over the threads
+        parallel_map(groups.len(), settings.threads, |group| {
+            let settings = MatchSettings {
+                seed: Some(match_seeds[group]),
+                ..group_settings[group].clone()
+            };
+
+            run_match_with_pspace(
+                &group_programs[group],
+                &settings,
+                &mut settings.pspace(group_size),
+            )
+        })
+        .into_iter()
+        .collect::<Result<_, _>>()?
+    } else {
+        let seeds: Vec<Vec<u64>> = match_seeds
+            .iter()
+            .map(|match_seed| round_seeds(*match_seed, settings.rounds))
+            .collect();
+
+        // Every round of every match is an independent job, which spreads the work evenly over
+        // the threads even when there are fewer matches than threads
+        let mut rounds = parallel_map(groups.len() * settings.rounds, settings.threads, |job| {
+            let (group, round) = (job / settings.rounds, job % settings.rounds);
+
+            run_round(
+                &group_programs[group],
+                &group_settings[group],
+                round % group_size,
+                seeds[group][round],
+            )
+        })
+        .into_iter();
+
+        match_seeds
+            .iter()
+            .zip(&group_settings)
+            .map(|(match_seed, group_settings)| {
+                let rounds: Vec<_> = rounds.by_ref().take(settings.rounds).collect();
+
+                MatchResult {
+                    scores: tally_rounds(group_size, &rounds),
+                    rounds,
+                    seed: *match_seed,
+                    limits: match_limits(group_size, group_settings),
+                    teams: match_teams(group_size, group_settings),
+                }
+            })
+            .collect()
+    };
+
+    let matches: Vec<TournamentMatch> = groups
+        .into_iter()
+        .zip(results)
+        .map(|(warriors, result)| TournamentMatch { warriors, result })
+        .collect();
+
+    for tournament_match in &matches {
+        let group = &tournament_match.warriors;
+
+        for (i, score) in tournament_match.result.scores.iter().enumerate() {
+            let total = &mut totals[group[i]];
+            total.wins += score.wins;
+            total.losses += score.losses;
+            total.ties += score.ties;
+
+            for opponent in group.iter().filter(|id| **id != group[i]) {
+                score_matrix[group[i]][*opponent] += score.score();
+            }
+        }
+    }
+
+    Ok(TournamentResult {
+        matches,
+        score_matrix,
+        totals,
+        seed,
+    })
+}
@@ -1,7 +1,79 @@
-use crate::{handlers, AddressMode, Instruction, Modifier, OpCode};
+use crate::handlers::{self, Limits};
+use crate::{AddressMode, Instruction, Modifier, OpCode};
+
+mod history;
+use history::History;
+
+mod icws94;
+use icws94::Action;
+
+mod observer;
+pub use observer::*;
+use observer::{notify, reads_targets, reborrow, writes_target};
+
+mod ownership;
+pub use ownership::*;
+use ownership::{CellOwnership, OwnershipTracker};
+
+mod processes;
+pub use processes::*;
+
+mod presets;
+pub use presets::*;
+
+mod snapshot;
+pub use snapshot::*;
+
+mod statistics;
+use statistics::StatisticsTracker;
+pub use statistics::*;
+
+mod pspace;
+pub use pspace::*;
+
+mod trace;
+pub use trace::*;
+
+use rand::Rng;
 
 use std::collections::VecDeque;
 
+/// How the VM evaluates the operands of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExecutionMode {
+    /// Pre-decrements of both operands are applied before the instruction runs, the operands are
+    /// then followed against live memory as the instruction needs them, and post-increments of
+    /// both operands are applied after the instruction has run
+    #[default]
+    Lazy,
+    /// Follows the instruction register semantics of the ICWS '94 draft: the A operand is
+    /// evaluated completely (including its post-increment) and the instruction it points to is
+    /// copied before the B operand is evaluated
+    Icws94,
+}
+
+impl ExecutionMode {
+    /// The modifier that an instruction without one is executed with in this mode
+    pub(crate) fn resolve_modifier(self, instruction: &Instruction) -> Modifier {
+        use OpCode::*;
+
+        match (instruction.modifier, self) {
+            // The lazy arithmetic handlers treat a missing modifier as AB
+            (Modifier::None, ExecutionMode::Lazy)
+                if matches!(instruction.op_code, ADD | SUB | MUL | DIV | MOD) =>
+            {
+                Modifier::AB
+            }
+            (Modifier::None, _) => crate::compiler::get_default_modifier(
+                instruction.op_code,
+                instruction.a_mode,
+                instruction.b_mode,
+            ),
+            (modifier, _) => modifier,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MatchSettings {
     /// The minimum separation between warriors when they are loaded
@@ -10,6 +82,74 @@ pub struct MatchSettings {
     pub max_processes: usize,
     /// The size of the core
     pub core_size: usize,
+    /// The maximum number of instructions in a warrior
+    pub max_length: usize,
+    /// The number of cycles before a round is declared a tie, during each cycle every living
+    /// warrior executes one instruction
+    pub max_cycles: usize,
+    /// The number of rounds played in a match
+    pub rounds: usize,
+    /// The seed used to generate the placements of every round. If this is `None` then a random
+    /// seed is chosen for each match. The seeds are expanded with splitmix64 rather than one of
+    /// rand's generators, so a seed keeps giving the same placements when rand is updated.
+    pub seed: Option<u64>,
+    /// The number of threads that rounds are spread across. This doesn't affect the results.
+    pub threads: usize,
+    /// How the VM evaluates the operands of an instruction
+    pub execution_mode: ExecutionMode,
+    /// The number of locations in each warrior's P-space, if this is 0 then warriors can't use
+    /// P-space
+    pub pspace_size: usize,
+    /// How far from the executing instruction a warrior can read (READLIMIT), addresses further
+    /// away are folded back into this window. `None` means the whole core can be read.
+    pub read_limit: Option<usize>,
+    /// How far from the executing instruction a warrior can write (WRITELIMIT), addresses further
+    /// away are folded back into this window. `None` means the whole core can be written to.
+    pub write_limit: Option<usize>,
+    /// The handicap of each warrior in the order the warriors are given to the match (or by id
+    /// for a tournament). Warriors without an entry have no handicap.
+    pub handicaps: Vec<Handicap>,
+    /// Whether to collect the statistics of each warrior in every round, which slows battles down
+    pub statistics: bool,
+    /// The team of each warrior in the order the warriors are given to the match (or by id for a
+    /// tournament). A round ends once only one team has survivors, and a team wins if any of its
+    /// warriors survive. Warriors without an entry are on the team with the same id as their
+    /// index, so by default every warrior is on a team of its own.
+    pub teams: Vec<usize>,
+    /// The distance from the first warrior to the second, like the `-F` option of pMARS. If this
+    /// is `None` the warriors are placed randomly. This can only be used with two warriors.
+    pub fixed_position: Option<usize>,
+    /// The PIN of each warrior in the order the warriors are given to the match (or by id for a
+    /// tournament), see `Compilation::pin`. Warriors with the same PIN share their P-space, and
+    /// warriors without an entry have no PIN.
+    pub pins: Vec<Option<u64>>,
+}
+
+/// Changes to the limits of a single warrior, used for handicapped or teaching matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handicap {
+    /// The maximum number of processes of the warrior, `None` uses `MatchSettings::max_processes`
+    pub max_processes: Option<usize>,
+    /// The number of instructions the warrior executes each time it is its turn
+    pub speed: usize,
+}
+
+impl Default for Handicap {
+    fn default() -> Handicap {
+        Handicap {
+            max_processes: None,
+            speed: 1,
+        }
+    }
+}
+
+/// The limits a warrior played with once its handicap has been applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarriorLimits {
+    /// The maximum number of processes of the warrior
+    pub max_processes: usize,
+    /// The number of instructions the warrior executes each time it is its turn
+    pub speed: usize,
 }
 
 impl Default for MatchSettings {
@@ -18,11 +158,25 @@ impl Default for MatchSettings {
             min_separation: 100,
             max_processes: 8000,
             core_size: 8000,
+            max_length: 100,
+            max_cycles: 80000,
+            rounds: 1,
+            seed: None,
+            threads: 1,
+            execution_mode: ExecutionMode::default(),
+            pspace_size: 500,
+            read_limit: None,
+            write_limit: None,
+            handicaps: Vec::new(),
+            statistics: false,
+            teams: Vec::new(),
+            fixed_position: None,
+            pins: Vec::new(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VirtualMachine {
     memory: Vec<Instruction>,
     /// Each element in this vector represents the queue of a user's processes
@@ -30,18 +184,38 @@ pub struct VirtualMachine {
     users_pcs: Vec<VecDeque<usize>>,
     /// The id of the user whose process should run next
     cur_user: usize,
-    /// The maximum number of processes for an individual user
-    max_processes: usize,
+    /// The users that still have processes, in load order
+    alive: Vec<usize>,
+    /// The maximum number of processes of each user
+    max_processes: Vec<usize>,
+    /// The number of instructions each user executes when it is their turn
+    speeds: Vec<usize>,
+    /// The number of instructions the current user has executed during its current turn
+    turns: usize,
+    /// How the operands of each instruction are evaluated
+    execution_mode: ExecutionMode,
+    /// The P-space of each user
+    pspace: PSpace,
+    /// How far from the executing instruction reads and writes can reach
+    limits: Limits,
+    /// The number of times `cycle` has been run
+    cycle_count: usize,
+    /// Which user last wrote to and executed each cell, if it is being tracked
+    ownership: Option<CoreOwnership>,
+    /// The changes made by the most recent cycles, if they are being recorded
+    history: Option<History>,
+    /// What each user has done, if it is being tracked
+    statistics: Option<BattleStatistics>,
+    /// The id of every process and which process started which, if it is being tracked
+    processes: Option<ProcessTree>,
 }
 
-fn generate_random_insertion_points(
+fn generate_random_insertion_points<R: Rng>(
     size: usize,
     programs: &[Vec<Instruction>],
     min_separation: usize,
+    rng: &mut R,
 ) -> Vec<usize> {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-
     #[derive(Debug)]
     struct Block {
         start: usize,
@@ -65,8 +239,6 @@ fn generate_random_insertion_points(
     };
 
     for program in programs.iter().skip(1) {
-        println!("{:?}", free_blocks);
-
         let total_free_spaces: usize = free_blocks
             .iter()
             .map(|block| {
@@ -81,39 +253,36 @@ fn generate_random_insertion_points(
             .sum();
 
         if total_free_spaces == 0 {
-            panic!("Not enough room to insert all the programs");
+            // The earlier programs were placed so that this one doesn't fit anywhere
+            return spread_insertion_points(size, programs, min_separation);
         }
 
         let mut n: usize = rng.gen_range(0, total_free_spaces);
 
         for i in 0..free_blocks.len() {
             let block = &free_blocks[i];
+            let block_free_spaces = (block.len + 1).saturating_sub(program.len());
 
-            if n > block.len {
-                // Program should be inserted outside this block, so continue to the next and deduct the length
-                n -= block.len;
+            if n >= block_free_spaces {
+                // Program should be inserted outside this block, so continue to the next and
+                // deduct the number of places it could have been inserted in this block
+                n -= block_free_spaces;
             } else {
                 // Program is within this block
-                indices.push(block.start + n);
+                indices.push((block.start + n) % size);
+
+                // The space left in the block after the program
+                let remaining = block.len - n - program.len();
+
+                // The block after this program starts after the program and its padding
+                let after = Block {
+                    start: (block.start + n + program.len() + min_separation) % size,
+                    len: remaining.saturating_sub(min_separation),
+                };
 
                 // The block before this program now has length n - min_separation or 0 if there isn't enough room
-                free_blocks.insert(
-                    i,
-                    Block {
-                        start: free_blocks[i].start,
-                        len: n.saturating_sub(min_separation),
-                    },
-                );
-
-                // The distance from the previous start to the new start
-                let new_start_distance = n + (2 * min_separation) + program.len();
-                // If there is enough room for another free_block after this new program and its padding
-                if free_blocks[i + 1].len > new_start_distance {
-                    free_blocks[i + 1].start = (free_blocks[i].start + new_start_distance) % size;
-                } else {
-                    // Remove the block since there isn't enough room
-                    free_blocks.remove(i + 1);
-                }
+                free_blocks[i].len = n.saturating_sub(min_separation);
+                free_blocks.insert(i + 1, after);
 
                 // Move on to the next program
                 break;
@@ -124,6 +293,122 @@ fn generate_random_insertion_points(
     indices
 }
 
+/// Places the programs one after the other with the spare room shared equally between them.
+/// Used when the random placement runs out of room, which can only happen with 3 or more programs.
+fn spread_insertion_points(
+    size: usize,
+    programs: &[Vec<Instruction>],
+    min_separation: usize,
+) -> Vec<usize> {
+    let needed: usize = programs
+        .iter()
+        .map(|program| program.len() + min_separation)
+        .sum();
+    assert!(needed <= size, "Not enough room to insert all the programs");
+    let gap = min_separation + (size - needed) / programs.len();
+
+    let mut indices = Vec::with_capacity(programs.len());
+    let mut next = min_separation;
+    for program in programs {
+        indices.push(next % size);
+        next += program.len() + gap;
+    }
+
+    indices
+}
+
+impl MatchSettings {
+    /// The read and write limits of the match, where no limit is the size of the core.
+    /// Returns an error if a limit is 0 or larger than the core.
+    pub fn limits(&self) -> Result<Limits, SettingsError> {
+        let limits = Limits {
+            read: self.read_limit.unwrap_or(self.core_size),
+            write: self.write_limit.unwrap_or(self.core_size),
+        };
+
+        check_limits(limits, self.core_size)?;
+        Ok(limits)
+    }
+
+    /// The limits of warrior `warrior` of the match after its handicap has been applied
+    /// # Example
+    /// ```
+    /// use darwin_lib::{Handicap, MatchSettings};
+    ///
+    /// let settings = MatchSettings {
+    ///     max_processes: 64,
+    ///     handicaps: vec![Handicap { max_processes: Some(8), speed: 2 }],
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert_eq!(settings.warrior_limits(0).max_processes, 8);
+    /// assert_eq!(settings.warrior_limits(0).speed, 2);
+    /// // The second warrior has no handicap
+    /// assert_eq!(settings.warrior_limits(1).max_processes, 64);
+    /// assert_eq!(settings.warrior_limits(1).speed, 1);
+    /// ```
+    pub fn warrior_limits(&self, warrior: usize) -> WarriorLimits {
+        let handicap = self.handicaps.get(warrior).copied().unwrap_or_default();
+
+        WarriorLimits {
+            max_processes: handicap.max_processes.unwrap_or(self.max_processes),
+            speed: handicap.speed,
+        }
+    }
+
+    /// The team of warrior `warrior` of the match
+    pub fn warrior_team(&self, warrior: usize) -> usize {
+        self.teams.get(warrior).copied().unwrap_or(warrior)
+    }
+
+    /// The PIN of warrior `warrior` of the match
+    pub fn warrior_pin(&self, warrior: usize) -> Option<u64> {
+        self.pins.get(warrior).copied().flatten()
+    }
+
+    /// An empty P-space for the warriors of a match, shared between warriors with the same PIN
+    pub fn pspace(&self, warriors: usize) -> PSpace {
+        let pins: Vec<Option<u64>> = (0..warriors).map(|i| self.warrior_pin(i)).collect();
+        PSpace::with_pins(self.pspace_size, &pins)
+    }
+
+    /// The settings of a match between the given warriors of this match, in that order
+    pub(crate) fn select_warriors(&self, warriors: &[usize]) -> MatchSettings {
+        MatchSettings {
+            handicaps: warriors
+                .iter()
+                .map(|warrior| self.handicaps.get(*warrior).copied().unwrap_or_default())
+                .collect(),
+            teams: warriors
+                .iter()
+                .map(|warrior| self.warrior_team(*warrior))
+                .collect(),
+            pins: warriors
+                .iter()
+                .map(|warrior| self.warrior_pin(*warrior))
+                .collect(),
+            // Swapping the warriors puts the first warrior behind the second one instead
+            fixed_position: match warriors {
+                [1, 0] => self
+                    .fixed_position
+                    .map(|position| (self.core_size - position % self.core_size) % self.core_size),
+                _ => self.fixed_position,
+            },
+            ..self.clone()
+        }
+    }
+}
+
+fn check_limits(limits: Limits, core_size: usize) -> Result<(), SettingsError> {
+    if limits.read == 0 || limits.read > core_size {
+        Err(SettingsError::ReadLimit(limits.read, core_size))
+    } else if limits.write == 0 || limits.write > core_size {
+        Err(SettingsError::WriteLimit(limits.write, core_size))
+    } else {
+        Ok(())
+    }
+}
+
 fn generate_empty_memory(size: usize) -> Vec<Instruction> {
     (0..size)
         .map(|_| {
@@ -139,66 +424,213 @@ fn generate_empty_memory(size: usize) -> Vec<Instruction> {
         .collect()
 }
 
+/// The addresses that the A and B operands of `instruction` resolve to, found in the same way as
+/// the lazy handlers find them. This must be called after the pre-decrements.
+fn lazy_operands(
+    instruction: Instruction,
+    cur_address: usize,
+    max: usize,
+    limits: Limits,
+    memory: &[Instruction],
+) -> (usize, usize) {
+    let b_limit = if writes_target(instruction.op_code) {
+        limits.write
+    } else {
+        limits.read
+    };
+
+    (
+        handlers::follow_address_with_limit(
+            instruction.a_reg,
+            instruction.a_mode,
+            cur_address,
+            max,
+            limits.read,
+            memory,
+        ),
+        handlers::follow_address_with_limit(
+            instruction.b_reg,
+            instruction.b_mode,
+            cur_address,
+            max,
+            b_limit,
+            memory,
+        ),
+    )
+}
+
+/// The addresses that the lazy handlers read from and write to when running `instruction`, found
+/// in the same way as the handlers find them. This must be called after the pre-decrements.
+fn lazy_accesses(
+    instruction: Instruction,
+    cur_address: usize,
+    max: usize,
+    limits: Limits,
+    memory: &[Instruction],
+) -> (Vec<usize>, Option<usize>) {
+    use AddressMode::*;
+
+    let writes = writes_target(instruction.op_code);
+    let (reads_a, reads_b) = reads_targets(instruction.op_code);
+
+    let b_limit = if writes { limits.write } else { limits.read };
+
+    let mut reads = Vec::new();
+
+    for (reg, mode, limit, reads_target) in [
+        (instruction.a_reg, instruction.a_mode, limits.read, reads_a),
+        (instruction.b_reg, instruction.b_mode, b_limit, reads_b),
+    ] {
+        if instruction.op_code == OpCode::DAT || mode == Immediate {
+            continue;
+        }
+
+        if mode != Direct {
+            reads.push((cur_address + handlers::fold(reg, limit, max)) % max);
+        }
+
+        if reads_target {
+            reads.push(handlers::follow_address_with_limit(
+                reg,
+                mode,
+                cur_address,
+                max,
+                limit,
+                memory,
+            ));
+        }
+    }
+
+    let write = if writes {
+        Some(handlers::follow_address_with_limit(
+            instruction.b_reg,
+            instruction.b_mode,
+            cur_address,
+            max,
+            limits.write,
+            memory,
+        ))
+    } else {
+        None
+    };
+
+    (reads, write)
+}
+
+/// Returns the address of the pointer that was changed and its previous value
 fn handle_pre_decrement(
     reg: isize,
     mode: AddressMode,
     cur_address: usize,
     max: usize,
+    write_limit: usize,
     memory: &mut [Instruction],
-) {
-    use handlers::relative_address;
+) -> Option<(usize, Instruction)> {
     use AddressMode::*;
 
+    // The pointer is written to so it is folded using the write limit
+    let index = (cur_address + handlers::fold(reg, write_limit, max)) % max;
+    let old = memory[index];
+
     match mode {
         PreDecrementIndirectA => {
-            let index = relative_address(max, cur_address, reg);
             memory[index].a_reg = (memory[index].a_reg - 1 + max as isize) % max as isize;
         }
         PreDecrementIndirectB => {
-            let index = relative_address(max, cur_address, reg);
             memory[index].b_reg = (memory[index].b_reg - 1 + max as isize) % max as isize;
         }
-        _ => {}
+        _ => return None,
     }
+
+    Some((index, old))
 }
 
+/// Returns the address of the pointer that was changed and its previous value
 fn handle_post_increment(
     reg: isize,
     mode: AddressMode,
     cur_address: usize,
     max: usize,
+    write_limit: usize,
     memory: &mut [Instruction],
-) {
-    use handlers::relative_address;
+) -> Option<(usize, Instruction)> {
     use AddressMode::*;
 
+    // The pointer is written to so it is folded using the write limit
+    let index = (cur_address + handlers::fold(reg, write_limit, max)) % max;
+    let old = memory[index];
+
     match mode {
         PostIncrementIndirectA => {
-            let index = relative_address(max, cur_address, reg);
             memory[index].a_reg = (memory[index].a_reg + 1) % max as isize;
         }
         PostIncrementIndirectB => {
-            let index = relative_address(max, cur_address, reg);
             memory[index].b_reg = (memory[index].b_reg + 1) % max as isize;
         }
-        _ => {}
+        _ => return None,
     }
+
+    Some((index, old))
 }
 
 impl VirtualMachine {
     /// Creates a new VM with specified programs and match settings
     /// This inserts programs randomly into memory
+    /// Panics if the match can't be played with the settings, see `MatchSettings::validate_match`
     pub fn new_battle(
         programs: &[Vec<Instruction>],
         match_settings: &MatchSettings,
     ) -> VirtualMachine {
-        let mut memory = generate_empty_memory(match_settings.core_size);
+        VirtualMachine::new_battle_with_rng(programs, match_settings, &mut rand::thread_rng())
+    }
 
-        let indices = generate_random_insertion_points(
-            match_settings.core_size,
-            &programs,
-            match_settings.min_separation,
+    /// Creates a new VM with specified programs and match settings
+    /// The insertion points of the programs are chosen using the given random number generator,
+    /// unless `MatchSettings::fixed_position` is set
+    /// Panics if the match can't be played with the settings, see `MatchSettings::validate_match`
+    pub fn new_battle_with_rng<R: Rng>(
+        programs: &[Vec<Instruction>],
+        match_settings: &MatchSettings,
+        rng: &mut R,
+    ) -> VirtualMachine {
+        if let Err(error) = match_settings.validate_match(programs) {
+            panic!("{}", error);
+        }
+
+        let indices = match match_settings.fixed_position {
+            Some(position) => vec![0, position],
+            None => generate_random_insertion_points(
+                match_settings.core_size,
+                programs,
+                match_settings.min_separation,
+                rng,
+            ),
+        };
+
+        VirtualMachine::new_battle_at(programs, match_settings, &indices)
+    }
+
+    /// Creates a new VM with specified programs and match settings
+    /// Program `i` is inserted at `placements[i]` (and starts executing there)
+    pub fn new_battle_at(
+        programs: &[Vec<Instruction>],
+        match_settings: &MatchSettings,
+        placements: &[usize],
+    ) -> VirtualMachine {
+        assert_eq!(
+            programs.len(),
+            placements.len(),
+            "Every program needs a placement"
         );
+        assert!(
+            placements
+                .iter()
+                .all(|placement| *placement < match_settings.core_size),
+            "Programs must be placed inside the core"
+        );
+
+        let mut memory = generate_empty_memory(match_settings.core_size);
+        let indices = placements;
 
         for (start_index, program) in indices.iter().zip(programs.iter()) {
             for (instruction_i, instruction) in program.iter().enumerate() {
@@ -209,10 +641,31 @@ impl VirtualMachine {
         VirtualMachine {
             memory,
             cur_user: 0,
+            alive: (0..programs.len()).collect(),
             users_pcs: (0..programs.len())
                 .map(|i| VecDeque::from(vec![indices[i]]))
                 .collect(),
-            max_processes: match_settings.max_processes,
+            max_processes: (0..programs.len())
+                .map(|i| match_settings.warrior_limits(i).max_processes)
+                .collect(),
+            speeds: (0..programs.len())
+                .map(|i| {
+                    let speed = match_settings.warrior_limits(i).speed;
+                    assert!(speed > 0, "A warrior's speed must be at least 1");
+                    speed
+                })
+                .collect(),
+            turns: 0,
+            execution_mode: match_settings.execution_mode,
+            pspace: match_settings.pspace(programs.len()),
+            limits: match_settings
+                .limits()
+                .unwrap_or_else(|error| panic!("{}", error)),
+            cycle_count: 0,
+            ownership: None,
+            history: None,
+            statistics: None,
+            processes: None,
         }
     }
 
@@ -230,11 +683,25 @@ impl VirtualMachine {
             memory[i] = *instruction
         }
 
+        // The program has the limits of an unhandicapped warrior
+        let limits = MatchSettings::default().warrior_limits(0);
+
         VirtualMachine {
             memory,
             cur_user: 0,
+            alive: vec![0],
             users_pcs: vec![VecDeque::from(vec![0])],
-            max_processes: 8000,
+            max_processes: vec![limits.max_processes],
+            speeds: vec![limits.speed],
+            turns: 0,
+            execution_mode: ExecutionMode::default(),
+            pspace: PSpace::new((size / 16).max(1), 1),
+            limits: Limits::unlimited(size),
+            cycle_count: 0,
+            ownership: None,
+            history: None,
+            statistics: None,
+            processes: None,
         }
     }
 
@@ -250,8 +717,296 @@ impl VirtualMachine {
         self.cur_user
     }
 
-    /// Runs one iteration of the virtual machine
+    /// The users that still have processes, in the order they take their turns
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, MatchSettings, VirtualMachine};
+    ///
+    /// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    /// let dat = create_program! { DAT(F, 0, Immediate, 0, Immediate) };
+    ///
+    /// let mut vm = VirtualMachine::new_battle(&[dat, imp.clone(), imp], &MatchSettings::default());
+    /// vm.cycle();
+    ///
+    /// assert_eq!(vm.alive_warriors(), &[1, 2]);
+    /// assert_eq!(vm.get_cur_user(), 1);
+    /// ```
+    pub fn alive_warriors(&self) -> &[usize] {
+        &self.alive
+    }
+
+    /// Whether `user` still has any processes
+    pub fn is_alive(&self, user: usize) -> bool {
+        !self.users_pcs[user].is_empty()
+    }
+
+    pub fn get_execution_mode(&self) -> ExecutionMode {
+        self.execution_mode
+    }
+
+    pub fn set_execution_mode(&mut self, execution_mode: ExecutionMode) {
+        self.execution_mode = execution_mode;
+    }
+
+    /// The P-space of the users, where user `i` is warrior `i` of the P-space
+    pub fn get_pspace(&self) -> &PSpace {
+        &self.pspace
+    }
+
+    pub fn get_pspace_mut(&mut self) -> &mut PSpace {
+        &mut self.pspace
+    }
+
+    /// The maximum number of processes of the given user
+    pub fn get_max_processes(&self, user: usize) -> usize {
+        self.max_processes[user]
+    }
+
+    pub fn set_max_processes(&mut self, user: usize, max_processes: usize) {
+        self.max_processes[user] = max_processes;
+    }
+
+    /// The number of instructions the given user executes each time it is their turn
+    pub fn get_speed(&self, user: usize) -> usize {
+        self.speeds[user]
+    }
+
+    /// Sets the number of instructions the given user executes each time it is their turn.
+    /// Panics if the speed is 0.
+    pub fn set_speed(&mut self, user: usize, speed: usize) {
+        assert!(speed > 0, "A user's speed must be at least 1");
+        self.speeds[user] = speed;
+    }
+
+    pub fn get_limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Sets how far from the executing instruction reads and writes can reach.
+    /// Panics if a limit is 0 or larger than the core.
+    pub fn set_limits(&mut self, limits: Limits) {
+        if let Err(error) = check_limits(limits, self.memory.len()) {
+            panic!("{}", error);
+        }
+        self.limits = limits;
+    }
+
+    /// The number of times `cycle` has been run
+    pub fn get_cycle_count(&self) -> usize {
+        self.cycle_count
+    }
+
+    /// Starts keeping track of which user last wrote to and executed each cell.
+    /// Cells that were written before this was called have no owner.
+    pub fn track_ownership(&mut self) {
+        if self.ownership.is_none() {
+            self.ownership = Some(CoreOwnership::new(self.memory.len()));
+        }
+    }
+
+    /// Which user last wrote to and executed each cell, or `None` if this isn't being tracked
+    /// (see `track_ownership`)
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, VirtualMachine};
+    ///
+    /// let mut vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+    /// vm.track_ownership();
+    /// vm.cycle();
+    ///
+    /// let ownership = vm.get_ownership().unwrap();
+    /// assert_eq!(ownership.executors()[0], Some(0));
+    /// assert_eq!(ownership.writers()[1], Some(0));
+    /// assert_eq!(ownership.last_touched()[1], Some(0));
+    /// ```
+    pub fn get_ownership(&self) -> Option<&CoreOwnership> {
+        self.ownership.as_ref()
+    }
+
+    /// Starts collecting statistics about what each user does (see `BattleStatistics`).
+    /// This slows the VM down so it is off by default. Cycles that are undone with `step_back`
+    /// aren't removed from the statistics.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, OpCode, VirtualMachine};
+    ///
+    /// let mut vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+    /// vm.track_statistics();
+    /// vm.cycle();
+    /// vm.cycle();
+    ///
+    /// let statistics = &vm.get_statistics().unwrap().warriors()[0];
+    /// assert_eq!(statistics.instructions, 2);
+    /// assert_eq!(statistics.op_count(OpCode::MOV), 2);
+    /// assert_eq!(statistics.cells_written, 2);
+    /// ```
+    pub fn track_statistics(&mut self) {
+        if self.statistics.is_none() {
+            let processes: Vec<usize> = self.users_pcs.iter().map(|queue| queue.len()).collect();
+            let mut statistics = BattleStatistics::new(&processes, self.memory.len());
+            statistics.start_at(self.cycle_count);
+            self.statistics = Some(statistics);
+        }
+    }
+
+    /// The statistics of each user, or `None` if they aren't being collected (see
+    /// `track_statistics`)
+    pub fn get_statistics(&self) -> Option<&BattleStatistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Stops collecting statistics, returning the statistics that were collected
+    pub fn take_statistics(&mut self) -> Option<BattleStatistics> {
+        self.statistics.take()
+    }
+
+    /// Runs one iteration of the virtual machine, which does nothing once every user has been
+    /// killed
     pub fn cycle(&mut self) {
+        self.step(None);
+    }
+
+    /// Runs one iteration of the virtual machine like `cycle`, telling `observer` about every
+    /// instruction fetch, memory access, and process that is started or killed
+    pub fn cycle_with_observer(&mut self, observer: &mut dyn Observer) {
+        self.step(Some(observer));
+    }
+
+    fn step(&mut self, observer: Option<&mut dyn Observer>) {
+        if self.alive.is_empty() {
+            return;
+        }
+
+        let user = self.cur_user;
+        let queue_len = self.users_pcs[user].len();
+
+        if self.history.is_some() {
+            self.execute_recorded(observer);
+        } else {
+            self.execute_tracked(observer, None);
+        }
+
+        if let Some(processes) = &mut self.processes {
+            // Executing an instruction always pops the front of the queue and pushes 0 or more
+            // addresses onto the back
+            let pushed = self.users_pcs[user].len() + 1 - queue_len;
+            processes.record_cycle(user, pushed, self.cycle_count);
+        }
+
+        self.cycle_count += 1;
+
+        let killed = self.users_pcs[user].is_empty();
+        if killed {
+            self.alive.retain(|alive| *alive != user);
+        }
+
+        // A user keeps the turn until it has executed as many instructions as its speed
+        self.turns += 1;
+        if self.turns < self.speeds[user] && !killed {
+            return;
+        }
+        self.turns = 0;
+
+        // The turn passes to the next user (in load order) that is still alive, so the survivors
+        // keep their order when a user is killed
+        if let Some(first) = self.alive.first() {
+            self.cur_user = self
+                .alive
+                .iter()
+                .copied()
+                .find(|alive| *alive > user)
+                .unwrap_or(*first);
+        }
+    }
+
+    /// Runs the next instruction, updating the statistics and the ownership map if they are being
+    /// tracked. The previous ownership of each changed cell is recorded in `journal`.
+    fn execute_tracked(
+        &mut self,
+        observer: Option<&mut dyn Observer>,
+        journal: Option<&mut Vec<(usize, CellOwnership)>>,
+    ) {
+        match self.statistics.take() {
+            Some(mut statistics) => {
+                let user = self.cur_user;
+                let mut tracker = StatisticsTracker {
+                    statistics: &mut statistics,
+                    cycle: self.cycle_count,
+                    op_code: None,
+                    next: observer,
+                };
+                self.execute_owned(Some(&mut tracker), journal);
+                statistics.record_processes(user, self.users_pcs[user].len(), self.cycle_count + 1);
+                self.statistics = Some(statistics);
+            }
+            None => self.execute_owned(observer, journal),
+        }
+    }
+
+    /// Runs the next instruction, updating the ownership map if it is being tracked
+    fn execute_owned(
+        &mut self,
+        observer: Option<&mut dyn Observer>,
+        journal: Option<&mut Vec<(usize, CellOwnership)>>,
+    ) {
+        match self.ownership.take() {
+            Some(mut ownership) => {
+                let mut tracker = OwnershipTracker {
+                    ownership: &mut ownership,
+                    cycle: self.cycle_count,
+                    next: observer,
+                    journal,
+                };
+                self.execute(Some(&mut tracker));
+                self.ownership = Some(ownership);
+            }
+            None => self.execute(observer),
+        }
+    }
+
+    fn execute(&mut self, observer: Option<&mut dyn Observer>) {
+        match self.execution_mode {
+            ExecutionMode::Lazy => self.execute_lazy(observer),
+            ExecutionMode::Icws94 => self.execute_icws94(observer),
+        }
+    }
+
+    /// Runs the next instruction of the current user using `ExecutionMode::Icws94`
+    fn execute_icws94(&mut self, mut observer: Option<&mut dyn Observer>) {
+        let process_queue = &mut self.users_pcs[self.cur_user];
+
+        let pc = process_queue
+            .pop_front()
+            .expect("All user processes have been killed");
+        let actor = Actor {
+            user: self.cur_user,
+            pc,
+        };
+
+        match icws94::execute(
+            pc,
+            &mut self.memory,
+            self.limits,
+            &mut self.pspace,
+            self.cur_user,
+            reborrow(&mut observer),
+        ) {
+            Action::Continue(next) => process_queue.push_back(next),
+            Action::Split(next, new) => {
+                process_queue.push_back(next);
+
+                // If max processes is reached then no new process is started
+                if process_queue.len() < self.max_processes[self.cur_user] {
+                    process_queue.push_back(new);
+                    notify(&mut observer, |o| o.spawn(actor, new));
+                }
+            }
+            Action::Kill => notify(&mut observer, |o| o.death(actor)),
+        }
+    }
+
+    /// Runs the next instruction of the current user using `ExecutionMode::Lazy`
+    fn execute_lazy(&mut self, mut observer: Option<&mut dyn Observer>) {
         // Get the user's process queue
         let process_queue = &mut self.users_pcs[self.cur_user];
 
@@ -266,116 +1021,180 @@ impl VirtualMachine {
 
         let memory_len = self.memory.len();
 
+        let actor = Actor {
+            user: self.cur_user,
+            pc,
+        };
+        notify(&mut observer, |o| o.fetch(actor, &instruction));
+
         // Advance the PC by 1 and add it to the back of the queue (for this user)
         // For the commands that don't want to have the PC advanced by 1, they must override this
         process_queue.push_back((pc + 1) % memory_len);
 
         // Handle pre-decrement address modes:
-        handle_pre_decrement(
-            instruction.a_reg,
-            instruction.a_mode,
-            pc,
-            memory_len,
-            &mut self.memory,
-        );
-        handle_pre_decrement(
-            instruction.b_reg,
-            instruction.b_mode,
-            pc,
-            memory_len,
-            &mut self.memory,
-        );
+        for (reg, mode) in [
+            (instruction.a_reg, instruction.a_mode),
+            (instruction.b_reg, instruction.b_mode),
+        ] {
+            let changed = handle_pre_decrement(
+                reg,
+                mode,
+                pc,
+                memory_len,
+                self.limits.write,
+                &mut self.memory,
+            );
+
+            if let Some((address, old)) = changed {
+                let new = self.memory[address];
+                notify(&mut observer, |o| o.write(actor, address, &old, &new));
+            }
+        }
+
+        // The handlers don't report their memory accesses, so work them out up front (only when
+        // they are being observed)
+        let mut write = None;
+        if let Some(observer) = reborrow(&mut observer) {
+            let (a, b) = lazy_operands(instruction, pc, memory_len, self.limits, &self.memory);
+            observer.operands(actor, a, b);
+
+            let (reads, write_address) =
+                lazy_accesses(instruction, pc, memory_len, self.limits, &self.memory);
+
+            for address in reads {
+                observer.read(actor, address);
+            }
+
+            if let Some(address) = write_address {
+                write = Some((address, self.memory[address]));
+            }
+        }
 
         use OpCode::*;
 
         // Run different code for each instruction
         match instruction.op_code {
-            MOV => handlers::mov(instruction, pc, memory_len, &mut self.memory),
-            ADD => handlers::add(instruction, pc, memory_len, &mut self.memory),
-            SUB => handlers::sub(instruction, pc, memory_len, &mut self.memory),
-            MUL => handlers::mul(instruction, pc, memory_len, &mut self.memory),
+            MOV => handlers::mov(instruction, pc, memory_len, self.limits, &mut self.memory),
+            ADD => handlers::add(instruction, pc, memory_len, self.limits, &mut self.memory),
+            SUB => handlers::sub(instruction, pc, memory_len, self.limits, &mut self.memory),
+            MUL => handlers::mul(instruction, pc, memory_len, self.limits, &mut self.memory),
             DIV => {
                 // Will remove the last queued process if a division by zero occurs
-                if !handlers::div(instruction, pc, memory_len, &mut self.memory) {
+                if !handlers::div(instruction, pc, memory_len, self.limits, &mut self.memory) {
                     process_queue.pop_back().unwrap();
+                    notify(&mut observer, |o| o.death(actor));
                 }
             }
             MOD => {
                 // Will remove the last queued process if a division by zero occurs
-                if !handlers::modulo(instruction, pc, memory_len, &mut self.memory) {
+                if !handlers::modulo(instruction, pc, memory_len, self.limits, &mut self.memory) {
                     process_queue.pop_back().unwrap();
+                    notify(&mut observer, |o| o.death(actor));
                 }
             }
             DAT => {
                 // Remove the last queued process (kill it)
                 process_queue.pop_back().unwrap();
+                notify(&mut observer, |o| o.death(actor));
             }
             JMP => {
-                let new_addr = handlers::jmp(instruction, pc, memory_len, &self.memory);
+                let new_addr =
+                    handlers::jmp(instruction, pc, memory_len, self.limits, &self.memory);
                 // Override the program counter for this process to the new address
                 *(process_queue.back_mut().unwrap()) = new_addr;
             }
             JMZ => {
-                if let Some(new_addr) = handlers::jmz(instruction, pc, memory_len, &self.memory) {
+                if let Some(new_addr) =
+                    handlers::jmz(instruction, pc, memory_len, self.limits, &self.memory)
+                {
                     *(process_queue.back_mut().unwrap()) = new_addr;
                 }
             }
             JMN => {
-                if let Some(new_addr) = handlers::jmn(instruction, pc, memory_len, &self.memory) {
+                if let Some(new_addr) =
+                    handlers::jmn(instruction, pc, memory_len, self.limits, &self.memory)
+                {
                     *(process_queue.back_mut().unwrap()) = new_addr;
                 }
             }
             DJN => {
-                if let Some(new_addr) = handlers::djn(instruction, pc, memory_len, &mut self.memory)
+                if let Some(new_addr) =
+                    handlers::djn(instruction, pc, memory_len, self.limits, &mut self.memory)
                 {
                     *(process_queue.back_mut().unwrap()) = new_addr;
                 }
             }
             SPL => {
                 // If max processes is reached then this command behaves like NOP
-                if process_queue.len() < self.max_processes {
-                    let new_addr = handlers::spl(instruction, pc, memory_len, &self.memory);
+                if process_queue.len() < self.max_processes[self.cur_user] {
+                    let new_addr =
+                        handlers::spl(instruction, pc, memory_len, self.limits, &self.memory);
 
                     // Queue an additional process
                     process_queue.push_back(new_addr);
+                    notify(&mut observer, |o| o.spawn(actor, new_addr));
                 }
             }
             SEQ => {
-                if handlers::seq(instruction, pc, memory_len, &self.memory) {
+                if handlers::seq(instruction, pc, memory_len, self.limits, &self.memory) {
                     *(process_queue.back_mut().unwrap()) += 1
                 }
             }
             SNE => {
-                if handlers::sne(instruction, pc, memory_len, &self.memory) {
+                if handlers::sne(instruction, pc, memory_len, self.limits, &self.memory) {
                     *(process_queue.back_mut().unwrap()) += 1
                 }
             }
             SLT => {
-                if handlers::slt(instruction, pc, memory_len, &self.memory) {
+                if handlers::slt(instruction, pc, memory_len, self.limits, &self.memory) {
                     *(process_queue.back_mut().unwrap()) += 1
                 }
             }
+            LDP => handlers::ldp(
+                instruction,
+                pc,
+                memory_len,
+                self.limits,
+                &mut self.memory,
+                &self.pspace,
+                self.cur_user,
+            ),
+            STP => handlers::stp(
+                instruction,
+                pc,
+                memory_len,
+                self.limits,
+                &self.memory,
+                &mut self.pspace,
+                self.cur_user,
+            ),
             // Does nothing
             NOP => {}
         }
 
+        if let Some((address, old)) = write {
+            let new = self.memory[address];
+            notify(&mut observer, |o| o.write(actor, address, &old, &new));
+        }
+
         // Handle post-increment address modes:
-        handle_post_increment(
-            instruction.a_reg,
-            instruction.a_mode,
-            pc,
-            memory_len,
-            &mut self.memory,
-        );
-        handle_post_increment(
-            instruction.b_reg,
-            instruction.b_mode,
-            pc,
-            memory_len,
-            &mut self.memory,
-        );
+        for (reg, mode) in [
+            (instruction.a_reg, instruction.a_mode),
+            (instruction.b_reg, instruction.b_mode),
+        ] {
+            let changed = handle_post_increment(
+                reg,
+                mode,
+                pc,
+                memory_len,
+                self.limits.write,
+                &mut self.memory,
+            );
 
-        // Advance the user counter
-        self.cur_user = (self.cur_user + 1) % self.users_pcs.len();
+            if let Some((address, old)) = changed {
+                let new = self.memory[address];
+                notify(&mut observer, |o| o.write(actor, address, &old, &new));
+            }
+        }
     }
 }
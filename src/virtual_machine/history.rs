@@ -0,0 +1,240 @@
+use crate::{Instruction, OpCode};
+
+use super::observer::{Actor, Observer};
+use super::ownership::CellOwnership;
+use super::VirtualMachine;
+
+use std::collections::VecDeque;
+
+/// The changes made by a single cycle, which are just enough to undo it
+#[derive(Debug, Clone)]
+struct CycleDelta {
+    /// The user whose process ran
+    user: usize,
+    /// The number of instructions the user had executed during its turn before this cycle
+    turns: usize,
+    /// The address that was popped from the front of the user's process queue
+    popped: usize,
+    /// The number of addresses that were pushed onto the back of the user's process queue
+    pushed: usize,
+    /// The address and previous value of each cell that was written, in the order of the writes
+    writes: Vec<(usize, Instruction)>,
+    /// The index and previous value of each P-space location of the user that was changed
+    pspace: Vec<(isize, isize)>,
+    /// The address and previous ownership of each cell whose ownership changed
+    ownership: Vec<(usize, CellOwnership)>,
+}
+
+/// The most recent cycles of a VM, see `VirtualMachine::record_history`
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    deltas: VecDeque<CycleDelta>,
+    /// The maximum number of cycles that are kept
+    limit: usize,
+}
+
+impl History {
+    /// Forgets every recorded cycle
+    pub(super) fn clear(&mut self) {
+        self.deltas.clear();
+    }
+}
+
+/// Records the previous value of every cell that is written and passes the events on to another
+/// observer
+struct WriteRecorder<'a> {
+    writes: Vec<(usize, Instruction)>,
+    next: Option<&'a mut dyn Observer>,
+}
+
+impl Observer for WriteRecorder<'_> {
+    fn fetch(&mut self, actor: Actor, instruction: &Instruction) {
+        if let Some(next) = &mut self.next {
+            next.fetch(actor, instruction);
+        }
+    }
+
+    fn operands(&mut self, actor: Actor, a: usize, b: usize) {
+        if let Some(next) = &mut self.next {
+            next.operands(actor, a, b);
+        }
+    }
+
+    fn read(&mut self, actor: Actor, address: usize) {
+        if let Some(next) = &mut self.next {
+            next.read(actor, address);
+        }
+    }
+
+    fn write(&mut self, actor: Actor, address: usize, old: &Instruction, new: &Instruction) {
+        self.writes.push((address, *old));
+
+        if let Some(next) = &mut self.next {
+            next.write(actor, address, old, new);
+        }
+    }
+
+    fn spawn(&mut self, actor: Actor, new_pc: usize) {
+        if let Some(next) = &mut self.next {
+            next.spawn(actor, new_pc);
+        }
+    }
+
+    fn death(&mut self, actor: Actor) {
+        if let Some(next) = &mut self.next {
+            next.death(actor);
+        }
+    }
+}
+
+impl VirtualMachine {
+    /// Starts recording the changes made by each cycle so that the most recent `limit` cycles
+    /// can be undone with `step_back`. Any history that was already recorded is kept (up to the
+    /// new limit).
+    ///
+    /// The history is not included when a snapshot is saved to a file.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, VirtualMachine};
+    ///
+    /// let mut vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+    /// vm.record_history(100);
+    ///
+    /// vm.cycle();
+    /// vm.cycle();
+    /// assert_eq!(vm.get_users_pcs()[0][0], 2);
+    ///
+    /// assert!(vm.step_back());
+    /// assert_eq!(vm.get_users_pcs()[0][0], 1);
+    /// assert_eq!(vm.get_memory()[2], vm.get_memory()[9]);
+    /// ```
+    pub fn record_history(&mut self, limit: usize) {
+        let mut history = self.history.take().unwrap_or_else(|| History {
+            deltas: VecDeque::new(),
+            limit,
+        });
+
+        history.limit = limit;
+        while history.deltas.len() > limit {
+            history.deltas.pop_front();
+        }
+
+        self.history = Some(history);
+    }
+
+    /// Stops recording the changes made by each cycle and forgets the recorded history
+    pub fn stop_recording_history(&mut self) {
+        self.history = None;
+    }
+
+    /// The number of cycles that can currently be undone
+    pub fn get_history_len(&self) -> usize {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.deltas.len())
+    }
+
+    /// Undoes the most recent cycle, returning false if there was no recorded cycle to undo.
+    /// Running `cycle` again repeats exactly what was undone.
+    pub fn step_back(&mut self) -> bool {
+        let delta = match self.history.as_mut().and_then(|h| h.deltas.pop_back()) {
+            Some(delta) => delta,
+            None => return false,
+        };
+
+        self.cycle_count -= 1;
+        self.cur_user = delta.user;
+        self.turns = delta.turns;
+
+        let queue = &mut self.users_pcs[delta.user];
+        for _ in 0..delta.pushed {
+            queue.pop_back();
+        }
+        queue.push_front(delta.popped);
+
+        // The user is brought back to life if the cycle killed it
+        if let Err(index) = self.alive.binary_search(&delta.user) {
+            self.alive.insert(index, delta.user);
+        }
+
+        for (address, old) in delta.writes.iter().rev() {
+            self.memory[*address] = *old;
+        }
+
+        for (index, old) in delta.pspace.iter().rev() {
+            self.pspace.store(delta.user, *index, *old);
+        }
+
+        if let Some(ownership) = &mut self.ownership {
+            for (address, old) in delta.ownership.iter().rev() {
+                ownership.set_cell(*address, *old);
+            }
+        }
+
+        if let Some(processes) = &mut self.processes {
+            processes.undo_cycle(delta.user, delta.pushed);
+        }
+
+        true
+    }
+
+    /// Undoes up to `cycles` cycles, returning the number that were undone
+    pub fn step_back_by(&mut self, cycles: usize) -> usize {
+        (0..cycles).take_while(|_| self.step_back()).count()
+    }
+
+    /// Runs the next instruction like `execute_tracked`, recording what changed in the history
+    pub(super) fn execute_recorded(&mut self, observer: Option<&mut dyn Observer>) {
+        let user = self.cur_user;
+        let turns = self.turns;
+        let queue_len = self.users_pcs[user].len();
+        let popped = self.users_pcs[user].front().copied();
+
+        // Only STP changes P-space, which is rare enough to justify copying it
+        let pspace_before = match popped {
+            Some(pc) if self.memory[pc].op_code == OpCode::STP => Some(self.pspace.clone()),
+            _ => None,
+        };
+
+        let mut recorder = WriteRecorder {
+            writes: Vec::new(),
+            next: observer,
+        };
+        let mut ownership = Vec::new();
+        self.execute_tracked(Some(&mut recorder), Some(&mut ownership));
+
+        // Executing an instruction always pops the front of the queue and pushes 0 or more
+        // addresses onto the back (it would have panicked if the queue was empty)
+        let pushed = self.users_pcs[user].len() + 1 - queue_len;
+        let popped = popped.expect("The queue had a process to execute");
+
+        let pspace = match pspace_before {
+            Some(before) => (0..before.get_size() as isize)
+                .filter(|index| before.load(user, *index) != self.pspace.load(user, *index))
+                .map(|index| (index, before.load(user, index)))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let history = self
+            .history
+            .as_mut()
+            .expect("The history is recorded while it is enabled");
+
+        if history.deltas.len() == history.limit {
+            history.deltas.pop_front();
+        }
+
+        if history.limit > 0 {
+            history.deltas.push_back(CycleDelta {
+                user,
+                turns,
+                popped,
+                pushed,
+                writes: recorder.writes,
+                pspace,
+                ownership,
+            });
+        }
+    }
+}
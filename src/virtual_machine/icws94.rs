@@ -0,0 +1,398 @@
+use crate::compiler::get_default_modifier;
+use crate::handlers::{fold, Limits};
+use crate::{AddressMode, Instruction, Modifier, OpCode, PSpace};
+
+use super::observer::{notify, reads_targets, writes_target, Actor, Observer};
+
+/// What happens to the process that executed an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Action {
+    /// The process continues at the given address
+    Continue(usize),
+    /// The process continues at the first address and a new process is started at the second
+    Split(usize, usize),
+    /// The process is killed
+    Kill,
+}
+
+/// An evaluated operand
+struct Operand {
+    /// The offset from the PC of the instruction the operand reads from
+    read: usize,
+    /// The offset from the PC of the instruction the operand writes to
+    write: usize,
+    /// A copy of the instruction that was read, taken before any post-increment
+    register: Instruction,
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    A,
+    B,
+}
+
+fn get(instruction: &Instruction, field: Field) -> isize {
+    match field {
+        Field::A => instruction.a_reg,
+        Field::B => instruction.b_reg,
+    }
+}
+
+fn set(instruction: &mut Instruction, field: Field, value: isize) {
+    match field {
+        Field::A => instruction.a_reg = value,
+        Field::B => instruction.b_reg = value,
+    }
+}
+
+/// The pairs of (A register field, B register field) that an instruction with the given modifier
+/// works on. The B register field is also the field that is written to.
+fn fields(modifier: Modifier) -> &'static [(Field, Field)] {
+    use Field::*;
+    use Modifier as m;
+
+    match modifier {
+        m::A => &[(A, A)],
+        m::B => &[(B, B)],
+        m::AB => &[(A, B)],
+        m::BA => &[(B, A)],
+        m::F | m::I => &[(A, A), (B, B)],
+        m::X => &[(A, B), (B, A)],
+        m::None => unreachable!("The default modifier is used instead of `None`"),
+    }
+}
+
+/// The (A register field, B register field) that LDP and STP work on. F, X and I behave like B.
+fn pspace_fields(modifier: Modifier) -> (Field, Field) {
+    use Field::*;
+    use Modifier as m;
+
+    match modifier {
+        m::A => (A, A),
+        m::AB => (A, B),
+        m::BA => (B, A),
+        _ => (B, B),
+    }
+}
+
+/// Brings the fields of an instruction into the range `0..size`
+fn normalise(instruction: Instruction, size: isize) -> Instruction {
+    Instruction {
+        a_reg: instruction.a_reg.rem_euclid(size),
+        b_reg: instruction.b_reg.rem_euclid(size),
+        ..instruction
+    }
+}
+
+fn evaluate_operand(
+    value: isize,
+    mode: AddressMode,
+    pc: usize,
+    limits: Limits,
+    memory: &mut [Instruction],
+    observer: &mut Option<&mut dyn Observer>,
+    actor: Actor,
+) -> Operand {
+    use AddressMode::*;
+
+    let size = memory.len();
+    let max = size as isize;
+    let address = |offset: usize| (pc + offset) % size;
+
+    if mode == Immediate {
+        return Operand {
+            read: 0,
+            write: 0,
+            register: memory[pc],
+        };
+    }
+
+    // Reads and writes are folded into their own windows around the PC
+    let mut read = fold(value, limits.read, size);
+    let mut write = fold(value, limits.write, size);
+    let mut post_increment = None;
+
+    if mode != Direct {
+        let pointer = address(write);
+
+        match mode {
+            PreDecrementIndirectA | PreDecrementIndirectB => {
+                let old = memory[pointer];
+                if mode == PreDecrementIndirectA {
+                    memory[pointer].a_reg = (old.a_reg - 1).rem_euclid(max);
+                } else {
+                    memory[pointer].b_reg = (old.b_reg - 1).rem_euclid(max);
+                }
+                notify(observer, |o| {
+                    o.write(actor, pointer, &old, &memory[pointer])
+                });
+            }
+            PostIncrementIndirectA | PostIncrementIndirectB => post_increment = Some(pointer),
+            _ => {}
+        }
+
+        let field = match mode {
+            IndirectA | PreDecrementIndirectA | PostIncrementIndirectA => Field::A,
+            _ => Field::B,
+        };
+
+        notify(observer, |o| {
+            o.read(actor, address(read));
+            if read != write {
+                o.read(actor, address(write));
+            }
+        });
+
+        read = fold(
+            read as isize + get(&memory[address(read)], field),
+            limits.read,
+            size,
+        );
+        write = fold(
+            write as isize + get(&memory[address(write)], field),
+            limits.write,
+            size,
+        );
+    }
+
+    let register = memory[address(read)];
+
+    if let Some(pointer) = post_increment {
+        let old = memory[pointer];
+        match mode {
+            PostIncrementIndirectA => memory[pointer].a_reg = (old.a_reg + 1).rem_euclid(max),
+            _ => memory[pointer].b_reg = (old.b_reg + 1).rem_euclid(max),
+        }
+        notify(observer, |o| {
+            o.write(actor, pointer, &old, &memory[pointer])
+        });
+    }
+
+    Operand {
+        read,
+        write,
+        register,
+    }
+}
+
+/// Writes the result of `op(b, a)` for each pair of fields into `destination`.
+/// If the operation fails for a pair then that field is left untouched and false is returned.
+fn arithmetic(
+    fields: &[(Field, Field)],
+    a_register: &Instruction,
+    b_register: &Instruction,
+    destination: &mut Instruction,
+    max: isize,
+    op: fn(isize, isize) -> Option<isize>,
+) -> bool {
+    let mut success = true;
+
+    for (a_field, b_field) in fields {
+        match op(get(b_register, *b_field), get(a_register, *a_field)) {
+            Some(result) => set(destination, *b_field, result.rem_euclid(max)),
+            None => success = false,
+        }
+    }
+
+    success
+}
+
+/// Executes the instruction at `pc` following the instruction register semantics of the
+/// ICWS '94 draft, returning what should happen to the process.
+///
+/// The A operand is evaluated completely (including its post-increment) and the instruction it
+/// points to is copied into the A register before the B operand is evaluated. The instruction then
+/// works on these copies, so it isn't affected by its own increments and decrements.
+pub(crate) fn execute(
+    pc: usize,
+    memory: &mut [Instruction],
+    limits: Limits,
+    pspace: &mut PSpace,
+    user: usize,
+    mut observer: Option<&mut dyn Observer>,
+) -> Action {
+    use OpCode::*;
+
+    let size = memory.len();
+    let max = size as isize;
+
+    let actor = Actor { user, pc };
+
+    // The instruction register
+    let instruction = memory[pc];
+    notify(&mut observer, |o| o.fetch(actor, &instruction));
+
+    let a = evaluate_operand(
+        instruction.a_reg,
+        instruction.a_mode,
+        pc,
+        limits,
+        memory,
+        &mut observer,
+        actor,
+    );
+    let b = evaluate_operand(
+        instruction.b_reg,
+        instruction.b_mode,
+        pc,
+        limits,
+        memory,
+        &mut observer,
+        actor,
+    );
+
+    let (reads_a, reads_b) = reads_targets(instruction.op_code);
+    let b_target = if writes_target(instruction.op_code) {
+        b.write
+    } else {
+        b.read
+    };
+    notify(&mut observer, |o| {
+        o.operands(actor, (pc + a.read) % size, (pc + b_target) % size);
+
+        if reads_a && instruction.a_mode != AddressMode::Immediate {
+            o.read(actor, (pc + a.read) % size);
+        }
+        if reads_b && instruction.b_mode != AddressMode::Immediate {
+            o.read(actor, (pc + b.read) % size);
+        }
+    });
+
+    let a_register = normalise(a.register, max);
+    let mut b_register = normalise(b.register, max);
+
+    let modifier = match instruction.modifier {
+        Modifier::None => {
+            get_default_modifier(instruction.op_code, instruction.a_mode, instruction.b_mode)
+        }
+        modifier => modifier,
+    };
+    let fields = fields(modifier);
+
+    let next = Action::Continue((pc + 1) % size);
+    let skip = Action::Continue((pc + 2) % size);
+    let jump = Action::Continue((pc + a.read) % size);
+    let destination = (pc + b.write) % size;
+
+    let operate = |memory: &mut [Instruction], op: fn(isize, isize) -> Option<isize>| {
+        if arithmetic(
+            fields,
+            &a_register,
+            &b_register,
+            &mut memory[destination],
+            max,
+            op,
+        ) {
+            next
+        } else {
+            Action::Kill
+        }
+    };
+
+    let old_destination = memory[destination];
+
+    let action = match instruction.op_code {
+        DAT => Action::Kill,
+        MOV => {
+            if modifier == Modifier::I {
+                memory[destination] = a_register;
+            } else {
+                for (a_field, b_field) in fields {
+                    set(
+                        &mut memory[destination],
+                        *b_field,
+                        get(&a_register, *a_field),
+                    );
+                }
+            }
+            next
+        }
+        ADD => operate(memory, |b, a| Some(b + a)),
+        SUB => operate(memory, |b, a| Some(b - a)),
+        MUL => operate(memory, |b, a| Some(b * a)),
+        DIV => operate(memory, |b, a| b.checked_div(a)),
+        MOD => operate(memory, |b, a| b.checked_rem(a)),
+        JMP => jump,
+        JMZ => {
+            if fields.iter().all(|(_, f)| get(&b_register, *f) == 0) {
+                jump
+            } else {
+                next
+            }
+        }
+        JMN => {
+            if fields.iter().any(|(_, f)| get(&b_register, *f) != 0) {
+                jump
+            } else {
+                next
+            }
+        }
+        DJN => {
+            for (_, field) in fields {
+                let value = get(&memory[destination], *field);
+                set(
+                    &mut memory[destination],
+                    *field,
+                    (value - 1).rem_euclid(max),
+                );
+
+                let value = get(&b_register, *field);
+                set(&mut b_register, *field, value - 1);
+            }
+
+            if fields.iter().any(|(_, f)| get(&b_register, *f) != 0) {
+                jump
+            } else {
+                next
+            }
+        }
+        SEQ | SNE => {
+            let equal = if modifier == Modifier::I {
+                a_register == b_register
+            } else {
+                fields.iter().all(|(a_field, b_field)| {
+                    get(&a_register, *a_field) == get(&b_register, *b_field)
+                })
+            };
+
+            if equal == (instruction.op_code == SEQ) {
+                skip
+            } else {
+                next
+            }
+        }
+        SLT => {
+            if fields
+                .iter()
+                .all(|(a_field, b_field)| get(&a_register, *a_field) < get(&b_register, *b_field))
+            {
+                skip
+            } else {
+                next
+            }
+        }
+        SPL => Action::Split((pc + 1) % size, (pc + a.read) % size),
+        // The P-space location given by the A value is loaded into the B target
+        LDP => {
+            let (a_field, b_field) = pspace_fields(modifier);
+            let value = pspace.load(user, get(&a_register, a_field));
+            set(&mut memory[destination], b_field, value.rem_euclid(max));
+            next
+        }
+        // The A value is stored in the P-space location given by the B value
+        STP => {
+            let (a_field, b_field) = pspace_fields(modifier);
+            pspace.store(user, get(&b_register, b_field), get(&a_register, a_field));
+            next
+        }
+        NOP => next,
+    };
+
+    if writes_target(instruction.op_code) {
+        notify(&mut observer, |o| {
+            o.write(actor, destination, &old_destination, &memory[destination])
+        });
+    }
+
+    action
+}
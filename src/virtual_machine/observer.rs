@@ -0,0 +1,99 @@
+use crate::{Instruction, OpCode};
+
+/// The process that caused an event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Actor {
+    /// The user (warrior) that owns the process
+    pub user: usize,
+    /// The address of the instruction the process was executing
+    pub pc: usize,
+}
+
+/// Receives the events of a VM as it runs, see `VirtualMachine::cycle_with_observer`.
+/// Every method does nothing by default so only the events of interest need to be implemented.
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, Actor, Instruction, Observer, VirtualMachine};
+///
+/// #[derive(Default)]
+/// struct Writes(Vec<usize>);
+///
+/// impl Observer for Writes {
+///     fn write(&mut self, _: Actor, address: usize, _: &Instruction, _: &Instruction) {
+///         self.0.push(address);
+///     }
+/// }
+///
+/// let mut vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+/// let mut writes = Writes::default();
+///
+/// vm.cycle_with_observer(&mut writes);
+/// vm.cycle_with_observer(&mut writes);
+///
+/// assert_eq!(writes.0, vec![1, 2]);
+/// ```
+pub trait Observer {
+    /// A process is about to execute the instruction at `actor.pc`
+    fn fetch(&mut self, _actor: Actor, _instruction: &Instruction) {}
+
+    /// The A and B operands of the instruction at `actor.pc` resolved to the addresses `a` and
+    /// `b`, where an immediate operand resolves to the instruction itself. `b` is the address the
+    /// instruction writes to if it writes to its B target.
+    fn operands(&mut self, _actor: Actor, _a: usize, _b: usize) {}
+
+    /// A process read the instruction at `address` (addresses that are only used as jump targets
+    /// and immediate operands are not reads)
+    fn read(&mut self, _actor: Actor, _address: usize) {}
+
+    /// A process wrote to the instruction at `address`, changing it from `old` to `new`
+    /// (`old` and `new` can be equal)
+    fn write(&mut self, _actor: Actor, _address: usize, _old: &Instruction, _new: &Instruction) {}
+
+    /// A process started a new process at `new_pc`
+    fn spawn(&mut self, _actor: Actor, _new_pc: usize) {}
+
+    /// A process was killed while executing the instruction at `actor.pc`
+    fn death(&mut self, _actor: Actor) {}
+}
+
+/// Calls `event` with the observer, if there is one
+#[inline]
+pub(crate) fn notify(
+    observer: &mut Option<&mut dyn Observer>,
+    event: impl FnOnce(&mut dyn Observer),
+) {
+    if let Some(observer) = observer {
+        event(&mut **observer);
+    }
+}
+
+/// Reborrows the observer so that it can be passed on without giving it up
+#[inline]
+pub(crate) fn reborrow<'a>(
+    observer: &'a mut Option<&mut dyn Observer>,
+) -> Option<&'a mut dyn Observer> {
+    match observer {
+        Some(observer) => Some(&mut **observer),
+        None => None,
+    }
+}
+
+/// Whether an instruction uses the values of its A target and its B target, rather than just
+/// their addresses
+pub(crate) fn reads_targets(op_code: OpCode) -> (bool, bool) {
+    use OpCode::*;
+
+    match op_code {
+        MOV | LDP => (true, false),
+        ADD | SUB | MUL | DIV | MOD | SEQ | SNE | SLT | STP => (true, true),
+        JMZ | JMN | DJN => (false, true),
+        DAT | JMP | SPL | NOP => (false, false),
+    }
+}
+
+/// Whether an instruction writes to its B target
+pub(crate) fn writes_target(op_code: OpCode) -> bool {
+    use OpCode::*;
+
+    matches!(op_code, MOV | ADD | SUB | MUL | DIV | MOD | DJN | LDP)
+}
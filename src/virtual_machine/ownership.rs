@@ -0,0 +1,170 @@
+use crate::Instruction;
+
+use super::observer::{Actor, Observer};
+use super::snapshot::{Encode, SnapshotError};
+
+use std::io::{self, Read, Write};
+
+/// The (writer, executor, last touched) of a single cell
+pub(crate) type CellOwnership = (Option<usize>, Option<usize>, Option<usize>);
+
+/// Which user last wrote to and last executed each cell of the core, and when
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreOwnership {
+    /// The user that last wrote to each cell, `None` if no user has written to it
+    writers: Vec<Option<usize>>,
+    /// The user that last executed each cell, `None` if no user has executed it
+    executors: Vec<Option<usize>>,
+    /// The cycle when each cell was last written to or executed
+    touched: Vec<Option<usize>>,
+}
+
+impl CoreOwnership {
+    /// Creates an ownership map for a core of `size` cells that no user has touched
+    pub fn new(size: usize) -> CoreOwnership {
+        CoreOwnership {
+            writers: vec![None; size],
+            executors: vec![None; size],
+            touched: vec![None; size],
+        }
+    }
+
+    /// The user that last wrote to each cell
+    pub fn writers(&self) -> &[Option<usize>] {
+        &self.writers
+    }
+
+    /// The user that last executed each cell
+    pub fn executors(&self) -> &[Option<usize>] {
+        &self.executors
+    }
+
+    /// The cycle (see `VirtualMachine::get_cycle_count`) when each cell was last written to or
+    /// executed
+    pub fn last_touched(&self) -> &[Option<usize>] {
+        &self.touched
+    }
+
+    /// The number of cells that each of the `users` last wrote to
+    pub fn territory(&self, users: usize) -> Vec<usize> {
+        let mut territory = vec![0; users];
+
+        for user in self.writers.iter().flatten() {
+            territory[*user] += 1;
+        }
+
+        territory
+    }
+
+    /// The (writer, executor, last touched) of a cell
+    pub(crate) fn cell(&self, address: usize) -> CellOwnership {
+        (
+            self.writers[address],
+            self.executors[address],
+            self.touched[address],
+        )
+    }
+
+    pub(crate) fn set_cell(&mut self, address: usize, cell: CellOwnership) {
+        let (writer, executor, touched) = cell;
+        self.writers[address] = writer;
+        self.executors[address] = executor;
+        self.touched[address] = touched;
+    }
+
+    pub(crate) fn record_write(&mut self, user: usize, address: usize, cycle: usize) {
+        self.writers[address] = Some(user);
+        self.touched[address] = Some(cycle);
+    }
+
+    pub(crate) fn record_execute(&mut self, user: usize, address: usize, cycle: usize) {
+        self.executors[address] = Some(user);
+        self.touched[address] = Some(cycle);
+    }
+}
+
+impl Encode for CoreOwnership {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.writers.encode(writer)?;
+        self.executors.encode(writer)?;
+        self.touched.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<CoreOwnership, SnapshotError> {
+        let ownership = CoreOwnership {
+            writers: Vec::decode(reader)?,
+            executors: Vec::decode(reader)?,
+            touched: Vec::decode(reader)?,
+        };
+
+        let size = ownership.writers.len();
+        if ownership.executors.len() != size || ownership.touched.len() != size {
+            return Err(SnapshotError::Invalid("the ownership map is inconsistent"));
+        }
+
+        Ok(ownership)
+    }
+}
+
+/// Updates the ownership map from the events of a cycle and passes the events on to another
+/// observer
+pub(crate) struct OwnershipTracker<'a, 'b, 'c> {
+    pub ownership: &'a mut CoreOwnership,
+    pub cycle: usize,
+    pub next: Option<&'b mut dyn Observer>,
+    /// Where the previous ownership of each changed cell is recorded, if anywhere
+    pub journal: Option<&'c mut Vec<(usize, CellOwnership)>>,
+}
+
+impl OwnershipTracker<'_, '_, '_> {
+    fn remember(&mut self, address: usize) {
+        if let Some(journal) = &mut self.journal {
+            journal.push((address, self.ownership.cell(address)));
+        }
+    }
+}
+
+impl Observer for OwnershipTracker<'_, '_, '_> {
+    fn fetch(&mut self, actor: Actor, instruction: &Instruction) {
+        self.remember(actor.pc);
+        self.ownership
+            .record_execute(actor.user, actor.pc, self.cycle);
+
+        if let Some(next) = &mut self.next {
+            next.fetch(actor, instruction);
+        }
+    }
+
+    fn operands(&mut self, actor: Actor, a: usize, b: usize) {
+        if let Some(next) = &mut self.next {
+            next.operands(actor, a, b);
+        }
+    }
+
+    fn read(&mut self, actor: Actor, address: usize) {
+        if let Some(next) = &mut self.next {
+            next.read(actor, address);
+        }
+    }
+
+    fn write(&mut self, actor: Actor, address: usize, old: &Instruction, new: &Instruction) {
+        self.remember(address);
+        self.ownership.record_write(actor.user, address, self.cycle);
+
+        if let Some(next) = &mut self.next {
+            next.write(actor, address, old, new);
+        }
+    }
+
+    fn spawn(&mut self, actor: Actor, new_pc: usize) {
+        if let Some(next) = &mut self.next {
+            next.spawn(actor, new_pc);
+        }
+    }
+
+    fn death(&mut self, actor: Actor) {
+        if let Some(next) = &mut self.next {
+            next.death(actor);
+        }
+    }
+}
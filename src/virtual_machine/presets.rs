@@ -0,0 +1,308 @@
+use crate::{ExecutionMode, Instruction, MatchSettings, OpCode};
+
+use std::fmt;
+
+/// The settings of commonly used hills
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preset {
+    /// The ICWS '94 draft hill
+    Draft94,
+    /// The ICWS '94 draft hill without P-space
+    NoPSpace94,
+    /// The beginner hill, which uses the '94 draft settings
+    Beginner,
+    /// The tiny hill with a core size of 800
+    Tiny,
+    /// The nano hill with a core size of 80
+    Nano,
+    /// The limited process hill where each warrior can have at most 8 processes
+    LimitedProcess,
+    /// The experimental hill with a core size of 55440
+    Experimental,
+}
+
+impl Preset {
+    /// Every preset
+    pub const ALL: [Preset; 7] = [
+        Preset::Draft94,
+        Preset::NoPSpace94,
+        Preset::Beginner,
+        Preset::Tiny,
+        Preset::Nano,
+        Preset::LimitedProcess,
+        Preset::Experimental,
+    ];
+
+    /// The short name of the preset, as used by `Preset::from_name`
+    pub fn name(self) -> &'static str {
+        use Preset::*;
+        match self {
+            Draft94 => "94",
+            NoPSpace94 => "94nop",
+            Beginner => "beginner",
+            Tiny => "tiny",
+            Nano => "nano",
+            LimitedProcess => "lp",
+            Experimental => "exp",
+        }
+    }
+
+    /// Finds a preset from its short name
+    /// # Example
+    /// ```
+    /// use darwin_lib::Preset;
+    /// assert_eq!(Preset::from_name("nano"), Some(Preset::Nano));
+    /// assert_eq!(Preset::from_name("huge"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL
+            .iter()
+            .copied()
+            .find(|preset| preset.name() == name)
+    }
+
+    /// The match settings used by the hill, which run with ICWS '94 semantics
+    pub fn settings(self) -> MatchSettings {
+        use Preset::*;
+
+        // (core size, max processes, max cycles, max length, min separation)
+        let (core_size, max_processes, max_cycles, max_length, min_separation) = match self {
+            Draft94 | NoPSpace94 | Beginner => (8000, 8000, 80000, 100, 100),
+            Tiny => (800, 800, 8000, 20, 20),
+            Nano => (80, 80, 800, 5, 5),
+            LimitedProcess => (8000, 8, 80000, 200, 200),
+            Experimental => (55440, 10000, 500_000, 200, 200),
+        };
+
+        MatchSettings {
+            core_size,
+            max_processes,
+            max_cycles,
+            max_length,
+            min_separation,
+            rounds: 250,
+            execution_mode: ExecutionMode::Icws94,
+            pspace_size: if self == NoPSpace94 {
+                0
+            } else {
+                core_size / 16
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The reasons a warrior can't take part in a match
+#[derive(Debug, PartialEq)]
+pub enum WarriorError {
+    /// The warrior has no instructions
+    Empty,
+    /// The warrior has more instructions than the match allows.
+    /// Holds the length of the warrior and the maximum length.
+    TooLong(usize, usize),
+    /// The warrior uses LDP or STP but the match has no P-space
+    PSpaceDisabled,
+}
+
+impl fmt::Display for WarriorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WarriorError::Empty => write!(f, "The warrior has no instructions"),
+            WarriorError::TooLong(len, max) => write!(
+                f,
+                "The warrior has {} instructions but at most {} are allowed",
+                len, max
+            ),
+            WarriorError::PSpaceDisabled => {
+                write!(f, "The warrior uses P-space but P-space is disabled")
+            }
+        }
+    }
+}
+
+/// The reasons a match can't be played with some settings
+#[derive(Debug, PartialEq)]
+pub enum SettingsError {
+    /// The match has no warriors
+    NoWarriors,
+    /// A warrior can't take part in the match (see `MatchSettings::validate_warrior`).
+    /// Holds the warrior and the reason.
+    Warrior(usize, WarriorError),
+    /// The warriors and the space between them don't fit in the core.
+    /// Holds the space that is needed and the core size.
+    NotEnoughRoom(usize, usize),
+    /// A fixed position was given for a match that doesn't have two warriors.
+    /// Holds the number of warriors.
+    FixedPositionWarriors(usize),
+    /// The fixed position is too close to the first warrior.
+    /// Holds the position and the lowest and highest positions that are allowed.
+    FixedPositionOutOfRange(usize, usize, usize),
+    /// The handicap of a warrior gives it a speed of 0. Holds the warrior.
+    ZeroSpeed(usize),
+    /// The read limit is 0 or larger than the core. Holds the limit and the core size.
+    ReadLimit(usize, usize),
+    /// The write limit is 0 or larger than the core. Holds the limit and the core size.
+    WriteLimit(usize, usize),
+    /// A battle wasn't given a team for every warrior.
+    /// Holds the number of teams and the number of warriors.
+    Teams(usize, usize),
+    /// A P-space was made for a different number of warriors than the match has.
+    /// Holds the number of warriors of the P-space and of the match.
+    PSpaceWarriors(usize, usize),
+    /// A P-space has a different number of locations than `MatchSettings::pspace_size`.
+    /// Holds the size of the P-space and the size the match uses.
+    PSpaceSize(usize, usize),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::NoWarriors => write!(f, "A match needs at least one warrior"),
+            SettingsError::Warrior(warrior, e) => {
+                write!(f, "Warrior {} can't take part in the match: {}", warrior, e)
+            }
+            SettingsError::NotEnoughRoom(needed, size) => write!(
+                f,
+                "The warriors need a core of at least {} instructions but the core has {}",
+                needed, size
+            ),
+            SettingsError::FixedPositionWarriors(warriors) => write!(
+                f,
+                "A fixed position can only be used with two warriors, not {}",
+                warriors
+            ),
+            SettingsError::FixedPositionOutOfRange(position, low, high) => write!(
+                f,
+                "The fixed position {} must be between {} and {}",
+                position, low, high
+            ),
+            SettingsError::ZeroSpeed(warrior) => {
+                write!(f, "Warrior {} has a speed of 0", warrior)
+            }
+            SettingsError::ReadLimit(limit, size) => write!(
+                f,
+                "The read limit {} must be between 1 and the core size {}",
+                limit, size
+            ),
+            SettingsError::WriteLimit(limit, size) => write!(
+                f,
+                "The write limit {} must be between 1 and the core size {}",
+                limit, size
+            ),
+            SettingsError::Teams(teams, warriors) => write!(
+                f,
+                "There are {} teams but {} warriors, each warrior needs a team",
+                teams, warriors
+            ),
+            SettingsError::PSpaceWarriors(pspace, warriors) => write!(
+                f,
+                "The P-space is for {} warriors but the match has {}",
+                pspace, warriors
+            ),
+            SettingsError::PSpaceSize(size, pspace_size) => write!(
+                f,
+                "The P-space has {} locations but the match uses {}",
+                size, pspace_size
+            ),
+        }
+    }
+}
+
+impl MatchSettings {
+    /// The settings used by the given hill
+    pub fn from_preset(preset: Preset) -> MatchSettings {
+        preset.settings()
+    }
+
+    /// Checks that a warrior is allowed to take part in a match with these settings
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, MatchSettings, Preset, WarriorError};
+    ///
+    /// let settings = MatchSettings::from_preset(Preset::Nano);
+    /// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    ///
+    /// assert_eq!(settings.validate_warrior(&imp), Ok(()));
+    /// assert_eq!(settings.validate_warrior(&imp.repeat(6)), Err(WarriorError::TooLong(6, 5)));
+    /// ```
+    pub fn validate_warrior(&self, program: &[Instruction]) -> Result<(), WarriorError> {
+        if program.is_empty() {
+            Err(WarriorError::Empty)
+        } else if program.len() > self.max_length {
+            Err(WarriorError::TooLong(program.len(), self.max_length))
+        } else if self.pspace_size == 0 && uses_pspace(program) {
+            Err(WarriorError::PSpaceDisabled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that a match between the programs can be played with these settings, so that the
+    /// warriors can be loaded without panicking.
+    /// Every warrior has to pass `validate_warrior` and needs its own length plus
+    /// `min_separation` instructions of the core.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, MatchSettings, SettingsError};
+    ///
+    /// let imp = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    /// let settings = MatchSettings { core_size: 200, ..Default::default() };
+    ///
+    /// assert_eq!(settings.validate_match(&[imp.clone()]), Ok(()));
+    /// assert_eq!(
+    ///     settings.validate_match(&[imp.clone(), imp]),
+    ///     Err(SettingsError::NotEnoughRoom(202, 200))
+    /// );
+    /// ```
+    pub fn validate_match(&self, programs: &[Vec<Instruction>]) -> Result<(), SettingsError> {
+        if programs.is_empty() {
+            return Err(SettingsError::NoWarriors);
+        }
+
+        for (warrior, program) in programs.iter().enumerate() {
+            self.validate_warrior(program)
+                .map_err(|e| SettingsError::Warrior(warrior, e))?;
+        }
+
+        let needed: usize = programs
+            .iter()
+            .map(|program| program.len() + self.min_separation)
+            .sum();
+        if self.core_size == 0 || needed > self.core_size {
+            return Err(SettingsError::NotEnoughRoom(needed, self.core_size));
+        }
+
+        self.limits()?;
+
+        if let Some(position) = self.fixed_position {
+            if programs.len() != 2 {
+                return Err(SettingsError::FixedPositionWarriors(programs.len()));
+            }
+
+            // The second warrior can't overlap either end of the first one
+            let low = self.min_separation.max(programs[0].len());
+            let high = self.core_size - self.min_separation.max(programs[1].len());
+            if position < low || position > high {
+                return Err(SettingsError::FixedPositionOutOfRange(position, low, high));
+            }
+        }
+
+        match (0..programs.len()).find(|warrior| self.warrior_limits(*warrior).speed == 0) {
+            Some(warrior) => Err(SettingsError::ZeroSpeed(warrior)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Whether a warrior contains any instructions that use P-space
+pub(crate) fn uses_pspace(program: &[Instruction]) -> bool {
+    program
+        .iter()
+        .any(|i| i.op_code == OpCode::LDP || i.op_code == OpCode::STP)
+}
@@ -0,0 +1,219 @@
+use super::VirtualMachine;
+
+use std::collections::VecDeque;
+
+/// A single process, which keeps its id for its whole life
+#[derive(Debug, Clone, PartialEq)]
+pub struct Process {
+    /// The id of the process, which is its index in `ProcessTree::processes`
+    pub id: usize,
+    /// The user the process belongs to
+    pub user: usize,
+    /// The process that started this one with SPL, `None` for the processes that were running
+    /// when the tree was created
+    pub parent: Option<usize>,
+    /// The processes that this one started with SPL, in the order they were started
+    pub children: Vec<usize>,
+    /// The cycle (see `VirtualMachine::get_cycle_count`) when the process was started
+    pub birth_cycle: usize,
+    /// The cycle when the process was killed, `None` if it is still alive
+    pub death_cycle: Option<usize>,
+}
+
+/// Every process that each user has had and which process started which, see
+/// `VirtualMachine::track_processes`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessTree {
+    processes: Vec<Process>,
+    /// The ids of the live processes of each user, in the same order as their program counters
+    /// in `VirtualMachine::get_users_pcs`
+    queues: Vec<VecDeque<usize>>,
+    /// The ids of the processes that have been killed, in the order they were killed
+    deaths: Vec<usize>,
+}
+
+impl ProcessTree {
+    /// Creates a tree where the users start with the given numbers of processes at `cycle`
+    pub fn new(processes: &[usize], cycle: usize) -> ProcessTree {
+        let mut tree = ProcessTree {
+            processes: Vec::new(),
+            queues: vec![VecDeque::new(); processes.len()],
+            deaths: Vec::new(),
+        };
+
+        for (user, count) in processes.iter().enumerate() {
+            for _ in 0..*count {
+                let id = tree.start(user, None, cycle);
+                tree.queues[user].push_back(id);
+            }
+        }
+
+        tree
+    }
+
+    /// Every process that has been started, indexed by id
+    pub fn processes(&self) -> &[Process] {
+        &self.processes
+    }
+
+    pub fn get_process(&self, id: usize) -> &Process {
+        &self.processes[id]
+    }
+
+    /// The ids of the live processes of `user`, with the process that runs next at the front
+    pub fn get_queue(&self, user: usize) -> &VecDeque<usize> {
+        &self.queues[user]
+    }
+
+    /// The processes of `user` that weren't started by another process, which are the roots of
+    /// the user's process tree
+    pub fn roots(&self, user: usize) -> Vec<usize> {
+        self.processes
+            .iter()
+            .filter(|process| process.user == user && process.parent.is_none())
+            .map(|process| process.id)
+            .collect()
+    }
+
+    /// The number of processes between `id` and the root of its tree, so a root has a generation
+    /// of 0 and its children have a generation of 1
+    pub fn generation(&self, id: usize) -> usize {
+        let mut generation = 0;
+        let mut process = &self.processes[id];
+
+        while let Some(parent) = process.parent {
+            generation += 1;
+            process = &self.processes[parent];
+        }
+
+        generation
+    }
+
+    /// The ids of every process that was started by `id`, its children, their children and so on
+    pub fn descendants(&self, id: usize) -> Vec<usize> {
+        let mut descendants = Vec::new();
+        let mut pending = vec![id];
+
+        while let Some(id) = pending.pop() {
+            for child in &self.processes[id].children {
+                descendants.push(*child);
+                pending.push(*child);
+            }
+        }
+
+        descendants.sort();
+        descendants
+    }
+
+    fn start(&mut self, user: usize, parent: Option<usize>, cycle: usize) -> usize {
+        let id = self.processes.len();
+
+        self.processes.push(Process {
+            id,
+            user,
+            parent,
+            children: Vec::new(),
+            birth_cycle: cycle,
+            death_cycle: None,
+        });
+
+        if let Some(parent) = parent {
+            self.processes[parent].children.push(id);
+        }
+
+        id
+    }
+
+    /// Updates the tree after the front process of `user` ran during `cycle` and `pushed`
+    /// addresses were pushed onto the back of the user's queue (0 if the process was killed, 2 if
+    /// it started another process)
+    pub(crate) fn record_cycle(&mut self, user: usize, pushed: usize, cycle: usize) {
+        let id = self.queues[user]
+            .pop_front()
+            .expect("The process tree matches the process queues");
+
+        if pushed == 0 {
+            self.processes[id].death_cycle = Some(cycle);
+            self.deaths.push(id);
+            return;
+        }
+
+        self.queues[user].push_back(id);
+
+        if pushed == 2 {
+            let child = self.start(user, Some(id), cycle);
+            self.queues[user].push_back(child);
+        }
+    }
+
+    /// Undoes `record_cycle`
+    pub(crate) fn undo_cycle(&mut self, user: usize, pushed: usize) {
+        let queue = &mut self.queues[user];
+
+        let id = match pushed {
+            0 => {
+                let id = self.deaths.pop().expect("A process was killed");
+                self.processes[id].death_cycle = None;
+                id
+            }
+            _ => {
+                if pushed == 2 {
+                    let child = queue.pop_back().expect("A process was started");
+                    let parent = self.processes[child]
+                        .parent
+                        .expect("The child has a parent");
+                    self.processes[parent].children.pop();
+                    self.processes.pop();
+                }
+
+                queue.pop_back().expect("The process continued")
+            }
+        };
+
+        self.queues[user].push_front(id);
+    }
+}
+
+impl VirtualMachine {
+    /// Starts giving every process an id and recording which process started which (see
+    /// `ProcessTree`). The processes that are already running become the roots of the tree.
+    /// Any history that was recorded before this is called is forgotten.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, VirtualMachine};
+    ///
+    /// let program = create_program! {
+    ///     SPL(B, 0, Direct, 0, Immediate)
+    ///     JMP(B, -1, Direct, 0, Immediate)
+    /// };
+    ///
+    /// let mut vm = VirtualMachine::new_simple(10, program);
+    /// vm.track_processes();
+    /// for _ in 0..3 {
+    ///     vm.cycle();
+    /// }
+    ///
+    /// let tree = vm.get_processes().unwrap();
+    /// assert_eq!(tree.roots(0), vec![0]);
+    /// assert_eq!(tree.get_process(0).children, vec![1]);
+    /// assert_eq!(tree.get_process(2).parent, Some(1));
+    /// assert_eq!(tree.generation(2), 2);
+    /// ```
+    pub fn track_processes(&mut self) {
+        if self.processes.is_none() {
+            let processes: Vec<usize> = self.users_pcs.iter().map(|queue| queue.len()).collect();
+            self.processes = Some(ProcessTree::new(&processes, self.cycle_count));
+
+            // The recorded cycles can't be undone without the processes they changed
+            if let Some(history) = &mut self.history {
+                history.clear();
+            }
+        }
+    }
+
+    /// The process tree of every user, or `None` if it isn't being tracked (see
+    /// `track_processes`)
+    pub fn get_processes(&self) -> Option<&ProcessTree> {
+        self.processes.as_ref()
+    }
+}
@@ -0,0 +1,143 @@
+use super::snapshot::{Encode, SnapshotError};
+
+use std::io::{self, Read, Write};
+
+/// Private storage (P-space) that lets warriors keep data between the rounds of a match.
+///
+/// Location 0 of each warrior's P-space holds the result of its previous round: -1 before the
+/// first round, 0 if the warrior lost, otherwise the number of warriors that survived. The other
+/// locations can be shared between warriors that were given the same PIN.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PSpace {
+    /// The number of locations in each warrior's P-space
+    size: usize,
+    /// The result of the previous round of each warrior (location 0)
+    results: Vec<isize>,
+    /// The index into `areas` of the storage used by each warrior
+    owners: Vec<usize>,
+    /// The storage areas, each is shared by every warrior with the same PIN
+    areas: Vec<Vec<isize>>,
+}
+
+impl PSpace {
+    /// Creates a P-space of `size` locations for each of the `warriors`, none of which are shared
+    pub fn new(size: usize, warriors: usize) -> PSpace {
+        PSpace::with_pins(size, &vec![None; warriors])
+    }
+
+    /// Creates a P-space of `size` locations for each warrior. Warriors with the same PIN share
+    /// every location except location 0, warriors without a PIN have their own P-space.
+    /// # Example
+    /// ```
+    /// use darwin_lib::PSpace;
+    ///
+    /// let mut pspace = PSpace::with_pins(16, &[Some(7), None, Some(7)]);
+    /// pspace.store(0, 3, 42);
+    ///
+    /// assert_eq!(pspace.load(2, 3), 42);
+    /// assert_eq!(pspace.load(1, 3), 0);
+    /// ```
+    pub fn with_pins(size: usize, pins: &[Option<u64>]) -> PSpace {
+        let mut owners = Vec::with_capacity(pins.len());
+        let mut area_pins: Vec<Option<u64>> = Vec::new();
+
+        for pin in pins {
+            let shared = pin.and_then(|pin| area_pins.iter().position(|p| *p == Some(pin)));
+
+            match shared {
+                Some(area) => owners.push(area),
+                None => {
+                    owners.push(area_pins.len());
+                    area_pins.push(*pin);
+                }
+            }
+        }
+
+        PSpace {
+            size,
+            results: vec![-1; pins.len()],
+            owners,
+            areas: vec![vec![0; size]; area_pins.len()],
+        }
+    }
+
+    /// The number of locations in each warrior's P-space
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Reads a location of a warrior's P-space. The index wraps around the size of the P-space.
+    /// If the P-space has no locations then this is always 0.
+    pub fn load(&self, warrior: usize, index: isize) -> isize {
+        if self.size == 0 {
+            return 0;
+        }
+
+        match index.rem_euclid(self.size as isize) as usize {
+            0 => self.results[warrior],
+            index => self.areas[self.owners[warrior]][index],
+        }
+    }
+
+    /// Writes to a location of a warrior's P-space. The index wraps around the size of the P-space.
+    /// If the P-space has no locations then this does nothing.
+    pub fn store(&mut self, warrior: usize, index: isize, value: isize) {
+        if self.size == 0 {
+            return;
+        }
+
+        match index.rem_euclid(self.size as isize) as usize {
+            0 => self.results[warrior] = value,
+            index => self.areas[self.owners[warrior]][index] = value,
+        }
+    }
+
+    /// The number of warriors that have a P-space
+    pub fn get_warriors(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Sets location 0 of a warrior's P-space to the result of a round
+    pub fn set_result(&mut self, warrior: usize, result: isize) {
+        self.results[warrior] = result;
+    }
+
+    /// Rotates the warriors so that warrior `n` becomes warrior 0
+    pub(crate) fn rotate_left(&mut self, n: usize) {
+        self.results.rotate_left(n);
+        self.owners.rotate_left(n);
+    }
+
+    /// Undoes `rotate_left`
+    pub(crate) fn rotate_right(&mut self, n: usize) {
+        self.results.rotate_right(n);
+        self.owners.rotate_right(n);
+    }
+}
+
+impl Encode for PSpace {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.size.encode(writer)?;
+        self.results.encode(writer)?;
+        self.owners.encode(writer)?;
+        self.areas.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<PSpace, SnapshotError> {
+        let pspace = PSpace {
+            size: usize::decode(reader)?,
+            results: Vec::decode(reader)?,
+            owners: Vec::decode(reader)?,
+            areas: Vec::decode(reader)?,
+        };
+
+        if pspace.owners.len() != pspace.results.len()
+            || pspace.owners.iter().any(|area| *area >= pspace.areas.len())
+            || pspace.areas.iter().any(|area| area.len() != pspace.size)
+        {
+            return Err(SnapshotError::Invalid("the P-space is inconsistent"));
+        }
+
+        Ok(pspace)
+    }
+}
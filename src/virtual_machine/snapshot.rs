@@ -0,0 +1,510 @@
+use crate::handlers::Limits;
+use crate::{AddressMode, Instruction, Modifier, OpCode};
+
+use super::{ExecutionMode, VirtualMachine};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// The bytes every snapshot file starts with
+const MAGIC: &[u8; 4] = b"DWVM";
+/// The version of the snapshot format
+const VERSION: u8 = 2;
+
+/// An error that occurred while restoring a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot couldn't be read
+    Io(io::Error),
+    /// The data is not a snapshot
+    NotASnapshot,
+    /// The snapshot was written by an unsupported version of the format. Holds the version.
+    UnsupportedVersion(u8),
+    /// The snapshot is corrupt. Holds a description of the problem.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "Couldn't read the snapshot: {}", e),
+            SnapshotError::NotASnapshot => write!(f, "The data is not a VM snapshot"),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "Version {} of the snapshot format is not supported", v)
+            }
+            SnapshotError::Invalid(e) => write!(f, "The snapshot is invalid: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> SnapshotError {
+        SnapshotError::Io(e)
+    }
+}
+
+/// A copy of the complete state of a VM, see `VirtualMachine::snapshot`
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    vm: VirtualMachine,
+}
+
+impl Snapshot {
+    /// The state of the VM when the snapshot was taken
+    pub fn get_vm(&self) -> &VirtualMachine {
+        &self.vm
+    }
+
+    /// Writes the snapshot in a compact binary format that can be read by `Snapshot::load`.
+    /// The statistics of the VM are written, but its history and process tree aren't, so a loaded
+    /// VM has to start tracking them again.
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        self.vm.encode(writer)
+    }
+
+    /// Reads a snapshot written by `Snapshot::save`
+    pub fn load<R: Read>(reader: &mut R) -> Result<Snapshot, SnapshotError> {
+        let mut header = [0; 5];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| SnapshotError::NotASnapshot)?;
+
+        if &header[..4] != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        if header[4] != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(header[4]));
+        }
+
+        Ok(Snapshot {
+            vm: VirtualMachine::decode(reader)?,
+        })
+    }
+
+    /// The snapshot in the format written by `Snapshot::save`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.save(&mut bytes).expect("Writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Reads a snapshot from the bytes written by `Snapshot::save`
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        Snapshot::load(&mut bytes)
+    }
+
+    /// Writes the snapshot to a file, see `Snapshot::save`
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Reads a snapshot from a file, see `Snapshot::load`
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Snapshot, SnapshotError> {
+        Snapshot::load(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// Hashes the bytes written to it with 64 bit FNV-1a, which (unlike the hashers in `std`) is
+/// guaranteed to give the same hash on every platform and version
+struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> StateHasher {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Write for StateHasher {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl VirtualMachine {
+    /// A hash of the state that affects how the VM runs: the memory, the process queues, whose
+    /// turn it is, the P-space and the cycle count. The hash is the same on every platform, so it
+    /// can be used to check that two VMs ran in exactly the same way.
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, VirtualMachine};
+    ///
+    /// let program = create_program! { MOV(I, 0, Direct, 1, Direct) };
+    /// let mut a = VirtualMachine::new_simple(10, program.clone());
+    /// let mut b = VirtualMachine::new_simple(10, program);
+    /// assert_eq!(a.state_hash(), b.state_hash());
+    ///
+    /// a.cycle();
+    /// assert_ne!(a.state_hash(), b.state_hash());
+    /// ```
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::default();
+        self.encode_state(&mut hasher).expect("Hashing can't fail");
+        hasher.0
+    }
+
+    /// Writes the parts of the VM that are hashed by `state_hash`
+    fn encode_state<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.memory.encode(writer)?;
+        self.users_pcs.encode(writer)?;
+        self.cur_user.encode(writer)?;
+        self.turns.encode(writer)?;
+        self.pspace.encode(writer)?;
+        self.cycle_count.encode(writer)
+    }
+
+    /// Copies the complete state of the VM so that it can be restored later (or saved to a file)
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, VirtualMachine};
+    ///
+    /// let mut vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+    /// let snapshot = vm.snapshot();
+    ///
+    /// vm.cycle();
+    /// assert_eq!(vm.get_users_pcs()[0][0], 1);
+    ///
+    /// vm.restore(&snapshot);
+    /// assert_eq!(vm.get_users_pcs()[0][0], 0);
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { vm: self.clone() }
+    }
+
+    /// Puts the VM back into the state it was in when the snapshot was taken
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.clone_from(&snapshot.vm);
+    }
+
+    /// Creates a VM in the state it was in when the snapshot was taken
+    pub fn from_snapshot(snapshot: Snapshot) -> VirtualMachine {
+        snapshot.vm
+    }
+}
+
+/// A value that can be written to and read from a snapshot
+pub(crate) trait Encode: Sized {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    fn decode<R: Read>(reader: &mut R) -> Result<Self, SnapshotError>;
+}
+
+// Integers are written as LEB128 variable length integers, so small values take a single byte
+impl Encode for u64 {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut value = *self;
+
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                return writer.write_all(&[byte]);
+            }
+
+            writer.write_all(&[byte | 0x80])?;
+        }
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<u64, SnapshotError> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0];
+            reader.read_exact(&mut byte)?;
+
+            value |= u64::from(byte[0] & 0x7f) << shift;
+
+            if byte[0] & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(SnapshotError::Invalid("integer is too long"))
+    }
+}
+
+impl Encode for usize {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (*self as u64).encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<usize, SnapshotError> {
+        Ok(u64::decode(reader)? as usize)
+    }
+}
+
+// Signed integers are zigzag encoded so that small negative values are also small
+impl Encode for isize {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let value = *self as i64;
+        (((value << 1) ^ (value >> 63)) as u64).encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<isize, SnapshotError> {
+        let value = u64::decode(reader)?;
+        Ok(((value >> 1) as i64 ^ -((value & 1) as i64)) as isize)
+    }
+}
+
+impl Encode for bool {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[*self as u8])
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<bool, SnapshotError> {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+
+        match byte[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::Invalid("unknown bool")),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Some(value) => {
+                writer.write_all(&[1])?;
+                value.encode(writer)
+            }
+            None => writer.write_all(&[0]),
+        }
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Option<T>, SnapshotError> {
+        let mut tag = [0];
+        reader.read_exact(&mut tag)?;
+
+        match tag[0] {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(reader)?)),
+            _ => Err(SnapshotError::Invalid("unknown option tag")),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.len().encode(writer)?;
+        self.iter().try_for_each(|value| value.encode(writer))
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Vec<T>, SnapshotError> {
+        let len = usize::decode(reader)?;
+        // Don't trust the length for the allocation, a corrupt length would run out of memory
+        let mut values = Vec::with_capacity(len.min(1 << 16));
+
+        for _ in 0..len {
+            values.push(T::decode(reader)?);
+        }
+
+        Ok(values)
+    }
+}
+
+impl<T: Encode> Encode for VecDeque<T> {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.len().encode(writer)?;
+        self.iter().try_for_each(|value| value.encode(writer))
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<VecDeque<T>, SnapshotError> {
+        Ok(Vec::decode(reader)?.into())
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.encode(writer)?;
+        self.1.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<(A, B), SnapshotError> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+/// Implements `Encode` for a field-less enum by writing the index of the variant
+macro_rules! encode_enum {
+    ($type:ident, $($variant:ident),+) => {
+        impl Encode for $type {
+            fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&[*self as u8])
+            }
+
+            fn decode<R: Read>(reader: &mut R) -> Result<$type, SnapshotError> {
+                const VARIANTS: &[$type] = &[$($type::$variant),+];
+
+                let mut byte = [0];
+                reader.read_exact(&mut byte)?;
+
+                VARIANTS
+                    .get(byte[0] as usize)
+                    .copied()
+                    .ok_or(SnapshotError::Invalid(concat!("unknown ", stringify!($type))))
+            }
+        }
+    };
+}
+
+encode_enum!(
+    OpCode, MOV, ADD, SUB, MUL, DIV, MOD, DAT, JMP, SPL, JMZ, JMN, NOP, DJN, SEQ, SNE, SLT, LDP,
+    STP
+);
+encode_enum!(Modifier, None, A, B, AB, BA, F, X, I);
+encode_enum!(
+    AddressMode,
+    Direct,
+    Immediate,
+    IndirectA,
+    IndirectB,
+    PreDecrementIndirectA,
+    PreDecrementIndirectB,
+    PostIncrementIndirectA,
+    PostIncrementIndirectB
+);
+encode_enum!(ExecutionMode, Lazy, Icws94);
+
+impl Encode for Instruction {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.op_code.encode(writer)?;
+        self.modifier.encode(writer)?;
+        self.a_mode.encode(writer)?;
+        self.a_reg.encode(writer)?;
+        self.b_mode.encode(writer)?;
+        self.b_reg.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Instruction, SnapshotError> {
+        Ok(Instruction {
+            op_code: OpCode::decode(reader)?,
+            modifier: Modifier::decode(reader)?,
+            a_mode: AddressMode::decode(reader)?,
+            a_reg: isize::decode(reader)?,
+            b_mode: AddressMode::decode(reader)?,
+            b_reg: isize::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Limits {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.read.encode(writer)?;
+        self.write.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<Limits, SnapshotError> {
+        Ok(Limits {
+            read: usize::decode(reader)?,
+            write: usize::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for VirtualMachine {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.memory.encode(writer)?;
+        self.users_pcs.encode(writer)?;
+        self.cur_user.encode(writer)?;
+        self.max_processes.encode(writer)?;
+        self.speeds.encode(writer)?;
+        self.turns.encode(writer)?;
+        self.execution_mode.encode(writer)?;
+        self.pspace.encode(writer)?;
+        self.limits.encode(writer)?;
+        self.cycle_count.encode(writer)?;
+        self.ownership.encode(writer)?;
+        self.statistics.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<VirtualMachine, SnapshotError> {
+        let memory = Vec::decode(reader)?;
+        let users_pcs: Vec<VecDeque<usize>> = Vec::decode(reader)?;
+        let alive = (0..users_pcs.len())
+            .filter(|user| !users_pcs[*user].is_empty())
+            .collect();
+
+        let vm = VirtualMachine {
+            memory,
+            users_pcs,
+            cur_user: usize::decode(reader)?,
+            alive,
+            max_processes: Vec::decode(reader)?,
+            speeds: Vec::decode(reader)?,
+            turns: usize::decode(reader)?,
+            execution_mode: ExecutionMode::decode(reader)?,
+            pspace: Encode::decode(reader)?,
+            limits: Limits::decode(reader)?,
+            cycle_count: usize::decode(reader)?,
+            ownership: Encode::decode(reader)?,
+            history: None,
+            statistics: Encode::decode(reader)?,
+            processes: None,
+        };
+
+        let size = vm.memory.len();
+        let users = vm.users_pcs.len();
+
+        if size == 0 || users == 0 {
+            return Err(SnapshotError::Invalid("the VM has no memory or no users"));
+        }
+        if vm.cur_user >= users || vm.max_processes.len() != users || vm.speeds.len() != users {
+            return Err(SnapshotError::Invalid("the users don't match"));
+        }
+        if !vm.alive.is_empty() && vm.users_pcs[vm.cur_user].is_empty() {
+            return Err(SnapshotError::Invalid("the current user has been killed"));
+        }
+        if vm.speeds.contains(&0) {
+            return Err(SnapshotError::Invalid("a user has a speed of 0"));
+        }
+        if vm.users_pcs.iter().flatten().any(|pc| *pc >= size) {
+            return Err(SnapshotError::Invalid("a process is outside of memory"));
+        }
+        if vm.limits.read == 0
+            || vm.limits.read > size
+            || vm.limits.write == 0
+            || vm.limits.write > size
+        {
+            return Err(SnapshotError::Invalid("the read or write limit is invalid"));
+        }
+        if vm.pspace.get_warriors() != users {
+            return Err(SnapshotError::Invalid(
+                "the P-space doesn't match the users",
+            ));
+        }
+        if let Some(ownership) = &vm.ownership {
+            if ownership.writers().len() != size {
+                return Err(SnapshotError::Invalid(
+                    "the ownership map doesn't match memory",
+                ));
+            }
+        }
+        if let Some(statistics) = &vm.statistics {
+            if !statistics.matches(users, size) {
+                return Err(SnapshotError::Invalid(
+                    "the statistics don't match the users",
+                ));
+            }
+        }
+
+        Ok(vm)
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{Instruction, OpCode};
+
+use super::observer::{Actor, Observer};
+use super::snapshot::{Encode, SnapshotError};
+
+use std::io::{self, Read, Write};
+
+/// The number of op codes, see `WarriorStatistics::op_counts`
+const OP_CODES: usize = OpCode::STP as usize + 1;
+
+/// What a single warrior did during a battle
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarriorStatistics {
+    /// The number of instructions the warrior executed
+    pub instructions: usize,
+    /// The number of times the warrior executed each op code, indexed by `OpCode as usize`
+    pub op_counts: Vec<usize>,
+    /// The largest number of processes the warrior had at once
+    pub peak_processes: usize,
+    /// The number of processes the warrior had at the end
+    pub final_processes: usize,
+    /// The number of different cells the warrior wrote to
+    pub cells_written: usize,
+    /// The number of processes that were killed by executing a DAT
+    pub dat_deaths: usize,
+    /// The number of processes that were killed by dividing by zero
+    pub division_deaths: usize,
+    /// The cycle (see `VirtualMachine::get_cycle_count`) when each process was killed, in order
+    pub death_cycles: Vec<usize>,
+    /// Each change in the number of processes of the warrior as `(cycle, processes)`, meaning
+    /// that the warrior had `processes` processes from that cycle (see
+    /// `VirtualMachine::get_cycle_count`) onwards. The first entry is from when the statistics
+    /// started being collected.
+    ///
+    /// An entry is only added when the number changes, but this isn't sampled so it can grow
+    /// with the length of the battle: a warrior that splits and loses processes all the time adds
+    /// an entry on most of its turns.
+    pub process_counts: Vec<(usize, usize)>,
+    /// The cycle after the last instruction the warrior executed, or when the statistics started
+    /// being collected if it hasn't executed any
+    pub last_cycle: usize,
+}
+
+impl WarriorStatistics {
+    /// Creates the statistics of a warrior that hasn't executed anything and has `processes`
+    /// processes
+    pub fn new(processes: usize) -> WarriorStatistics {
+        WarriorStatistics {
+            instructions: 0,
+            op_counts: vec![0; OP_CODES],
+            peak_processes: processes,
+            final_processes: processes,
+            cells_written: 0,
+            dat_deaths: 0,
+            division_deaths: 0,
+            death_cycles: Vec::new(),
+            process_counts: vec![(0, processes)],
+            last_cycle: 0,
+        }
+    }
+
+    /// The number of times the warrior executed `op_code`
+    pub fn op_count(&self, op_code: OpCode) -> usize {
+        self.op_counts[op_code as usize]
+    }
+
+    /// Adds the statistics of another warrior (or of another round) to these statistics. The
+    /// counts and the numbers of processes over time are added together, the peak number of
+    /// processes is the larger of the two peaks, and the death cycles are merged in order.
+    pub fn add(&mut self, other: &WarriorStatistics) {
+        self.instructions += other.instructions;
+        for (count, other) in self.op_counts.iter_mut().zip(&other.op_counts) {
+            *count += other;
+        }
+        self.peak_processes = self.peak_processes.max(other.peak_processes);
+        self.final_processes += other.final_processes;
+        self.cells_written += other.cells_written;
+        self.dat_deaths += other.dat_deaths;
+        self.division_deaths += other.division_deaths;
+        self.death_cycles.extend(&other.death_cycles);
+        self.death_cycles.sort_unstable();
+        self.process_counts = add_process_counts(&self.process_counts, &other.process_counts);
+        self.last_cycle = self.last_cycle.max(other.last_cycle);
+    }
+}
+
+/// Adds two lists of changes in the number of processes, see `WarriorStatistics::process_counts`.
+/// Before its first entry a list has no processes.
+fn add_process_counts(a: &[(usize, usize)], b: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut sum: Vec<(usize, usize)> = Vec::with_capacity(a.len() + b.len());
+    let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+    let (mut a_processes, mut b_processes) = (0, 0);
+
+    loop {
+        // Apply every change at the next cycle where either list changes
+        let cycle = match (a.peek(), b.peek()) {
+            (Some((a_cycle, _)), Some((b_cycle, _))) => *a_cycle.min(b_cycle),
+            (Some((cycle, _)), None) | (None, Some((cycle, _))) => *cycle,
+            (None, None) => return sum,
+        };
+        while let Some((_, processes)) = a.next_if(|(c, _)| *c == cycle) {
+            a_processes = *processes;
+        }
+        while let Some((_, processes)) = b.next_if(|(c, _)| *c == cycle) {
+            b_processes = *processes;
+        }
+
+        let processes = a_processes + b_processes;
+        if sum.last().map(|(_, count)| *count) != Some(processes) {
+            sum.push((cycle, processes));
+        }
+    }
+}
+
+impl Encode for WarriorStatistics {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.instructions.encode(writer)?;
+        self.op_counts.encode(writer)?;
+        self.peak_processes.encode(writer)?;
+        self.final_processes.encode(writer)?;
+        self.cells_written.encode(writer)?;
+        self.dat_deaths.encode(writer)?;
+        self.division_deaths.encode(writer)?;
+        self.death_cycles.encode(writer)?;
+        self.process_counts.encode(writer)?;
+        self.last_cycle.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<WarriorStatistics, SnapshotError> {
+        let statistics = WarriorStatistics {
+            instructions: usize::decode(reader)?,
+            op_counts: Vec::decode(reader)?,
+            peak_processes: usize::decode(reader)?,
+            final_processes: usize::decode(reader)?,
+            cells_written: usize::decode(reader)?,
+            dat_deaths: usize::decode(reader)?,
+            division_deaths: usize::decode(reader)?,
+            death_cycles: Vec::decode(reader)?,
+            process_counts: Vec::decode(reader)?,
+            last_cycle: usize::decode(reader)?,
+        };
+
+        if statistics.op_counts.len() != OP_CODES || statistics.process_counts.is_empty() {
+            return Err(SnapshotError::Invalid(
+                "the statistics of a warrior are invalid",
+            ));
+        }
+
+        Ok(statistics)
+    }
+}
+
+/// The statistics of every user of a VM, see `VirtualMachine::track_statistics`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BattleStatistics {
+    warriors: Vec<WarriorStatistics>,
+    /// Whether each user has written to each cell
+    written: Vec<Vec<bool>>,
+}
+
+impl BattleStatistics {
+    /// Creates the statistics for users that start with the given numbers of processes in a core
+    /// of `size` cells
+    pub fn new(processes: &[usize], size: usize) -> BattleStatistics {
+        BattleStatistics {
+            warriors: processes
+                .iter()
+                .map(|processes| WarriorStatistics::new(*processes))
+                .collect(),
+            written: vec![vec![false; size]; processes.len()],
+        }
+    }
+
+    /// The statistics of each user
+    pub fn warriors(&self) -> &[WarriorStatistics] {
+        &self.warriors
+    }
+
+    /// Consumes the statistics, returning the statistics of each user
+    pub fn into_warriors(self) -> Vec<WarriorStatistics> {
+        self.warriors
+    }
+
+    /// Whether these are the statistics of `users` users in a core of `size` cells
+    pub(crate) fn matches(&self, users: usize, size: usize) -> bool {
+        self.warriors.len() == users
+            && self.written.len() == users
+            && self.written.iter().all(|written| written.len() == size)
+    }
+
+    /// Sets the cycle of the first entry of each user's process counts
+    pub(crate) fn start_at(&mut self, cycle: usize) {
+        for warrior in &mut self.warriors {
+            warrior.process_counts[0].0 = cycle;
+            warrior.last_cycle = cycle;
+        }
+    }
+
+    /// Updates the peak, final and changing number of processes of `user` from `cycle`
+    pub(crate) fn record_processes(&mut self, user: usize, processes: usize, cycle: usize) {
+        let warrior = &mut self.warriors[user];
+        warrior.peak_processes = warrior.peak_processes.max(processes);
+        warrior.final_processes = processes;
+        warrior.last_cycle = cycle;
+        if warrior.process_counts.last().map(|(_, count)| *count) != Some(processes) {
+            warrior.process_counts.push((cycle, processes));
+        }
+    }
+}
+
+impl Encode for BattleStatistics {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.warriors.encode(writer)?;
+        self.written.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> Result<BattleStatistics, SnapshotError> {
+        Ok(BattleStatistics {
+            warriors: Vec::decode(reader)?,
+            written: Vec::decode(reader)?,
+        })
+    }
+}
+
+/// Updates the statistics from the events of a cycle and passes the events on to another observer
+pub(crate) struct StatisticsTracker<'a, 'b> {
+    pub statistics: &'a mut BattleStatistics,
+    pub cycle: usize,
+    /// The op code of the instruction that was fetched, which is what killed a process
+    pub op_code: Option<OpCode>,
+    pub next: Option<&'b mut dyn Observer>,
+}
+
+impl Observer for StatisticsTracker<'_, '_> {
+    fn fetch(&mut self, actor: Actor, instruction: &Instruction) {
+        let warrior = &mut self.statistics.warriors[actor.user];
+        warrior.instructions += 1;
+        warrior.op_counts[instruction.op_code as usize] += 1;
+        self.op_code = Some(instruction.op_code);
+
+        if let Some(next) = &mut self.next {
+            next.fetch(actor, instruction);
+        }
+    }
+
+    fn operands(&mut self, actor: Actor, a: usize, b: usize) {
+        if let Some(next) = &mut self.next {
+            next.operands(actor, a, b);
+        }
+    }
+
+    fn read(&mut self, actor: Actor, address: usize) {
+        if let Some(next) = &mut self.next {
+            next.read(actor, address);
+        }
+    }
+
+    fn write(&mut self, actor: Actor, address: usize, old: &Instruction, new: &Instruction) {
+        let written = &mut self.statistics.written[actor.user][address];
+        if !*written {
+            *written = true;
+            self.statistics.warriors[actor.user].cells_written += 1;
+        }
+
+        if let Some(next) = &mut self.next {
+            next.write(actor, address, old, new);
+        }
+    }
+
+    fn spawn(&mut self, actor: Actor, new_pc: usize) {
+        if let Some(next) = &mut self.next {
+            next.spawn(actor, new_pc);
+        }
+    }
+
+    fn death(&mut self, actor: Actor) {
+        let warrior = &mut self.statistics.warriors[actor.user];
+        match self.op_code {
+            Some(OpCode::DIV) | Some(OpCode::MOD) => warrior.division_deaths += 1,
+            _ => warrior.dat_deaths += 1,
+        }
+        warrior.death_cycles.push(self.cycle);
+
+        if let Some(next) = &mut self.next {
+            next.death(actor);
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use crate::{AddressMode, Instruction, Modifier, OpCode};
+
+use super::observer::{Actor, Observer};
+use super::snapshot::{Encode, SnapshotError};
+use super::VirtualMachine;
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// The bytes every binary trace starts with
+const MAGIC: &[u8; 4] = b"DWTR";
+/// The version of the trace formats
+const VERSION: u8 = 1;
+/// The first line of every text trace
+const TEXT_HEADER: &str = "# darwin trace v1";
+
+/// The format of a trace, see `TraceRecorder`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    /// One line per cycle after a `# darwin trace v1` header line, with the fields separated by
+    /// `|`:
+    ///
+    /// `cycle|warrior|process|pc|instruction|a|b|address=instruction|...`
+    ///
+    /// The process is `-` if the process ids aren't being tracked, and there is a final field for
+    /// each cell that was written (in the order of the writes) holding its address and new value.
+    /// Instructions are written like `MOV.I  $0 $1`. Lines that start with `#` are comments.
+    Text,
+    /// The magic bytes `DWTR` and a version byte, followed by each record as variable length
+    /// integers. This is much smaller and faster to read than the text format.
+    Binary,
+}
+
+/// An error that occurred while reading a trace
+#[derive(Debug)]
+pub enum TraceError {
+    /// The trace couldn't be read
+    Io(io::Error),
+    /// The data is not a trace
+    NotATrace,
+    /// The trace was written by an unsupported version of the format. Holds the version.
+    UnsupportedVersion(u8),
+    /// A record is corrupt. Holds a description of the problem.
+    Invalid(&'static str),
+    /// A line of a text trace couldn't be parsed. Holds the line number.
+    Parse(usize),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "Couldn't read the trace: {}", e),
+            TraceError::NotATrace => write!(f, "The data is not an execution trace"),
+            TraceError::UnsupportedVersion(v) => {
+                write!(f, "Version {} of the trace format is not supported", v)
+            }
+            TraceError::Invalid(e) => write!(f, "The trace is invalid: {}", e),
+            TraceError::Parse(l) => write!(f, "Couldn't parse line {} of the trace", l),
+        }
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> TraceError {
+        TraceError::Io(e)
+    }
+}
+
+impl From<SnapshotError> for TraceError {
+    fn from(e: SnapshotError) -> TraceError {
+        match e {
+            SnapshotError::Io(e) => TraceError::Io(e),
+            SnapshotError::Invalid(e) => TraceError::Invalid(e),
+            _ => TraceError::Invalid("unexpected header"),
+        }
+    }
+}
+
+/// What happened during a single cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    /// The cycle (see `VirtualMachine::get_cycle_count`)
+    pub cycle: usize,
+    /// The user (warrior) whose process ran
+    pub warrior: usize,
+    /// The id of the process that ran, `None` if process ids weren't being tracked (see
+    /// `VirtualMachine::track_processes`)
+    pub process: Option<usize>,
+    /// The address of the instruction that was executed
+    pub pc: usize,
+    /// The instruction that was executed
+    pub instruction: Instruction,
+    /// The address the A operand resolved to
+    pub a: usize,
+    /// The address the B operand resolved to
+    pub b: usize,
+    /// The address and new value of each cell that was written, in the order of the writes
+    pub writes: Vec<(usize, Instruction)>,
+}
+
+impl TraceRecord {
+    /// Writes the record as a line of a text trace, see `TraceFormat::Text`
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "{}|{}|", self.cycle, self.warrior)?;
+        match self.process {
+            Some(process) => write!(writer, "{}", process)?,
+            None => write!(writer, "-")?,
+        }
+        write!(
+            writer,
+            "|{}|{}|{}|{}",
+            self.pc, self.instruction, self.a, self.b
+        )?;
+
+        for (address, instruction) in &self.writes {
+            write!(writer, "|{}={}", address, instruction)?;
+        }
+
+        writeln!(writer)
+    }
+
+    /// Parses a line of a text trace, see `TraceFormat::Text`
+    /// # Example
+    /// ```
+    /// use darwin_lib::{create_program, TraceRecord};
+    ///
+    /// let record = TraceRecord::parse_text("4|1|-|20|MOV.I  $0 $1|20|21|21=MOV.I  $0 $1").unwrap();
+    ///
+    /// assert_eq!(record.warrior, 1);
+    /// assert_eq!(record.process, None);
+    /// assert_eq!(record.instruction, create_program! { MOV(I, 0, Direct, 1, Direct) }[0]);
+    /// assert_eq!(record.writes, vec![(21, record.instruction)]);
+    /// ```
+    pub fn parse_text(line: &str) -> Option<TraceRecord> {
+        let mut fields = line.trim_end().split('|');
+
+        let cycle = fields.next()?.parse().ok()?;
+        let warrior = fields.next()?.parse().ok()?;
+        let process = match fields.next()? {
+            "-" => None,
+            process => Some(process.parse().ok()?),
+        };
+        let pc = fields.next()?.parse().ok()?;
+        let instruction = parse_instruction(fields.next()?)?;
+        let a = fields.next()?.parse().ok()?;
+        let b = fields.next()?.parse().ok()?;
+
+        let writes = fields
+            .map(|field| {
+                let (address, instruction) = field.split_once('=')?;
+                Some((address.parse().ok()?, parse_instruction(instruction)?))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(TraceRecord {
+            cycle,
+            warrior,
+            process,
+            pc,
+            instruction,
+            a,
+            b,
+            writes,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.cycle.encode(writer)?;
+        self.warrior.encode(writer)?;
+        self.process.encode(writer)?;
+        self.pc.encode(writer)?;
+        self.instruction.encode(writer)?;
+        self.a.encode(writer)?;
+        self.b.encode(writer)?;
+        self.writes.encode(writer)
+    }
+
+    fn decode<R: io::Read>(reader: &mut R) -> Result<TraceRecord, SnapshotError> {
+        Ok(TraceRecord {
+            cycle: usize::decode(reader)?,
+            warrior: usize::decode(reader)?,
+            process: Encode::decode(reader)?,
+            pc: usize::decode(reader)?,
+            instruction: Instruction::decode(reader)?,
+            a: usize::decode(reader)?,
+            b: usize::decode(reader)?,
+            writes: Vec::decode(reader)?,
+        })
+    }
+}
+
+/// Parses an instruction written by its `Display` implementation
+fn parse_instruction(text: &str) -> Option<Instruction> {
+    use AddressMode::*;
+
+    const OP_CODES: [OpCode; 18] = {
+        use OpCode::*;
+        [
+            MOV, ADD, SUB, MUL, DIV, MOD, DAT, JMP, SPL, JMZ, JMN, NOP, DJN, SEQ, SNE, SLT, LDP,
+            STP,
+        ]
+    };
+    const MODIFIERS: [Modifier; 7] = {
+        use Modifier::*;
+        [A, B, AB, BA, F, X, I]
+    };
+    const MODES: [AddressMode; 8] = [
+        Direct,
+        Immediate,
+        IndirectA,
+        IndirectB,
+        PreDecrementIndirectA,
+        PreDecrementIndirectB,
+        PostIncrementIndirectA,
+        PostIncrementIndirectB,
+    ];
+
+    let operand = |word: &str| {
+        let mode = MODES
+            .iter()
+            .copied()
+            .find(|mode| word.starts_with(&mode.to_string()))?;
+        Some((word[1..].parse().ok()?, mode))
+    };
+
+    let mut words = text.split_whitespace();
+
+    let mut name = words.next()?.split('.');
+    let op_code = name.next()?;
+    let op_code = OP_CODES
+        .iter()
+        .copied()
+        .find(|op| format!("{:?}", op) == op_code)?;
+    let modifier = match name.next() {
+        Some(modifier) => MODIFIERS
+            .iter()
+            .copied()
+            .find(|m| format!("{:?}", m) == modifier)?,
+        None => Modifier::None,
+    };
+
+    let (a_reg, a_mode) = operand(words.next()?)?;
+    let (b_reg, b_mode) = operand(words.next()?)?;
+
+    if words.next().is_some() {
+        return None;
+    }
+
+    Some(Instruction::new(
+        op_code, modifier, a_reg, a_mode, b_reg, b_mode,
+    ))
+}
+
+/// Collects the events of a cycle into a record
+struct RecordBuilder {
+    pc: usize,
+    instruction: Option<Instruction>,
+    operands: (usize, usize),
+    writes: Vec<(usize, Instruction)>,
+}
+
+impl Observer for RecordBuilder {
+    fn fetch(&mut self, actor: Actor, instruction: &Instruction) {
+        self.pc = actor.pc;
+        self.instruction = Some(*instruction);
+    }
+
+    fn operands(&mut self, _: Actor, a: usize, b: usize) {
+        self.operands = (a, b);
+    }
+
+    fn write(&mut self, _: Actor, address: usize, _: &Instruction, new: &Instruction) {
+        self.writes.push((address, *new));
+    }
+}
+
+/// Runs a VM, writing a record of every cycle to `writer`
+/// # Example
+/// ```
+/// use darwin_lib::{create_program, TraceFormat, TraceReader, TraceRecorder, VirtualMachine};
+///
+/// let mut vm = VirtualMachine::new_simple(10, create_program! { MOV(I, 0, Direct, 1, Direct) });
+/// let mut recorder = TraceRecorder::new(Vec::new(), TraceFormat::Text).unwrap();
+/// recorder.cycles(&mut vm, 3).unwrap();
+/// let trace = recorder.into_inner().unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(trace.clone()).unwrap().lines().nth(3).unwrap(),
+///     "2|0|-|2|MOV.I  $0 $1|2|3|3=MOV.I  $0 $1"
+/// );
+///
+/// let records: Vec<_> = TraceReader::new(&trace[..]).unwrap().map(Result::unwrap).collect();
+/// assert_eq!(records.len(), 3);
+/// assert_eq!(records[1].writes, vec![(2, records[1].instruction)]);
+/// ```
+pub struct TraceRecorder<W: Write> {
+    writer: W,
+    format: TraceFormat,
+}
+
+impl<W: Write> TraceRecorder<W> {
+    /// Creates a recorder that writes to `writer` in the given format, starting with the header
+    pub fn new(mut writer: W, format: TraceFormat) -> io::Result<TraceRecorder<W>> {
+        match format {
+            TraceFormat::Text => writeln!(writer, "{}", TEXT_HEADER)?,
+            TraceFormat::Binary => {
+                writer.write_all(MAGIC)?;
+                writer.write_all(&[VERSION])?;
+            }
+        }
+
+        Ok(TraceRecorder { writer, format })
+    }
+
+    /// Runs a single cycle of `vm` and writes its record, doing nothing if every user has been
+    /// killed. Returns the record that was written.
+    pub fn cycle(&mut self, vm: &mut VirtualMachine) -> io::Result<Option<TraceRecord>> {
+        if vm.alive_warriors().is_empty() {
+            return Ok(None);
+        }
+
+        let cycle = vm.get_cycle_count();
+        let warrior = vm.get_cur_user();
+        let process = vm
+            .get_processes()
+            .map(|processes| processes.get_queue(warrior)[0]);
+
+        let mut builder = RecordBuilder {
+            pc: 0,
+            instruction: None,
+            operands: (0, 0),
+            writes: Vec::new(),
+        };
+        vm.cycle_with_observer(&mut builder);
+
+        let record = TraceRecord {
+            cycle,
+            warrior,
+            process,
+            pc: builder.pc,
+            instruction: builder
+                .instruction
+                .expect("Every cycle fetches an instruction"),
+            a: builder.operands.0,
+            b: builder.operands.1,
+            writes: builder.writes,
+        };
+        self.write(&record)?;
+
+        Ok(Some(record))
+    }
+
+    /// Runs up to `cycles` cycles of `vm` (stopping early if every user is killed), writing the
+    /// record of each one
+    pub fn cycles(&mut self, vm: &mut VirtualMachine, cycles: usize) -> io::Result<()> {
+        for _ in 0..cycles {
+            if self.cycle(vm)?.is_none() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a record that was made elsewhere
+    pub fn write(&mut self, record: &TraceRecord) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Text => record.write_text(&mut self.writer),
+            TraceFormat::Binary => record.encode(&mut self.writer),
+        }
+    }
+
+    /// Flushes the writer and returns it
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the records of a trace written by `TraceRecorder`, in either format.
+/// This is an iterator over the records.
+pub struct TraceReader<R: BufRead> {
+    reader: R,
+    format: TraceFormat,
+    /// The number of lines of a text trace that have been read
+    line: usize,
+}
+
+impl<R: BufRead> TraceReader<R> {
+    /// Reads the header of the trace to find out its format
+    pub fn new(mut reader: R) -> Result<TraceReader<R>, TraceError> {
+        if reader.fill_buf()?.starts_with(MAGIC) {
+            let mut header = [0; 5];
+            reader.read_exact(&mut header)?;
+
+            if header[4] != VERSION {
+                return Err(TraceError::UnsupportedVersion(header[4]));
+            }
+
+            return Ok(TraceReader {
+                reader,
+                format: TraceFormat::Binary,
+                line: 0,
+            });
+        }
+
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+
+        match header.trim_end().strip_prefix("# darwin trace v") {
+            Some(version) if version == VERSION.to_string() => Ok(TraceReader {
+                reader,
+                format: TraceFormat::Text,
+                line: 1,
+            }),
+            Some(version) => Err(version
+                .parse()
+                .map_or(TraceError::NotATrace, TraceError::UnsupportedVersion)),
+            None => Err(TraceError::NotATrace),
+        }
+    }
+
+    pub fn get_format(&self) -> TraceFormat {
+        self.format
+    }
+
+    fn read_text(&mut self) -> Option<Result<TraceRecord, TraceError>> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            if !line.trim().is_empty() && !line.starts_with('#') {
+                return Some(TraceRecord::parse_text(&line).ok_or(TraceError::Parse(self.line)));
+            }
+        }
+    }
+
+    fn read_binary(&mut self) -> Option<Result<TraceRecord, TraceError>> {
+        match self.reader.fill_buf() {
+            Ok([]) => None,
+            Ok(_) => Some(TraceRecord::decode(&mut self.reader).map_err(TraceError::from)),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TraceReader<R> {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.format {
+            TraceFormat::Text => self.read_text(),
+            TraceFormat::Binary => self.read_binary(),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use darwin_lib::{
+    create_program, run_battle, run_match, Instruction, MatchSettings, Replay, SettingsError,
+    VirtualMachine,
+};
+
+mod common;
+use common::imp;
+
+#[test]
+fn imp_beats_dat() {
+    let dat = create_program! { DAT(None, 0, Immediate, 0, Immediate) };
+
+    let result = run_match(
+        &[imp(), dat],
+        &MatchSettings {
+            rounds: 10,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(result.rounds.len(), 10);
+    assert_eq!(result.scores[0].wins, 10);
+    assert_eq!(result.scores[0].score(), 30);
+    assert_eq!(result.scores[1].losses, 10);
+    assert_eq!(result.scores[1].score(), 0);
+
+    for round in &result.rounds {
+        assert_eq!(round.survivors, vec![0]);
+        // The DAT is killed on its first instruction
+        assert_eq!(round.cycles, 1);
+    }
+}
+
+#[test]
+fn first_warrior_alternates() {
+    let result = run_match(
+        &[imp(), imp()],
+        &MatchSettings {
+            rounds: 4,
+            max_cycles: 10,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let first_warriors: Vec<usize> = result.rounds.iter().map(|r| r.first_warrior).collect();
+    assert_eq!(first_warriors, vec![0, 1, 0, 1]);
+}
+
+#[test]
+fn imps_tie() {
+    let result = run_match(
+        &[imp(), imp()],
+        &MatchSettings {
+            rounds: 3,
+            max_cycles: 1000,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    for score in &result.scores {
+        assert_eq!(score.ties, 3);
+        assert_eq!(score.score(), 3);
+    }
+
+    for round in &result.rounds {
+        assert_eq!(round.survivors, vec![0, 1]);
+        assert_eq!(round.cycles, 1000);
+    }
+}
+
+#[test]
+fn lone_warrior_runs_until_killed() {
+    let program = create_program! {
+        NOP(F, 0, Direct, 0, Direct)
+        NOP(F, 0, Direct, 0, Direct)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+
+    let mut vm = VirtualMachine::new_simple(10, program);
+
+    assert_eq!(run_battle(&mut vm, 100), 3);
+    assert!(vm.get_users_pcs()[0].is_empty());
+}
+
+fn dwarf() -> Vec<Instruction> {
+    create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct)
+        DAT(F, 0, Immediate, 0, Immediate)
+    }
+}
+
+#[test]
+fn threads_do_not_change_results() {
+    let settings = MatchSettings {
+        core_size: 800,
+        min_separation: 50,
+        max_cycles: 2000,
+        rounds: 20,
+        seed: Some(42),
+        ..Default::default()
+    };
+
+    let single = run_match(&[dwarf(), imp()], &settings).unwrap();
+    let multi = run_match(
+        &[dwarf(), imp()],
+        &MatchSettings {
+            threads: 4,
+            ..settings
+        },
+    )
+    .unwrap();
+
+    assert_eq!(single, multi);
+    assert_eq!(single.seed, 42);
+}
+
+#[test]
+fn same_seed_same_placements() {
+    let settings = MatchSettings {
+        core_size: 800,
+        min_separation: 50,
+        max_cycles: 100,
+        rounds: 5,
+        seed: Some(7),
+        ..Default::default()
+    };
+
+    let first = run_match(&[dwarf(), dwarf()], &settings).unwrap();
+    let second = run_match(&[dwarf(), dwarf()], &settings).unwrap();
+
+    assert_eq!(first, second);
+
+    // Each round gets its own seed
+    let mut seeds: Vec<u64> = first.rounds.iter().map(|r| r.seed).collect();
+    seeds.dedup();
+    assert_eq!(seeds.len(), 5);
+}
+
+#[test]
+fn seeds_do_not_depend_on_rand() {
+    let settings = MatchSettings {
+        core_size: 800,
+        min_separation: 50,
+        max_cycles: 100,
+        rounds: 3,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let result = run_match(&[dwarf(), dwarf()], &settings).unwrap();
+
+    // The round seeds are the numbers splitmix64 generates from the match's seed
+    let seeds: Vec<u64> = result.rounds.iter().map(|r| r.seed).collect();
+    assert_eq!(
+        seeds,
+        vec![
+            0xE220_A839_7B1D_CDAF,
+            0x6E78_9E6A_A1B9_65F4,
+            0x06C4_5D18_8009_454F
+        ]
+    );
+
+    // and the warriors are placed with splitmix64 too, so a seed always gives these placements
+    let replay = Replay::record(&[dwarf(), dwarf()], &settings, 0, 1);
+    assert_eq!(replay.placements, vec![50, 716]);
+}
+
+#[test]
+fn fixed_position() {
+    // Bombs the cell 10 after itself and then loops
+    let bomber = create_program! {
+        MOV(I, 2, Direct, 10, Direct)
+        JMP(B, 0, Direct, 0, Direct)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    let looper = create_program! { JMP(B, 0, Direct, 0, Direct) };
+
+    let settings = MatchSettings {
+        rounds: 4,
+        max_cycles: 100,
+        min_separation: 10,
+        fixed_position: Some(10),
+        ..Default::default()
+    };
+
+    let vm = VirtualMachine::new_battle(&[bomber.clone(), looper.clone()], &settings);
+    assert_eq!(vm.get_users_pcs()[1][0], 10);
+
+    // The looper is always hit, whichever warrior goes first
+    let result = run_match(&[bomber.clone(), looper.clone()], &settings).unwrap();
+    assert_eq!(result.scores[0].wins, 4);
+
+    let settings = MatchSettings {
+        fixed_position: Some(20),
+        ..settings
+    };
+    let result = run_match(&[bomber.clone(), looper.clone()], &settings).unwrap();
+    assert_eq!(result.scores[0].ties, 4);
+
+    // The second warrior has to be at least `min_separation` away from both ends of the first
+    let at = |position| MatchSettings {
+        fixed_position: Some(position),
+        ..settings.clone()
+    };
+    assert_eq!(
+        run_match(&[bomber.clone(), looper.clone()], &at(9)),
+        Err(SettingsError::FixedPositionOutOfRange(9, 10, 7990))
+    );
+    assert_eq!(
+        run_match(&[bomber.clone(), looper.clone()], &at(7991)),
+        Err(SettingsError::FixedPositionOutOfRange(7991, 10, 7990))
+    );
+    assert_eq!(
+        run_match(&[bomber.clone(), looper.clone(), looper], &settings),
+        Err(SettingsError::FixedPositionWarriors(3))
+    );
+}
+
+#[test]
+fn not_enough_room() {
+    let settings = MatchSettings {
+        core_size: 10,
+        min_separation: 4,
+        rounds: 1,
+        ..Default::default()
+    };
+
+    assert!(run_match(&[imp(), imp()], &settings).is_ok());
+    assert_eq!(
+        run_match(&[imp(), imp(), imp()], &settings),
+        Err(SettingsError::NotEnoughRoom(15, 10))
+    );
+}
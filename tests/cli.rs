@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Writes a warrior into a temporary file
+fn warrior(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("darwin_cli_{}_{}.red", std::process::id(), name));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn darwin_mars(args: &[&str], warriors: &[&PathBuf]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_darwin-mars"))
+        .args(args)
+        .args(warriors)
+        .output()
+        .unwrap();
+
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn pmars_summary() {
+    let bomber = warrior(
+        "bomber",
+        ";redcode\n;name Bomber\n;author Someone\n\nMOV.I 2, 10\nJMP 0 ; loop\nDAT #0, #0\nend\n",
+    );
+    let looper = warrior("looper", "JMP 0\n");
+
+    let (success, stdout, _) = darwin_mars(
+        &["-r", "4", "-d", "10", "-F", "10", "-b"],
+        &[&bomber, &looper],
+    );
+    assert!(success);
+    assert_eq!(
+        stdout,
+        "Bomber by Someone scores 12\nUnknown by Anonymous scores 0\nResults: 4 0 0\n"
+    );
+
+    let (success, stdout, _) =
+        darwin_mars(&["-r2", "-c", "100", "-d10", "-F20"], &[&bomber, &looper]);
+    assert!(success);
+    assert!(stdout
+        .starts_with("Program \"Bomber\" (length 3) by \"Someone\"\n\n       MOV.I  $2 $10\n"));
+    assert!(stdout
+        .ends_with("Bomber by Someone scores 2\nUnknown by Anonymous scores 2\nResults: 0 0 2\n"));
+
+    std::fs::remove_file(bomber).unwrap();
+    std::fs::remove_file(looper).unwrap();
+}
+
+#[test]
+fn pmars_errors() {
+    let imp = warrior("imp", "MOV.I 0, 1\n");
+    let invalid = warrior("invalid", "MOV.I 0, 1\nABC 0 0\n");
+
+    let (success, _, stderr) = darwin_mars(&["-b"], &[&imp, &invalid]);
+    assert!(!success);
+    assert!(stderr.contains("Unknown OpCode 'ABC' found on line 2"));
+
+    let (success, _, stderr) = darwin_mars(&["-l", "0"], &[&imp]);
+    assert!(!success);
+    assert!(stderr.contains("at most 0 are allowed"));
+
+    let (success, _, stderr) = darwin_mars(&["-x", "1"], &[&imp]);
+    assert!(!success);
+    assert!(stderr.contains("unknown option -x"));
+
+    // Settings that the warriors don't fit in are a usage error rather than a crash
+    let status = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_darwin-mars"))
+            .args(args)
+            .args([&imp, &imp])
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    assert_eq!(status(&["-b", "-d", "8000"]), Some(2));
+    assert_eq!(status(&["-b", "-s", "1"]), Some(2));
+    assert_eq!(status(&["-b", "-F", "0"]), Some(2));
+    assert_eq!(status(&["-b", "-F", "7901"]), Some(2));
+    assert_eq!(status(&["-b", "-r", "1", "-F", "7900"]), Some(0));
+
+    std::fs::remove_file(imp).unwrap();
+    std::fs::remove_file(invalid).unwrap();
+}
+
+fn darwin_compile(args: &[&str], warriors: &[&PathBuf]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_darwin"))
+        .arg("compile")
+        .args(args)
+        .args(warriors)
+        .output()
+        .unwrap();
+
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn compile_listing_and_load_file() {
+    let dwarf = warrior(
+        "dwarf",
+        ";name Dwarf\nADD.AB #4, 3\nMOV.I 2, @2 ; bomb\nJMP -2\nDAT #0, #0\nPIN 42\n",
+    );
+
+    let (success, stdout, stderr) = darwin_compile(&["--listing"], &[&dwarf]);
+    assert!(success, "{}", stderr);
+    let lines: Vec<&str> = stdout.lines().skip(1).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "0000  ADD.AB #4 $3            2  ADD.AB #4, 3",
+            "0001  MOV.I  $2 @2            3  MOV.I 2, @2 ; bomb",
+            "0002  JMP.B  $-2 $0           4  JMP -2",
+            "0003  DAT.F  #0 #0            5  DAT #0, #0",
+        ]
+    );
+
+    // The load file compiles to the same program
+    let load_file = std::env::temp_dir().join(format!("darwin_cli_{}.load", std::process::id()));
+    let (success, stdout, _) = darwin_compile(&["-o", load_file.to_str().unwrap()], &[&dwarf]);
+    assert!(success);
+    assert!(stdout.is_empty());
+    let loaded = std::fs::read_to_string(&load_file).unwrap();
+    let original = std::fs::read_to_string(&dwarf).unwrap();
+    assert_eq!(
+        darwin_lib::parse_program(&loaded),
+        darwin_lib::parse_program(&original)
+    );
+    // and keeps sharing its P-space
+    assert_eq!(darwin_lib::compile_program(&loaded).pin, Some(42));
+
+    std::fs::remove_file(load_file).unwrap();
+    std::fs::remove_file(dwarf).unwrap();
+}
+
+#[test]
+fn compile_reports_every_error() {
+    let imp = warrior("compiled_imp", "MOV.I 0, 1\n");
+    let invalid = warrior("errors", "MOV.I 0, 1\nABC 0 0\nDAT.I 0 0\nMOV 0\n");
+
+    let (success, stdout, stderr) = darwin_compile(&["-q"], &[&imp, &invalid]);
+    assert!(!success);
+    assert!(stdout.is_empty());
+
+    let path = invalid.to_str().unwrap();
+    let errors: Vec<&str> = stderr.lines().collect();
+    assert_eq!(errors.len(), 3);
+    assert_eq!(
+        errors[0],
+        format!("{}: Unknown OpCode 'ABC' found on line 2", path)
+    );
+    assert!(errors[1].starts_with(&format!("{}: ", path)));
+    assert!(errors[1].contains("line 3"));
+    assert_eq!(
+        errors[2],
+        format!("{}: Not enough arguments supplied on line 4", path)
+    );
+
+    let (success, _, _) = darwin_compile(&["-q"], &[&imp]);
+    assert!(success);
+
+    // The output file is only created once a warrior compiles
+    let output = std::env::temp_dir().join(format!("darwin_cli_{}.failed", std::process::id()));
+    let (success, _, _) = darwin_compile(&["-o", output.to_str().unwrap()], &[&invalid]);
+    assert!(!success);
+    assert!(!output.exists());
+
+    std::fs::remove_file(imp).unwrap();
+    std::fs::remove_file(invalid).unwrap();
+}
@@ -0,0 +1,14 @@
+// Each test file only uses some of the warriors
+#![allow(dead_code)]
+
+use darwin_lib::{create_program, Instruction};
+
+/// A warrior that copies itself forward forever
+pub fn imp() -> Vec<Instruction> {
+    create_program! { MOV(I, 0, Direct, 1, Direct) }
+}
+
+/// A warrior that dies on its first instruction
+pub fn dat() -> Vec<Instruction> {
+    create_program! { DAT(F, 0, Immediate, 0, Immediate) }
+}
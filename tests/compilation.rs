@@ -1,4 +1,4 @@
-use darwin_lib::{create_program, parse_program, ParseError};
+use darwin_lib::{compile_program, create_program, parse_program, ParseError};
 
 #[test]
 fn simple_program() {
@@ -155,3 +155,47 @@ DAT 0 0"
         Err(ParseError::UnexpectedArgument(3))
     )
 }
+
+#[test]
+fn comments_and_commas() {
+    assert_eq!(
+        parse_program(
+            ";redcode
+;name Dwarf
+
+ADD.AB #4, 3 ; move the bomb pointer
+MOV.I 2, @2
+JMP -2
+DAT #0, #0
+END
+this isn't part of the program"
+        ),
+        Ok(create_program! {
+            ADD(AB, 4, Immediate, 3, Direct)
+            MOV(I, 2, Direct, 2, IndirectB)
+            JMP(B, -2, Direct, 0, Direct)
+            DAT(F, 0, Immediate, 0, Immediate)
+        })
+    );
+
+    // Line numbers still count the skipped lines
+    assert_eq!(
+        parse_program("; comment\n\nDAT 0 0 0"),
+        Err(ParseError::UnexpectedArgument(3))
+    );
+}
+
+#[test]
+fn every_error_is_found() {
+    let compilation = compile_program("; imp\nMOV.I 0, 1\nMOV 0\nDAT zero 0\nJMP -1 ; back");
+
+    let lines: Vec<(usize, &str)> = compilation
+        .lines
+        .iter()
+        .map(|line| (line.line_num, line.source))
+        .collect();
+    assert_eq!(lines, vec![(2, "MOV.I 0, 1"), (5, "JMP -1 ; back")]);
+
+    let errors: Vec<usize> = compilation.errors.iter().map(|e| e.line_num()).collect();
+    assert_eq!(errors, vec![3, 4]);
+}
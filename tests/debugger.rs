@@ -0,0 +1,166 @@
+use darwin_lib::{
+    create_program, Breakpoint, Condition, Debugger, MatchSettings, StopReason, VirtualMachine,
+    WatchField, Watchpoint,
+};
+
+mod common;
+use common::{dat, imp};
+
+fn dwarf() -> Vec<darwin_lib::Instruction> {
+    create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    }
+}
+
+#[test]
+fn breakpoints_stop_before_execution() {
+    let mut debugger = Debugger::new(VirtualMachine::new_simple(10, imp()));
+    debugger.add_breakpoint(Breakpoint {
+        address: 3,
+        user: None,
+    });
+
+    assert_eq!(
+        debugger.run(100),
+        StopReason::Breakpoint {
+            user: 0,
+            address: 3
+        }
+    );
+    assert_eq!(debugger.get_vm().get_cycle_count(), 3);
+
+    // Running again continues past the breakpoint until the imp comes back around
+    assert_eq!(
+        debugger.run(100),
+        StopReason::Breakpoint {
+            user: 0,
+            address: 3
+        }
+    );
+    assert_eq!(debugger.get_vm().get_cycle_count(), 13);
+
+    debugger.remove_breakpoint(3);
+    assert!(debugger.breakpoints().is_empty());
+    assert_eq!(debugger.run(100), StopReason::Done);
+}
+
+#[test]
+fn breakpoints_for_one_user() {
+    let vm = VirtualMachine::new_battle(&[imp(), imp()], &MatchSettings::default());
+    let start = vm.get_users_pcs()[0][0];
+    let mut debugger = Debugger::new(vm);
+
+    debugger.add_breakpoint(Breakpoint {
+        address: (start + 2) % 8000,
+        user: Some(1),
+    });
+    assert_eq!(debugger.run(20), StopReason::Done);
+
+    let mut debugger = Debugger::new(VirtualMachine::new_battle(
+        &[imp(), imp()],
+        &MatchSettings::default(),
+    ));
+    let start = debugger.get_vm().get_users_pcs()[0][0];
+    debugger.add_breakpoint(Breakpoint {
+        address: (start + 2) % 8000,
+        user: Some(0),
+    });
+    assert_eq!(
+        debugger.run(20),
+        StopReason::Breakpoint {
+            user: 0,
+            address: (start + 2) % 8000
+        }
+    );
+}
+
+#[test]
+fn watchpoints() {
+    let mut debugger = Debugger::new(VirtualMachine::new_simple(100, dwarf()));
+
+    // The dwarf only ever changes the B field of its bomb
+    debugger.add_watchpoint(Watchpoint {
+        address: 3,
+        field: WatchField::A,
+    });
+    assert_eq!(debugger.run(30), StopReason::Done);
+    debugger.remove_watchpoint(3);
+
+    let mut debugger = Debugger::new(VirtualMachine::new_simple(100, dwarf()));
+    debugger.add_watchpoint(Watchpoint {
+        address: 3,
+        field: WatchField::B,
+    });
+    match debugger.run(30) {
+        StopReason::Watchpoint {
+            actor,
+            address,
+            old,
+            new,
+        } => {
+            assert_eq!((actor.user, actor.pc, address), (0, 0, 3));
+            assert_eq!((old.b_reg, new.b_reg), (0, 4));
+        }
+        reason => panic!("Unexpected stop reason {:?}", reason),
+    }
+
+    let mut debugger = Debugger::new(VirtualMachine::new_simple(100, dwarf()));
+    debugger.add_watchpoint(Watchpoint {
+        address: 7,
+        field: WatchField::Cell,
+    });
+    match debugger.run(30) {
+        StopReason::Watchpoint { actor, address, .. } => {
+            assert_eq!((actor.pc, address), (1, 7));
+            assert_eq!(debugger.get_vm().get_cycle_count(), 2);
+        }
+        reason => panic!("Unexpected stop reason {:?}", reason),
+    }
+}
+
+#[test]
+fn process_count_conditions() {
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        JMP(B, -1, Direct, 0, Immediate)
+    };
+    let mut debugger = Debugger::new(VirtualMachine::new_simple(10, spl));
+    debugger.add_condition(Condition::ProcessesAtLeast(0, 5));
+
+    assert_eq!(
+        debugger.run(1000),
+        StopReason::Condition(Condition::ProcessesAtLeast(0, 5))
+    );
+    assert_eq!(debugger.get_vm().get_users_pcs()[0].len(), 5);
+
+    let mut debugger = Debugger::new(VirtualMachine::new_simple(10, dat()));
+    debugger.add_condition(Condition::ProcessesAtMost(0, 0));
+
+    assert_eq!(
+        debugger.step(),
+        StopReason::Condition(Condition::ProcessesAtMost(0, 0))
+    );
+    assert_eq!(debugger.run(10), StopReason::AllKilled);
+}
+
+#[test]
+fn step_one_warrior() {
+    let vm = VirtualMachine::new_battle(&[dat(), imp()], &MatchSettings::default());
+    let start = vm.get_users_pcs()[1][0];
+    let mut debugger = Debugger::new(vm);
+
+    // The first warrior runs (and dies) before the second takes its step
+    assert_eq!(debugger.step_warrior(1), StopReason::Done);
+    assert_eq!(debugger.get_vm().get_cycle_count(), 2);
+    assert_eq!(
+        debugger.get_vm().get_users_pcs()[1][0],
+        (start + 1) % debugger.get_vm().get_memory().len()
+    );
+
+    assert_eq!(debugger.step_warrior(1), StopReason::Done);
+    assert_eq!(debugger.get_vm().get_cycle_count(), 3);
+    assert_eq!(debugger.step_warrior(0), StopReason::WarriorKilled(0));
+}
@@ -0,0 +1,164 @@
+use darwin_lib::{
+    create_program, run_battle, run_match, run_tournament, Handicap, MatchSettings, VirtualMachine,
+    WarriorLimits,
+};
+
+mod common;
+use common::imp;
+
+/// Counts down 10 times then dies after executing 11 instructions
+fn countdown() -> Vec<darwin_lib::Instruction> {
+    create_program! {
+        DJN(B, 0, Direct, 10, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    }
+}
+
+#[test]
+fn process_caps_are_per_warrior() {
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        JMP(B, -1, Direct, 0, Immediate)
+    };
+
+    let settings = MatchSettings {
+        max_processes: 64,
+        handicaps: vec![Handicap {
+            max_processes: Some(4),
+            speed: 1,
+        }],
+        ..Default::default()
+    };
+
+    let mut vm = VirtualMachine::new_battle(&[spl.clone(), spl], &settings);
+    run_battle(&mut vm, 100);
+
+    assert_eq!(vm.get_max_processes(0), 4);
+    assert!(vm.get_users_pcs()[0].len() <= 4);
+    assert!(vm.get_users_pcs()[1].len() > 4);
+    assert!(vm.get_users_pcs()[1].len() <= 64);
+}
+
+#[test]
+fn simple_vm_has_the_default_limits() {
+    let vm = VirtualMachine::new_simple(100, countdown());
+    let limits = MatchSettings::default().warrior_limits(0);
+
+    assert_eq!(vm.get_max_processes(0), limits.max_processes);
+    assert_eq!(vm.get_speed(0), limits.speed);
+}
+
+#[test]
+fn battle_stops_once_every_warrior_is_dead() {
+    let settings = MatchSettings {
+        handicaps: vec![Handicap {
+            max_processes: None,
+            speed: 3,
+        }],
+        ..Default::default()
+    };
+
+    let mut vm = VirtualMachine::new_battle(&[countdown()], &settings);
+
+    // The warrior dies on the second instruction of its fourth turn
+    assert_eq!(run_battle(&mut vm, 100), 4);
+    assert_eq!(vm.get_cycle_count(), 11);
+}
+
+#[test]
+fn faster_warriors_execute_more_instructions() {
+    let settings = MatchSettings {
+        handicaps: vec![
+            Handicap {
+                max_processes: None,
+                speed: 3,
+            },
+            Handicap::default(),
+        ],
+        ..Default::default()
+    };
+
+    let mut vm = VirtualMachine::new_battle(&[imp(), imp()], &settings);
+    let start: Vec<usize> = vm.get_users_pcs().iter().map(|q| q[0]).collect();
+
+    run_battle(&mut vm, 2);
+
+    assert_eq!(vm.get_users_pcs()[0][0], start[0] + 6);
+    assert_eq!(vm.get_users_pcs()[1][0], start[1] + 2);
+}
+
+#[test]
+fn handicaps_follow_the_warrior() {
+    let jmp = create_program! { JMP(B, 0, Direct, 0, Immediate) };
+
+    let settings = MatchSettings {
+        rounds: 4,
+        handicaps: vec![
+            Handicap::default(),
+            Handicap {
+                max_processes: Some(1),
+                speed: 2,
+            },
+        ],
+        ..Default::default()
+    };
+
+    let result = run_match(&[jmp, countdown()], &settings).unwrap();
+
+    // The countdown executes two instructions each cycle no matter which warrior moves first
+    for round in &result.rounds {
+        assert_eq!(round.cycles, 6);
+    }
+
+    assert_eq!(
+        result.limits,
+        vec![
+            WarriorLimits {
+                max_processes: 8000,
+                speed: 1
+            },
+            WarriorLimits {
+                max_processes: 1,
+                speed: 2
+            },
+        ]
+    );
+}
+
+#[test]
+fn tournament_handicaps_use_tournament_ids() {
+    let jmp = create_program! { JMP(B, 0, Direct, 0, Immediate) };
+
+    let settings = MatchSettings {
+        rounds: 2,
+        seed: Some(1),
+        handicaps: vec![
+            Handicap::default(),
+            Handicap::default(),
+            Handicap {
+                max_processes: None,
+                speed: 2,
+            },
+        ],
+        ..Default::default()
+    };
+
+    let result = run_tournament(&[jmp.clone(), jmp, countdown()], &settings).unwrap();
+
+    // Warrior 2 (the countdown) is the second warrior of its matches
+    for tournament_match in &result.matches {
+        let speeds: Vec<usize> = tournament_match
+            .result
+            .limits
+            .iter()
+            .map(|l| l.speed)
+            .collect();
+
+        if tournament_match.warriors.contains(&2) {
+            assert_eq!(speeds, vec![1, 2]);
+            assert!(tournament_match.result.rounds.iter().all(|r| r.cycles == 6));
+        } else {
+            assert_eq!(speeds, vec![1, 1]);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use darwin_lib::{
+    create_program, Hill, HillError, Instruction, MatchSettings, Preset, SettingsError,
+    WarriorError,
+};
+
+mod common;
+use common::{dat, imp};
+
+fn stone() -> Vec<Instruction> {
+    // Loops forever without writing to memory
+    create_program! { JMP(B, 0, Direct, 0, Direct) }
+}
+
+fn settings() -> MatchSettings {
+    MatchSettings {
+        max_cycles: 100,
+        rounds: 2,
+        ..Default::default()
+    }
+}
+
+fn full_hill() -> Hill {
+    let mut hill = Hill::new(2, settings());
+    hill.challenge("dat", dat()).unwrap();
+    hill.challenge("imp", imp()).unwrap();
+    hill
+}
+
+#[test]
+fn challengers_are_ranked() {
+    let hill = full_hill();
+
+    let names: Vec<&str> = hill.warriors().iter().map(|w| w.name.as_str()).collect();
+    assert_eq!(names, vec!["imp", "dat"]);
+    assert_eq!(hill.score(0), 6);
+    assert_eq!(hill.score(1), 0);
+    assert_eq!(hill.result(0, 1), 6);
+}
+
+#[test]
+fn lowest_is_pushed_off() {
+    let mut hill = full_hill();
+
+    let result = hill.challenge("stone", stone()).unwrap();
+
+    // The stone ties with the imp and beats the DAT
+    assert_eq!(result.scores, vec![2, 6]);
+    assert_eq!(result.rank, Some(1));
+    assert_eq!(result.pushed_off.unwrap().name, "dat");
+
+    let names: Vec<&str> = hill.warriors().iter().map(|w| w.name.as_str()).collect();
+    assert_eq!(names, vec!["imp", "stone"]);
+    assert_eq!(hill.score(0), 2);
+    assert_eq!(hill.score(1), 2);
+}
+
+#[test]
+fn challenger_can_fail() {
+    let mut hill = full_hill();
+
+    let result = hill.challenge("dat 2", dat()).unwrap();
+
+    assert_eq!(result.rank, None);
+    assert_eq!(result.pushed_off.unwrap().name, "dat 2");
+    assert_eq!(hill.warriors().len(), 2);
+}
+
+#[test]
+fn invalid_challengers_are_rejected() {
+    // Even an empty hill, where the challenger plays no matches, checks the challenger
+    let mut hill = Hill::new(2, MatchSettings::from_preset(Preset::Nano));
+    assert_eq!(
+        hill.challenge("empty", vec![]).unwrap_err(),
+        SettingsError::Warrior(0, WarriorError::Empty)
+    );
+    assert_eq!(
+        hill.challenge("long", imp().repeat(50)).unwrap_err(),
+        SettingsError::Warrior(0, WarriorError::TooLong(50, 5))
+    );
+    assert!(hill.warriors().is_empty());
+
+    hill.challenge("imp", imp()).unwrap();
+    assert_eq!(
+        hill.challenge("long", imp().repeat(50)).unwrap_err(),
+        SettingsError::Warrior(0, WarriorError::TooLong(50, 5))
+    );
+    assert_eq!(hill.warriors().len(), 1);
+}
+
+#[test]
+fn age_counts_survived_challenges() {
+    let mut hill = full_hill();
+    hill.challenge("dat 2", dat()).unwrap();
+    hill.challenge("stone", stone()).unwrap();
+
+    let ages: Vec<(&str, usize)> = hill
+        .warriors()
+        .iter()
+        .map(|w| (w.name.as_str(), w.age))
+        .collect();
+    assert_eq!(ages, vec![("imp", 2), ("stone", 0)]);
+}
+
+#[test]
+fn save_and_load() {
+    let mut hill = full_hill();
+    hill.challenge("stone", stone()).unwrap();
+
+    let mut saved = Vec::new();
+    hill.save(&mut saved).unwrap();
+
+    let loaded = Hill::load(saved.as_slice(), settings()).unwrap();
+
+    assert_eq!(loaded.get_size(), 2);
+    assert_eq!(loaded.warriors(), hill.warriors());
+    for a in 0..2 {
+        for b in 0..2 {
+            assert_eq!(loaded.result(a, b), hill.result(a, b));
+        }
+    }
+
+    let path = std::env::temp_dir().join("darwin_lib_hill_test.txt");
+    hill.save_to_file(&path).unwrap();
+    let loaded = Hill::load_from_file(&path, settings()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.warriors(), hill.warriors());
+}
+
+#[test]
+fn load_invalid() {
+    match Hill::load("size 2\nwarrior x imp\n".as_bytes(), settings()) {
+        Err(HillError::InvalidLine(2)) => {}
+        other => panic!("Expected an invalid line error, got {:?}", other),
+    }
+
+    match Hill::load(
+        "size 2\nwarrior 0 imp\nresults 0\nABC 0 1\nend\n".as_bytes(),
+        settings(),
+    ) {
+        Err(HillError::InvalidInstruction(4, _)) => {}
+        other => panic!("Expected an invalid instruction error, got {:?}", other),
+    }
+
+    // The second warrior's results are missing its score against the first warrior
+    match Hill::load(
+        "size 2\nwarrior 0 imp\nresults 0 0\nJMP 0\nend\nwarrior 0 dat\nresults 0\nDAT #0, #0\nend\n"
+            .as_bytes(),
+        settings(),
+    ) {
+        Err(HillError::InvalidLine(7)) => {}
+        other => panic!("Expected an invalid line error, got {:?}", other),
+    }
+
+    // The hill holds more warriors than its size
+    match Hill::load(
+        "size 1\nwarrior 0 imp\nresults 0\nJMP 0\nend\nwarrior 0 dat\nresults 0\nDAT #0, #0\nend\n"
+            .as_bytes(),
+        settings(),
+    ) {
+        Err(HillError::InvalidLine(6)) => {}
+        other => panic!("Expected an invalid line error, got {:?}", other),
+    }
+}
+
+#[test]
+fn save_explicit_modifiers() {
+    // Without a modifier the compiler would load this as ADD.F, but it is executed as ADD.AB
+    let adder = create_program! {
+        ADD(None, 1, Direct, 2, Direct)
+        JMP(None, -1, Direct, 0, Direct)
+    };
+    let mut hill = Hill::new(2, settings());
+    hill.challenge("adder", adder).unwrap();
+
+    let mut saved = Vec::new();
+    hill.save(&mut saved).unwrap();
+    let loaded = Hill::load(saved.as_slice(), settings()).unwrap();
+
+    assert_eq!(
+        loaded.warriors()[0].program,
+        create_program! {
+            ADD(AB, 1, Direct, 2, Direct)
+            JMP(B, -1, Direct, 0, Direct)
+        }
+    );
+}
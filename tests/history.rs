@@ -0,0 +1,98 @@
+use darwin_lib::{create_program, ExecutionMode, MatchSettings, Snapshot, VirtualMachine};
+
+fn battle(mode: ExecutionMode) -> VirtualMachine {
+    // Stores a counter in P-space while bombing
+    let bomber = create_program! {
+        ADD(AB, 3, Immediate, 4, Direct)
+        MOV(I, 4, Direct, 3, PreDecrementIndirectB)
+        STP(B, 2, Direct, 7, Immediate)
+        JMP(B, -3, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        MOV(I, 0, Direct, 1, PostIncrementIndirectA)
+    };
+
+    let mut vm = VirtualMachine::new_battle(
+        &[bomber, spl],
+        &MatchSettings {
+            execution_mode: mode,
+            core_size: 400,
+            min_separation: 50,
+            max_processes: 16,
+            ..Default::default()
+        },
+    );
+    vm.track_ownership();
+    vm
+}
+
+fn assert_same_state(a: &VirtualMachine, b: &VirtualMachine) {
+    assert_eq!(a.get_memory(), b.get_memory());
+    assert_eq!(a.get_users_pcs(), b.get_users_pcs());
+    assert_eq!(a.get_cur_user(), b.get_cur_user());
+    assert_eq!(a.get_cycle_count(), b.get_cycle_count());
+    assert_eq!(a.get_pspace(), b.get_pspace());
+    assert_eq!(a.get_ownership(), b.get_ownership());
+}
+
+#[test]
+fn step_back_through_a_battle() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        let mut vm = battle(*mode);
+        vm.record_history(1000);
+
+        let mut states: Vec<Snapshot> = Vec::new();
+        for _ in 0..500 {
+            states.push(vm.snapshot());
+            vm.cycle();
+        }
+
+        assert_eq!(vm.get_history_len(), 500);
+        assert_ne!(vm.get_pspace().load(0, 7), 0);
+
+        for state in states.iter().rev() {
+            assert!(vm.step_back());
+            assert_same_state(&vm, state.get_vm());
+        }
+
+        assert!(!vm.step_back());
+    }
+}
+
+#[test]
+fn stepping_forward_repeats_the_cycles() {
+    let mut vm = battle(ExecutionMode::Icws94);
+    vm.record_history(100);
+
+    for _ in 0..300 {
+        vm.cycle();
+    }
+    let end = vm.snapshot();
+
+    assert_eq!(vm.step_back_by(60), 60);
+    for _ in 0..60 {
+        vm.cycle();
+    }
+
+    assert_same_state(&vm, end.get_vm());
+}
+
+#[test]
+fn history_is_limited() {
+    let mut vm = battle(ExecutionMode::Lazy);
+    vm.record_history(10);
+
+    for _ in 0..50 {
+        vm.cycle();
+    }
+
+    assert_eq!(vm.get_history_len(), 10);
+    assert_eq!(vm.step_back_by(20), 10);
+    assert_eq!(vm.get_cycle_count(), 40);
+
+    vm.stop_recording_history();
+    vm.cycle();
+    assert!(!vm.step_back());
+}
@@ -0,0 +1,449 @@
+use darwin_lib::{cmd, create_program, parse_program, ExecutionMode, Instruction, VirtualMachine};
+
+/// Creates a VM running `program` from address 0 with ICWS '94 semantics
+fn icws94_vm(size: usize, program: Vec<Instruction>) -> VirtualMachine {
+    let mut vm = VirtualMachine::new_simple(size, program);
+    vm.set_execution_mode(ExecutionMode::Icws94);
+    vm
+}
+
+fn parse_instruction(line: &str) -> Instruction {
+    parse_program(line).unwrap()[0]
+}
+
+/// (A mode, B mode, the cells that changed after one cycle as (address, instruction))
+type Case = (&'static str, &'static str, &'static [(usize, &'static str)]);
+
+/// Runs `op_code` with every combination of address modes against the same core, and checks the
+/// cells that changed against the table. The tables were generated with an independent model of
+/// the reference emulator in the ICWS '94 draft.
+fn check_mode_combinations(op_code: &str, operand: isize, cases: &[Case]) {
+    let core = [
+        // The instruction under test is inserted at address 0
+        cmd! { DAT(F, 1, Immediate, 2, Immediate) },
+        cmd! { DAT(F, 3, Immediate, 4, Immediate) },
+        cmd! { NOP(F, 5, Immediate, 6, Immediate) },
+        cmd! { DAT(F, 7, Immediate, 0, Immediate) },
+        cmd! { NOP(F, 1, Immediate, 2, Immediate) },
+        cmd! { DAT(F, 3, Immediate, 5, Immediate) },
+        cmd! { NOP(F, 6, Immediate, 7, Immediate) },
+    ];
+
+    assert_eq!(
+        cases.len(),
+        64,
+        "Every combination of modes should be tested"
+    );
+
+    for (a_mode, b_mode, changed) in cases {
+        let instruction = parse_instruction(&format!(
+            "{} {}{} {}{}",
+            op_code, a_mode, operand, b_mode, operand
+        ));
+
+        let mut program = vec![instruction];
+        program.extend(core.iter().copied());
+
+        let mut vm = icws94_vm(8, program.clone());
+        vm.cycle();
+
+        let mut expected = program;
+        for (address, cell) in changed.iter() {
+            expected[*address] = parse_instruction(cell);
+        }
+
+        assert_eq!(
+            vm.get_memory(),
+            expected.as_slice(),
+            "Incorrect core after `{}`",
+            instruction
+        );
+        assert_eq!(
+            vm.get_users_pcs()[0],
+            vec![1],
+            "Incorrect PC after `{}`",
+            instruction
+        );
+    }
+}
+
+#[test]
+fn mov_through_shared_pointer() {
+    check_mode_combinations("MOV.I", 1, MOV_THROUGH_SHARED_POINTER);
+}
+
+#[test]
+fn add_through_shared_pointer() {
+    check_mode_combinations("ADD.F", 1, ADD_THROUGH_SHARED_POINTER);
+}
+
+#[test]
+fn mov_self_reference() {
+    check_mode_combinations("MOV.I", 0, MOV_SELF_REFERENCE);
+}
+
+#[test]
+fn a_register_is_copied_before_increments() {
+    // The A register is a copy of `MOV }0, >0` taken before either increment, and is then written
+    // back over the instruction, undoing both increments
+    let mut vm = icws94_vm(
+        4,
+        create_program! { MOV(I, 0, PostIncrementIndirectA, 0, PostIncrementIndirectB) },
+    );
+    vm.cycle();
+    assert_eq!(
+        vm.get_memory()[0],
+        cmd! { MOV(I, 0, PostIncrementIndirectA, 0, PostIncrementIndirectB) }
+    );
+
+    // With lazy evaluation the increments are applied after the copy
+    let mut vm = VirtualMachine::new_simple(
+        4,
+        create_program! { MOV(I, 0, PostIncrementIndirectA, 0, PostIncrementIndirectB) },
+    );
+    vm.cycle();
+    assert_eq!(
+        vm.get_memory()[0],
+        cmd! { MOV(I, 1, PostIncrementIndirectA, 1, PostIncrementIndirectB) }
+    );
+}
+
+#[test]
+fn b_register_is_a_snapshot() {
+    // The B register is copied before the A value is added, so ADD.F adds to the original values
+    // even though the destination is also the A source
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            ADD(F, 1, Direct, 1, Direct)
+            DAT(F, 2, Immediate, 3, Immediate)
+        },
+    );
+    vm.cycle();
+    assert_eq!(
+        vm.get_memory()[1],
+        cmd! { DAT(F, 4, Immediate, 6, Immediate) }
+    );
+}
+
+#[test]
+fn jmn_f_jumps_if_either_field_is_non_zero() {
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            JMN(F, 3, Direct, 1, Direct)
+            DAT(F, 0, Immediate, 1, Immediate)
+        },
+    );
+    vm.cycle();
+    assert_eq!(vm.get_users_pcs()[0], vec![3]);
+
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            JMN(F, 3, Direct, 1, Direct)
+            DAT(F, 0, Immediate, 0, Immediate)
+        },
+    );
+    vm.cycle();
+    assert_eq!(vm.get_users_pcs()[0], vec![1]);
+}
+
+#[test]
+fn djn_f_decrements_both_fields() {
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            DJN(F, 3, Direct, 1, Direct)
+            DAT(F, 1, Immediate, 2, Immediate)
+        },
+    );
+    vm.cycle();
+    // The B field is still non-zero so it jumps
+    assert_eq!(
+        vm.get_memory()[1],
+        cmd! { DAT(F, 0, Immediate, 1, Immediate) }
+    );
+    assert_eq!(vm.get_users_pcs()[0], vec![3]);
+
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            DJN(F, 3, Direct, 1, Direct)
+            DAT(F, 1, Immediate, 1, Immediate)
+        },
+    );
+    vm.cycle();
+    assert_eq!(
+        vm.get_memory()[1],
+        cmd! { DAT(F, 0, Immediate, 0, Immediate) }
+    );
+    assert_eq!(vm.get_users_pcs()[0], vec![1]);
+}
+
+#[test]
+fn spl_queues_next_instruction_first() {
+    let mut vm = icws94_vm(
+        8,
+        create_program! { SPL(B, 2, PostIncrementIndirectB, 0, Direct) },
+    );
+    vm.cycle();
+
+    // The new process starts at 2 + 0, the B field of address 2 is only incremented afterwards
+    assert_eq!(vm.get_users_pcs()[0], vec![1, 2]);
+    assert_eq!(vm.get_memory()[2].b_reg, 1);
+}
+
+#[test]
+fn division_by_zero_kills_but_writes_other_field() {
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            DIV(F, 1, Direct, 2, Direct)
+            DAT(F, 0, Immediate, 2, Immediate)
+            DAT(F, 5, Immediate, 6, Immediate)
+        },
+    );
+    vm.cycle();
+
+    assert_eq!(
+        vm.get_memory()[2],
+        cmd! { DAT(F, 5, Immediate, 3, Immediate) }
+    );
+    assert!(vm.get_users_pcs()[0].is_empty());
+}
+
+#[test]
+fn seq_i_and_slt_i() {
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            SEQ(I, 1, Direct, 2, Direct)
+            DAT(F, 1, Immediate, 2, Immediate)
+            DAT(F, 1, Immediate, 2, Immediate)
+        },
+    );
+    vm.cycle();
+    assert_eq!(vm.get_users_pcs()[0], vec![2]);
+
+    // SLT.I behaves like SLT.F
+    let mut vm = icws94_vm(
+        8,
+        create_program! {
+            SLT(I, 1, Direct, 2, Direct)
+            DAT(F, 1, Immediate, 2, Immediate)
+            DAT(F, 2, Immediate, 3, Immediate)
+        },
+    );
+    vm.cycle();
+    assert_eq!(vm.get_users_pcs()[0], vec![2]);
+}
+
+#[test]
+fn missing_modifier_uses_default() {
+    // MOV with no modifier and no immediate operands behaves like MOV.I
+    let mut vm = icws94_vm(4, create_program! { MOV(None, 0, Direct, 1, Direct) });
+    vm.cycle();
+    assert_eq!(vm.get_memory()[1], cmd! { MOV(None, 0, Direct, 1, Direct) });
+}
+
+const MOV_THROUGH_SHARED_POINTER: &[Case] = &[
+    ("#", "#", &[]),
+    ("#", "$", &[(1, "MOV.I #1 $1")]),
+    ("#", "*", &[(2, "MOV.I #1 *1")]),
+    ("#", "@", &[(3, "MOV.I #1 @1")]),
+    ("#", "{", &[(1, "MOV.I #1 {1")]),
+    ("#", "<", &[(1, "DAT.F #1 #1"), (2, "MOV.I #1 <1")]),
+    ("#", "}", &[(1, "DAT.F #2 #2"), (2, "MOV.I #1 }1")]),
+    ("#", ">", &[(1, "DAT.F #1 #3"), (3, "MOV.I #1 >1")]),
+    ("$", "#", &[(0, "DAT.F #1 #2")]),
+    ("$", "$", &[]),
+    ("$", "*", &[(2, "DAT.F #1 #2")]),
+    ("$", "@", &[(3, "DAT.F #1 #2")]),
+    ("$", "{", &[]),
+    ("$", "<", &[(1, "DAT.F #1 #1"), (2, "DAT.F #1 #2")]),
+    ("$", "}", &[(1, "DAT.F #2 #2"), (2, "DAT.F #1 #2")]),
+    ("$", ">", &[(1, "DAT.F #1 #3"), (3, "DAT.F #1 #2")]),
+    ("*", "#", &[(0, "DAT.F #3 #4")]),
+    ("*", "$", &[(1, "DAT.F #3 #4")]),
+    ("*", "*", &[]),
+    ("*", "@", &[(3, "DAT.F #3 #4")]),
+    ("*", "{", &[(1, "DAT.F #3 #4")]),
+    ("*", "<", &[(1, "DAT.F #1 #1")]),
+    ("*", "}", &[(1, "DAT.F #2 #2")]),
+    ("*", ">", &[(1, "DAT.F #1 #3"), (3, "DAT.F #3 #4")]),
+    ("@", "#", &[(0, "NOP.F #5 #6")]),
+    ("@", "$", &[(1, "NOP.F #5 #6")]),
+    ("@", "*", &[(2, "NOP.F #5 #6")]),
+    ("@", "@", &[]),
+    ("@", "{", &[(1, "NOP.F #5 #6")]),
+    ("@", "<", &[(1, "DAT.F #1 #1"), (2, "NOP.F #5 #6")]),
+    ("@", "}", &[(1, "DAT.F #2 #2"), (2, "NOP.F #5 #6")]),
+    ("@", ">", &[(1, "DAT.F #1 #3")]),
+    ("{", "#", &[(0, "DAT.F #0 #2"), (1, "DAT.F #0 #2")]),
+    ("{", "$", &[(1, "DAT.F #0 #2")]),
+    ("{", "*", &[(1, "DAT.F #0 #2")]),
+    ("{", "@", &[(1, "DAT.F #0 #2"), (3, "DAT.F #0 #2")]),
+    ("{", "{", &[(0, "DAT.F #0 #2"), (1, "DAT.F #7 #2")]),
+    ("{", "<", &[(1, "DAT.F #0 #1"), (2, "DAT.F #0 #2")]),
+    ("{", "}", &[(1, "DAT.F #0 #2")]),
+    ("{", ">", &[(1, "DAT.F #0 #3"), (3, "DAT.F #0 #2")]),
+    ("<", "#", &[(0, "DAT.F #3 #4"), (1, "DAT.F #1 #1")]),
+    ("<", "$", &[(1, "DAT.F #3 #4")]),
+    ("<", "*", &[(1, "DAT.F #1 #1")]),
+    ("<", "@", &[(1, "DAT.F #1 #1")]),
+    ("<", "{", &[(1, "DAT.F #3 #4")]),
+    ("<", "<", &[(1, "DAT.F #3 #4")]),
+    ("<", "}", &[(1, "DAT.F #2 #1")]),
+    ("<", ">", &[]),
+    ("}", "#", &[(0, "DAT.F #3 #4"), (1, "DAT.F #2 #2")]),
+    ("}", "$", &[(1, "DAT.F #3 #4")]),
+    ("}", "*", &[(1, "DAT.F #2 #2"), (3, "DAT.F #3 #4")]),
+    ("}", "@", &[(1, "DAT.F #2 #2"), (3, "DAT.F #3 #4")]),
+    ("}", "{", &[]),
+    ("}", "<", &[(1, "DAT.F #2 #1")]),
+    ("}", "}", &[(1, "DAT.F #3 #2"), (3, "DAT.F #3 #4")]),
+    ("}", ">", &[(1, "DAT.F #2 #3"), (3, "DAT.F #3 #4")]),
+    (">", "#", &[(0, "NOP.F #5 #6"), (1, "DAT.F #1 #3")]),
+    (">", "$", &[(1, "NOP.F #5 #6")]),
+    (">", "*", &[(1, "DAT.F #1 #3"), (2, "NOP.F #5 #6")]),
+    (">", "@", &[(1, "DAT.F #1 #3"), (4, "NOP.F #5 #6")]),
+    (">", "{", &[(1, "NOP.F #5 #6")]),
+    (">", "<", &[]),
+    (">", "}", &[(1, "DAT.F #2 #3"), (2, "NOP.F #5 #6")]),
+    (">", ">", &[(1, "DAT.F #1 #4"), (4, "NOP.F #5 #6")]),
+];
+
+const ADD_THROUGH_SHARED_POINTER: &[Case] = &[
+    ("#", "#", &[(0, "ADD.F #2 #2")]),
+    ("#", "$", &[(1, "DAT.F #2 #3")]),
+    ("#", "*", &[(2, "DAT.F #4 #5")]),
+    ("#", "@", &[(3, "NOP.F #6 #7")]),
+    ("#", "{", &[(1, "DAT.F #1 #3")]),
+    ("#", "<", &[(1, "DAT.F #1 #1"), (2, "DAT.F #4 #5")]),
+    ("#", "}", &[(1, "DAT.F #2 #2"), (2, "DAT.F #4 #5")]),
+    ("#", ">", &[(1, "DAT.F #1 #3"), (3, "NOP.F #6 #7")]),
+    ("$", "#", &[(0, "ADD.F $2 #3")]),
+    ("$", "$", &[(1, "DAT.F #2 #4")]),
+    ("$", "*", &[(2, "DAT.F #4 #6")]),
+    ("$", "@", &[(3, "NOP.F #6 #0")]),
+    ("$", "{", &[(1, "DAT.F #1 #4")]),
+    ("$", "<", &[(1, "DAT.F #1 #1"), (2, "DAT.F #4 #6")]),
+    ("$", "}", &[(1, "DAT.F #2 #2"), (2, "DAT.F #4 #6")]),
+    ("$", ">", &[(1, "DAT.F #1 #3"), (3, "NOP.F #6 #0")]),
+    ("*", "#", &[(0, "ADD.F *4 #5")]),
+    ("*", "$", &[(1, "DAT.F #4 #6")]),
+    ("*", "*", &[(2, "DAT.F #6 #0")]),
+    ("*", "@", &[(3, "NOP.F #0 #2")]),
+    ("*", "{", &[(1, "DAT.F #3 #6")]),
+    ("*", "<", &[(1, "DAT.F #1 #1"), (2, "DAT.F #6 #0")]),
+    ("*", "}", &[(1, "DAT.F #2 #2"), (2, "DAT.F #6 #0")]),
+    ("*", ">", &[(1, "DAT.F #1 #3"), (3, "NOP.F #0 #2")]),
+    ("@", "#", &[(0, "ADD.F @6 #7")]),
+    ("@", "$", &[(1, "DAT.F #6 #0")]),
+    ("@", "*", &[(2, "DAT.F #0 #2")]),
+    ("@", "@", &[(3, "NOP.F #2 #4")]),
+    ("@", "{", &[(1, "DAT.F #5 #0")]),
+    ("@", "<", &[(1, "DAT.F #1 #1"), (2, "DAT.F #0 #2")]),
+    ("@", "}", &[(1, "DAT.F #2 #2"), (2, "DAT.F #0 #2")]),
+    ("@", ">", &[(1, "DAT.F #1 #3"), (3, "NOP.F #2 #4")]),
+    ("{", "#", &[(0, "ADD.F {1 #3"), (1, "DAT.F #0 #2")]),
+    ("{", "$", &[(1, "DAT.F #0 #4")]),
+    ("{", "*", &[(1, "DAT.F #0 #4")]),
+    ("{", "@", &[(1, "DAT.F #0 #2"), (3, "NOP.F #5 #0")]),
+    ("{", "{", &[(0, "ADD.F {1 {3"), (1, "DAT.F #7 #2")]),
+    ("{", "<", &[(1, "DAT.F #0 #1"), (2, "DAT.F #3 #6")]),
+    ("{", "}", &[(1, "DAT.F #0 #4")]),
+    ("{", ">", &[(1, "DAT.F #0 #3"), (3, "NOP.F #5 #0")]),
+    ("<", "#", &[(0, "ADD.F <4 #5"), (1, "DAT.F #1 #1")]),
+    ("<", "$", &[(1, "DAT.F #4 #5")]),
+    ("<", "*", &[(1, "DAT.F #1 #1"), (2, "DAT.F #6 #0")]),
+    ("<", "@", &[(1, "DAT.F #1 #1"), (2, "DAT.F #6 #0")]),
+    ("<", "{", &[(1, "DAT.F #3 #5")]),
+    ("<", "<", &[(1, "DAT.F #4 #4")]),
+    ("<", "}", &[(1, "DAT.F #2 #1"), (2, "DAT.F #6 #0")]),
+    ("<", ">", &[(2, "DAT.F #6 #0")]),
+    ("}", "#", &[(0, "ADD.F }4 #5"), (1, "DAT.F #2 #2")]),
+    ("}", "$", &[(1, "DAT.F #5 #6")]),
+    ("}", "*", &[(1, "DAT.F #2 #2"), (3, "NOP.F #0 #2")]),
+    ("}", "@", &[(1, "DAT.F #2 #2"), (3, "NOP.F #0 #2")]),
+    ("}", "{", &[(2, "DAT.F #6 #0")]),
+    ("}", "<", &[(1, "DAT.F #2 #1"), (2, "DAT.F #6 #0")]),
+    ("}", "}", &[(1, "DAT.F #3 #2"), (3, "NOP.F #0 #2")]),
+    ("}", ">", &[(1, "DAT.F #2 #3"), (3, "NOP.F #0 #2")]),
+    (">", "#", &[(0, "ADD.F >6 #7"), (1, "DAT.F #1 #3")]),
+    (">", "$", &[(1, "DAT.F #6 #1")]),
+    (">", "*", &[(1, "DAT.F #1 #3"), (2, "DAT.F #0 #2")]),
+    (">", "@", &[(1, "DAT.F #1 #3"), (4, "DAT.F #4 #6")]),
+    (">", "{", &[(1, "DAT.F #5 #1")]),
+    (">", "<", &[(3, "NOP.F #2 #4")]),
+    (">", "}", &[(1, "DAT.F #2 #3"), (2, "DAT.F #0 #2")]),
+    (">", ">", &[(1, "DAT.F #1 #4"), (4, "DAT.F #4 #6")]),
+];
+
+const MOV_SELF_REFERENCE: &[Case] = &[
+    ("#", "#", &[]),
+    ("#", "$", &[]),
+    ("#", "*", &[]),
+    ("#", "@", &[]),
+    ("#", "{", &[(0, "MOV.I #7 {0"), (7, "MOV.I #0 {0")]),
+    ("#", "<", &[(0, "MOV.I #0 <7"), (7, "MOV.I #0 <0")]),
+    ("#", "}", &[]),
+    ("#", ">", &[]),
+    ("$", "#", &[]),
+    ("$", "$", &[]),
+    ("$", "*", &[]),
+    ("$", "@", &[]),
+    ("$", "{", &[(0, "MOV.I $7 {0"), (7, "MOV.I $0 {0")]),
+    ("$", "<", &[(0, "MOV.I $0 <7"), (7, "MOV.I $0 <0")]),
+    ("$", "}", &[]),
+    ("$", ">", &[]),
+    ("*", "#", &[]),
+    ("*", "$", &[]),
+    ("*", "*", &[]),
+    ("*", "@", &[]),
+    ("*", "{", &[(0, "MOV.I *7 {0"), (7, "MOV.I *0 {0")]),
+    ("*", "<", &[(0, "MOV.I *0 <7"), (7, "MOV.I *0 <0")]),
+    ("*", "}", &[]),
+    ("*", ">", &[]),
+    ("@", "#", &[]),
+    ("@", "$", &[]),
+    ("@", "*", &[]),
+    ("@", "@", &[]),
+    ("@", "{", &[(0, "MOV.I @7 {0"), (7, "MOV.I @0 {0")]),
+    ("@", "<", &[(0, "MOV.I @0 <7"), (7, "MOV.I @0 <0")]),
+    ("@", "}", &[]),
+    ("@", ">", &[]),
+    ("{", "#", &[(0, "NOP.F #6 #7")]),
+    ("{", "$", &[(0, "NOP.F #6 #7")]),
+    ("{", "*", &[(0, "MOV.I {7 *0")]),
+    ("{", "@", &[(0, "NOP.F #6 #7")]),
+    ("{", "{", &[(0, "MOV.I {6 {0"), (6, "NOP.F #6 #7")]),
+    ("{", "<", &[(0, "MOV.I {7 <7")]),
+    ("{", "}", &[]),
+    ("{", ">", &[(0, "NOP.F #6 #7")]),
+    ("<", "#", &[(0, "NOP.F #6 #7")]),
+    ("<", "$", &[(0, "NOP.F #6 #7")]),
+    ("<", "*", &[(0, "NOP.F #6 #7")]),
+    ("<", "@", &[(0, "MOV.I <0 @7")]),
+    ("<", "{", &[(0, "MOV.I <7 {7")]),
+    ("<", "<", &[(0, "MOV.I <0 <6"), (6, "NOP.F #6 #7")]),
+    ("<", "}", &[(0, "NOP.F #6 #7")]),
+    ("<", ">", &[]),
+    ("}", "#", &[]),
+    ("}", "$", &[]),
+    ("}", "*", &[(0, "MOV.I }1 *0"), (1, "MOV.I }0 *0")]),
+    ("}", "@", &[]),
+    ("}", "{", &[]),
+    ("}", "<", &[(0, "MOV.I }1 <7"), (7, "MOV.I }0 <0")]),
+    ("}", "}", &[(0, "MOV.I }2 }0"), (1, "MOV.I }0 }0")]),
+    ("}", ">", &[]),
+    (">", "#", &[]),
+    (">", "$", &[]),
+    (">", "*", &[]),
+    (">", "@", &[(0, "MOV.I >0 @1"), (1, "MOV.I >0 @0")]),
+    (">", "{", &[(0, "MOV.I >7 {1"), (7, "MOV.I >0 {0")]),
+    (">", "<", &[]),
+    (">", "}", &[]),
+    (">", ">", &[(0, "MOV.I >0 >2"), (1, "MOV.I >0 >0")]),
+];
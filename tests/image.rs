@@ -0,0 +1,116 @@
+use darwin_lib::{
+    create_program, run_round, CoreImage, ImageFormat, MatchSettings, ProcessChart, VirtualMachine,
+    WarriorStatistics,
+};
+
+mod common;
+use common::imp;
+
+#[test]
+fn core_images() {
+    let mut vm = VirtualMachine::new_simple(10, imp());
+    vm.track_ownership();
+    vm.cycle();
+    vm.cycle();
+
+    let image = CoreImage {
+        width: 4,
+        scale: 2,
+        format: ImageFormat::Ppm,
+    };
+    assert_eq!(image.dimensions(10), (8, 6));
+
+    let mut bytes = Vec::new();
+    image.write(&vm, &mut bytes).unwrap();
+    let header = b"P6\n8 6\n255\n";
+    assert!(bytes.starts_with(header));
+    let pixels = &bytes[header.len()..];
+    assert_eq!(pixels.len(), 8 * 6 * 3);
+
+    // The imp was loaded into the first cell, wrote to the second and has its process in the
+    // third. The rest are empty.
+    let pixel = |x: usize, y: usize| &pixels[(y * 8 + x) * 3..(y * 8 + x + 1) * 3];
+    assert_eq!(pixel(2, 0), pixel(3, 1));
+    assert!(pixel(2, 0)[0] > pixel(2, 0)[1]);
+    assert_eq!(pixel(4, 0), &[255, 255, 255]);
+    assert_eq!(pixel(6, 0), &[0, 0, 0]);
+    // The last row is only half filled
+    assert_eq!(pixel(7, 5), &[0, 0, 0]);
+
+    let greyscale = CoreImage {
+        format: ImageFormat::Pgm,
+        ..image
+    };
+    let mut bytes = Vec::new();
+    greyscale.write(&vm, &mut bytes).unwrap();
+    assert!(bytes.starts_with(b"P5\n8 6\n255\n"));
+    assert_eq!(bytes.len() - 11, 8 * 6);
+}
+
+#[test]
+fn process_counts_over_time() {
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    let mut vm = VirtualMachine::new_simple(20, spl);
+    vm.cycle();
+    vm.track_statistics();
+    for _ in 0..4 {
+        vm.cycle();
+    }
+
+    // The SPL makes a process that is killed by the DAT, then it's run again
+    let statistics = &vm.get_statistics().unwrap().warriors()[0];
+    assert_eq!(
+        statistics.process_counts,
+        vec![(1, 2), (2, 1), (3, 2), (4, 1), (5, 2)]
+    );
+}
+
+#[test]
+fn process_charts() {
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        MOV(I, 0, Direct, 1, PostIncrementIndirectA)
+    };
+    let settings = MatchSettings {
+        statistics: true,
+        max_cycles: 2000,
+        ..Default::default()
+    };
+    let round = run_round(&[imp(), spl, imp()], &settings, 0, 3);
+    let statistics = round.statistics.unwrap();
+    assert_eq!(statistics[0].process_counts, vec![(0, 1)]);
+
+    let chart = ProcessChart {
+        width: 400,
+        height: 200,
+    };
+    let svg = chart.to_svg(&statistics);
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches("<polyline").count(), 3);
+    assert!(svg.contains("warrior 2"));
+    let end = statistics.iter().map(|warrior| warrior.last_cycle).max();
+    assert_eq!(end, Some(round.cycles * 3));
+    assert!(svg.contains(&format!("{} cycles", round.cycles * 3)));
+}
+
+#[test]
+fn twelve_warrior_colours() {
+    let chart = ProcessChart::default();
+    let colour = |warrior: usize| {
+        let svg = chart.to_svg(&vec![WarriorStatistics::new(1); warrior + 1]);
+        let line = &svg[svg.rfind("<polyline").unwrap()..];
+        let start = line.find("stroke=\"#").unwrap() + 8;
+        line[start..start + 7].to_string()
+    };
+
+    // There are twelve colours like the renderer, which then repeat
+    let colours: Vec<String> = (0..13).map(colour).collect();
+    for (i, a) in colours[..12].iter().enumerate() {
+        assert!(colours[i + 1..12].iter().all(|b| a != b));
+    }
+    assert_eq!(colours[12], colours[0]);
+}
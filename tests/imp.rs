@@ -17,6 +17,7 @@ fn create_imp() {
 }
 
 #[test]
+#[allow(clippy::partialeq_to_none)]
 fn run_imp() {
     let program = create_program! { MOV(I, 0, Direct, 1, Direct) };
     let mut vm = VirtualMachine::new_simple(20, program);
@@ -0,0 +1,190 @@
+use darwin_lib::handlers::{fold, Limits};
+use darwin_lib::{
+    cmd, create_program, run_match, ExecutionMode, Instruction, MatchSettings, OpCode,
+    SettingsError, VirtualMachine,
+};
+
+mod common;
+use common::imp;
+
+const MODES: [ExecutionMode; 2] = [ExecutionMode::Lazy, ExecutionMode::Icws94];
+
+/// Creates a VM of size 100 running `program` from address 0 with the given limits
+fn limited_vm(program: Vec<Instruction>, mode: ExecutionMode, limits: Limits) -> VirtualMachine {
+    let mut vm = VirtualMachine::new_simple(100, program);
+    vm.set_execution_mode(mode);
+    vm.set_limits(limits);
+    vm
+}
+
+#[test]
+fn fold_offsets() {
+    // Offsets up to half the limit are kept, anything further wraps to the negative side
+    assert_eq!(fold(10, 20, 100), 10);
+    assert_eq!(fold(11, 20, 100), 91);
+    assert_eq!(fold(-5, 20, 100), 95);
+    assert_eq!(fold(45, 20, 100), 5);
+    assert_eq!(fold(-45, 20, 100), 95);
+
+    // A limit of the core size only wraps around the core
+    assert_eq!(fold(-5, 100, 100), 95);
+    assert_eq!(fold(145, 100, 100), 45);
+
+    // Offsets are reduced to the core first, so a limit that doesn't divide the core folds the
+    // same cell to the same place
+    assert_eq!(fold(-1, 3000, 8000), 6999);
+    assert_eq!(fold(7999, 3000, 8000), 6999);
+    assert_eq!(fold(-1, 30, 100), 9);
+    assert_eq!(fold(99, 30, 100), 9);
+}
+
+#[test]
+fn limits_that_do_not_divide_the_core() {
+    for mode in &MODES {
+        for offset in &[-1, 99] {
+            let mut vm = limited_vm(
+                create_program! { MOV(I, 0, Direct, *offset, Direct) },
+                *mode,
+                Limits {
+                    read: 100,
+                    write: 30,
+                },
+            );
+            vm.cycle();
+
+            // The ICWS '94 mode reduces the copied fields to the core, so only the op codes are
+            // compared
+            let memory = vm.get_memory();
+            assert_eq!(memory[9].op_code, OpCode::MOV, "{:?} {}", mode, offset);
+            assert_eq!(memory[99].op_code, OpCode::DAT, "{:?} {}", mode, offset);
+        }
+    }
+}
+
+#[test]
+fn writes_are_folded() {
+    for mode in &MODES {
+        let mut vm = limited_vm(
+            create_program! { MOV(I, 0, Direct, 15, Direct) },
+            *mode,
+            Limits {
+                read: 100,
+                write: 20,
+            },
+        );
+        vm.cycle();
+
+        let imp = vm.get_memory()[0];
+        assert_eq!(vm.get_memory()[95], imp, "{:?}", mode);
+        assert_ne!(vm.get_memory()[15], imp, "{:?}", mode);
+    }
+}
+
+#[test]
+fn reads_are_folded() {
+    for mode in &MODES {
+        let mut vm = limited_vm(
+            create_program! {
+                MOV(I, 25, Direct, 1, Direct)
+                DAT(F, 0, Immediate, 0, Immediate)
+                DAT(F, 0, Immediate, 0, Immediate)
+                DAT(F, 0, Immediate, 0, Immediate)
+                DAT(F, 0, Immediate, 0, Immediate)
+                NOP(F, 1, Immediate, 2, Immediate)
+            },
+            *mode,
+            Limits {
+                read: 20,
+                write: 100,
+            },
+        );
+        vm.cycle();
+
+        // 25 is folded to 5
+        assert_eq!(
+            vm.get_memory()[1],
+            cmd! { NOP(F, 1, Immediate, 2, Immediate) },
+            "{:?}",
+            mode
+        );
+    }
+}
+
+#[test]
+fn indirect_addresses_are_folded() {
+    for mode in &MODES {
+        let mut vm = limited_vm(
+            create_program! {
+                MOV(I, 0, Direct, 1, IndirectB)
+                DAT(F, 0, Immediate, 14, Immediate)
+            },
+            *mode,
+            Limits {
+                read: 100,
+                write: 20,
+            },
+        );
+        vm.cycle();
+
+        // The pointer is 1 away and points a further 14 away, 15 is folded to -5
+        assert_eq!(vm.get_memory()[95], vm.get_memory()[0], "{:?}", mode);
+    }
+}
+
+#[test]
+fn pointer_decrements_are_folded() {
+    for mode in &MODES {
+        let mut vm = limited_vm(
+            create_program! { MOV(I, 0, Direct, 15, PreDecrementIndirectB) },
+            *mode,
+            Limits {
+                read: 100,
+                write: 20,
+            },
+        );
+        vm.cycle();
+
+        // The pointer at -5 is decremented to -1 so the imp is written to -5 - 1
+        assert_eq!(vm.get_memory()[95].b_reg, 99, "{:?}", mode);
+        assert_eq!(vm.get_memory()[94], vm.get_memory()[0], "{:?}", mode);
+    }
+}
+
+#[test]
+fn match_settings_limits() {
+    let settings = MatchSettings {
+        core_size: 800,
+        write_limit: Some(80),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        settings.limits(),
+        Ok(Limits {
+            read: 800,
+            write: 80
+        })
+    );
+}
+
+#[test]
+fn limit_larger_than_core() {
+    let settings = MatchSettings {
+        core_size: 800,
+        read_limit: Some(8000),
+        ..Default::default()
+    };
+    assert_eq!(settings.limits(), Err(SettingsError::ReadLimit(8000, 800)));
+
+    // A match can't be played with the limit either
+    assert_eq!(
+        run_match(&[imp()], &settings).err(),
+        Some(SettingsError::ReadLimit(8000, 800))
+    );
+
+    let settings = MatchSettings {
+        write_limit: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(settings.limits(), Err(SettingsError::WriteLimit(0, 8000)));
+}
@@ -0,0 +1,170 @@
+use darwin_lib::{
+    cmd, create_program, Actor, ExecutionMode, Instruction, Observer, VirtualMachine,
+};
+
+const MODES: [ExecutionMode; 2] = [ExecutionMode::Lazy, ExecutionMode::Icws94];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Fetch(usize),
+    Read(usize),
+    Write(usize, Instruction, Instruction),
+    Spawn(usize, usize),
+    Death(usize),
+}
+
+#[derive(Default)]
+struct Recorder(Vec<Event>);
+
+impl Observer for Recorder {
+    fn fetch(&mut self, actor: Actor, _: &Instruction) {
+        self.0.push(Event::Fetch(actor.pc));
+    }
+
+    fn read(&mut self, _: Actor, address: usize) {
+        self.0.push(Event::Read(address));
+    }
+
+    fn write(&mut self, _: Actor, address: usize, old: &Instruction, new: &Instruction) {
+        self.0.push(Event::Write(address, *old, *new));
+    }
+
+    fn spawn(&mut self, actor: Actor, new_pc: usize) {
+        self.0.push(Event::Spawn(actor.pc, new_pc));
+    }
+
+    fn death(&mut self, actor: Actor) {
+        self.0.push(Event::Death(actor.pc));
+    }
+}
+
+fn vm(program: Vec<Instruction>, mode: ExecutionMode) -> VirtualMachine {
+    let mut vm = VirtualMachine::new_simple(10, program);
+    vm.set_execution_mode(mode);
+    vm
+}
+
+#[test]
+fn imp_events() {
+    let imp = cmd! { MOV(I, 0, Direct, 1, Direct) };
+    let empty = cmd! { DAT(None, 0, Immediate, 0, Immediate) };
+
+    for mode in &MODES {
+        let mut vm = vm(vec![imp], *mode);
+        let mut recorder = Recorder::default();
+        vm.cycle_with_observer(&mut recorder);
+
+        // The value of the B target isn't used by MOV so it isn't a read
+        assert_eq!(
+            recorder.0,
+            vec![Event::Fetch(0), Event::Read(0), Event::Write(1, empty, imp)],
+            "{:?}",
+            mode
+        );
+    }
+}
+
+#[test]
+fn pointer_writes_are_reported() {
+    let program = create_program! {
+        MOV(I, 0, Direct, 1, PreDecrementIndirectB)
+        DAT(F, 0, Immediate, 2, Immediate)
+    };
+
+    for mode in &MODES {
+        let mut vm = vm(program.clone(), *mode);
+        let mut recorder = Recorder::default();
+        vm.cycle_with_observer(&mut recorder);
+
+        let writes: Vec<&Event> = recorder
+            .0
+            .iter()
+            .filter(|e| matches!(e, Event::Write(..)))
+            .collect();
+
+        assert_eq!(
+            writes,
+            vec![
+                &Event::Write(1, program[1], cmd! { DAT(F, 0, Immediate, 1, Immediate) }),
+                &Event::Write(
+                    2,
+                    cmd! { DAT(None, 0, Immediate, 0, Immediate) },
+                    program[0]
+                ),
+            ],
+            "{:?}",
+            mode
+        );
+        // The pointer is read before it is followed
+        assert!(recorder.0.contains(&Event::Read(1)), "{:?}", mode);
+    }
+}
+
+#[test]
+fn spawn_and_death() {
+    let program = create_program! {
+        SPL(B, 2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+        JMP(B, 0, Direct, 0, Immediate)
+    };
+
+    for mode in &MODES {
+        let mut vm = vm(program.clone(), *mode);
+        let mut recorder = Recorder::default();
+
+        for _ in 0..3 {
+            vm.cycle_with_observer(&mut recorder);
+        }
+
+        assert_eq!(
+            recorder.0,
+            vec![
+                Event::Fetch(0),
+                Event::Spawn(0, 2),
+                Event::Fetch(1),
+                Event::Death(1),
+                Event::Fetch(2),
+            ],
+            "{:?}",
+            mode
+        );
+    }
+}
+
+#[test]
+fn writes_reproduce_the_core() {
+    // A dwarf that bombs every 4th instruction
+    let dwarf = create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+
+    for mode in &MODES {
+        let mut observed = VirtualMachine::new_simple(100, dwarf.clone());
+        let mut plain = VirtualMachine::new_simple(100, dwarf.clone());
+        observed.set_execution_mode(*mode);
+        plain.set_execution_mode(*mode);
+
+        let mut core = observed.get_memory().to_vec();
+        let mut recorder = Recorder::default();
+
+        for _ in 0..200 {
+            observed.cycle_with_observer(&mut recorder);
+            plain.cycle();
+        }
+
+        // Observing doesn't change what happens
+        assert_eq!(observed.get_memory(), plain.get_memory(), "{:?}", mode);
+
+        for event in &recorder.0 {
+            if let Event::Write(address, old, new) = event {
+                assert_eq!(core[*address], *old, "{:?}", mode);
+                core[*address] = *new;
+            }
+        }
+
+        assert_eq!(core, observed.get_memory(), "{:?}", mode);
+    }
+}
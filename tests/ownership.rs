@@ -0,0 +1,113 @@
+use darwin_lib::{
+    create_program, Actor, ExecutionMode, Instruction, MatchSettings, Observer, VirtualMachine,
+};
+
+mod common;
+use common::imp;
+
+#[test]
+fn not_tracked_by_default() {
+    let vm = VirtualMachine::new_simple(10, imp());
+    assert!(vm.get_ownership().is_none());
+}
+
+#[test]
+fn writers_and_executors() {
+    let dwarf = create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        let settings = MatchSettings {
+            execution_mode: *mode,
+            ..Default::default()
+        };
+        let mut vm = VirtualMachine::new_battle(&[imp(), dwarf.clone()], &settings);
+        let imp_start = vm.get_users_pcs()[0][0];
+        let dwarf_start = vm.get_users_pcs()[1][0];
+
+        vm.track_ownership();
+        for _ in 0..60 {
+            vm.cycle();
+        }
+
+        let ownership = vm.get_ownership().unwrap();
+
+        // The imp has executed and written 30 consecutive cells
+        for i in 0..30 {
+            assert_eq!(ownership.executors()[imp_start + i], Some(0), "{:?}", mode);
+            assert_eq!(
+                ownership.writers()[imp_start + i + 1],
+                Some(0),
+                "{:?}",
+                mode
+            );
+        }
+
+        // The dwarf's ADD writes to its own DAT and the dwarf executes its first 3 cells
+        assert_eq!(ownership.writers()[dwarf_start + 3], Some(1), "{:?}", mode);
+        assert_eq!(ownership.executors()[dwarf_start + 3], None, "{:?}", mode);
+        assert_eq!(
+            ownership.executors()[dwarf_start + 2],
+            Some(1),
+            "{:?}",
+            mode
+        );
+
+        // 30 imp cells and the dwarf's pointer plus 10 bombs
+        assert_eq!(ownership.territory(2), vec![30, 11], "{:?}", mode);
+
+        // The imp executed its last instruction on the second last cycle
+        assert_eq!(
+            ownership.last_touched()[imp_start + 30],
+            Some(58),
+            "{:?}",
+            mode
+        );
+        assert_eq!(vm.get_cycle_count(), 60);
+    }
+}
+
+#[test]
+fn increments_and_decrements_are_writes() {
+    let program = create_program! {
+        JMP(B, 1, Direct, 2, PostIncrementIndirectB)
+        NOP(F, 1, PreDecrementIndirectA, 0, Immediate)
+    };
+
+    let mut vm = VirtualMachine::new_simple(10, program);
+    vm.track_ownership();
+    vm.cycle();
+    vm.cycle();
+
+    let ownership = vm.get_ownership().unwrap();
+    assert_eq!(ownership.writers()[2], Some(0));
+    assert_eq!(ownership.last_touched()[2], Some(1));
+    assert_eq!(ownership.writers()[0], None);
+}
+
+#[derive(Default)]
+struct CountWrites(usize);
+
+impl Observer for CountWrites {
+    fn write(&mut self, _: Actor, _: usize, _: &Instruction, _: &Instruction) {
+        self.0 += 1;
+    }
+}
+
+#[test]
+fn observers_still_receive_events() {
+    let mut vm = VirtualMachine::new_simple(10, imp());
+    vm.track_ownership();
+
+    let mut writes = CountWrites::default();
+    for _ in 0..5 {
+        vm.cycle_with_observer(&mut writes);
+    }
+
+    assert_eq!(writes.0, 5);
+    assert_eq!(vm.get_ownership().unwrap().territory(1), vec![5]);
+}
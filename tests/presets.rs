@@ -0,0 +1,66 @@
+use darwin_lib::{create_program, run_match, MatchSettings, Preset, SettingsError, WarriorError};
+
+mod common;
+use common::imp;
+
+#[test]
+fn preset_names_round_trip() {
+    for preset in Preset::ALL.iter() {
+        assert_eq!(Preset::from_name(preset.name()), Some(*preset));
+        assert_eq!(preset.to_string(), preset.name());
+    }
+}
+
+#[test]
+fn preset_settings() {
+    let tiny = MatchSettings::from_preset(Preset::Tiny);
+    assert_eq!(tiny.core_size, 800);
+    assert_eq!(tiny.max_processes, 800);
+    assert_eq!(tiny.max_cycles, 8000);
+    assert_eq!(tiny.max_length, 20);
+    assert_eq!(tiny.min_separation, 20);
+
+    let lp = MatchSettings::from_preset(Preset::LimitedProcess);
+    assert_eq!(lp.max_processes, 8);
+    assert_eq!(lp.max_length, 200);
+
+    let exp = MatchSettings::from_preset(Preset::Experimental);
+    assert_eq!(exp.core_size, 55440);
+    assert_eq!(exp.max_cycles, 500_000);
+}
+
+#[test]
+fn validate_warrior_length() {
+    let settings = MatchSettings::from_preset(Preset::Tiny);
+
+    assert_eq!(settings.validate_warrior(&imp().repeat(20)), Ok(()));
+    assert_eq!(
+        settings.validate_warrior(&imp().repeat(21)),
+        Err(WarriorError::TooLong(21, 20))
+    );
+    assert_eq!(settings.validate_warrior(&[]), Err(WarriorError::Empty));
+}
+
+#[test]
+fn matches_validate_warriors() {
+    let settings = MatchSettings::from_preset(Preset::Nano);
+    let ldp = create_program! { LDP(AB, 0, Immediate, 1, Direct) };
+
+    assert_eq!(
+        run_match(&[imp(), imp().repeat(50)], &settings),
+        Err(SettingsError::Warrior(1, WarriorError::TooLong(50, 5)))
+    );
+    assert_eq!(
+        settings.validate_match(&[vec![], imp()]),
+        Err(SettingsError::Warrior(0, WarriorError::Empty))
+    );
+
+    let settings = MatchSettings {
+        pspace_size: 0,
+        ..Default::default()
+    };
+    assert_eq!(
+        settings.validate_match(&[imp(), ldp]),
+        Err(SettingsError::Warrior(1, WarriorError::PSpaceDisabled))
+    );
+}
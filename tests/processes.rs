@@ -0,0 +1,123 @@
+use darwin_lib::{create_program, ExecutionMode, MatchSettings, ProcessTree, VirtualMachine};
+
+mod common;
+use common::imp;
+
+fn assert_matches_queues(tree: &ProcessTree, vm: &VirtualMachine) {
+    for (user, queue) in vm.get_users_pcs().iter().enumerate() {
+        assert_eq!(tree.get_queue(user).len(), queue.len());
+        assert!(tree
+            .get_queue(user)
+            .iter()
+            .all(|id| tree.get_process(*id).user == user
+                && tree.get_process(*id).death_cycle.is_none()));
+    }
+}
+
+#[test]
+fn births_and_deaths() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        let program = create_program! {
+            SPL(B, 2, Direct, 0, Immediate)
+            DAT(F, 0, Immediate, 0, Immediate)
+            DIV(AB, 0, Immediate, 1, Direct)
+        };
+
+        let mut vm = VirtualMachine::new_simple(10, program);
+        vm.set_execution_mode(*mode);
+        vm.track_processes();
+        for _ in 0..3 {
+            vm.cycle();
+        }
+
+        let tree = vm.get_processes().unwrap();
+        assert_eq!(tree.processes().len(), 2);
+        assert_eq!(tree.get_process(1).parent, Some(0));
+        assert_eq!(tree.get_process(1).birth_cycle, 0);
+        assert_eq!(tree.get_process(0).death_cycle, Some(1));
+        assert_eq!(tree.get_process(1).death_cycle, Some(2));
+        assert!(tree.get_queue(0).is_empty());
+    }
+}
+
+#[test]
+fn process_trees_of_a_battle() {
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        JMP(B, -1, Direct, 0, Immediate)
+    };
+
+    let mut vm = VirtualMachine::new_battle(
+        &[spl, imp()],
+        &MatchSettings {
+            max_processes: 32,
+            ..Default::default()
+        },
+    );
+    vm.track_processes();
+    for _ in 0..500 {
+        vm.cycle();
+    }
+
+    let tree = vm.get_processes().unwrap();
+    assert_matches_queues(tree, &vm);
+
+    // Every process of the first warrior descends from its first process
+    let root = tree.roots(0)[0];
+    assert_eq!(tree.roots(0), vec![root]);
+    assert_eq!(tree.descendants(root).len(), 31);
+    assert!(tree
+        .descendants(root)
+        .iter()
+        .all(|id| tree.generation(*id) > 0));
+
+    // The imp never splits
+    assert_eq!(tree.roots(1).len(), 1);
+    assert!(tree.descendants(tree.roots(1)[0]).is_empty());
+}
+
+#[test]
+fn stepping_back_undoes_the_tree() {
+    let bomber = create_program! {
+        ADD(AB, 3, Immediate, 3, Direct)
+        MOV(I, 3, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        MOV(I, 0, Direct, 1, PostIncrementIndirectA)
+    };
+
+    let mut vm = VirtualMachine::new_battle(
+        &[bomber, spl],
+        &MatchSettings {
+            core_size: 400,
+            min_separation: 50,
+            max_processes: 16,
+            ..Default::default()
+        },
+    );
+    vm.record_history(1000);
+    vm.track_processes();
+
+    let mut trees = Vec::new();
+    for _ in 0..400 {
+        trees.push(vm.get_processes().unwrap().clone());
+        vm.cycle();
+    }
+
+    let deaths = vm
+        .get_processes()
+        .unwrap()
+        .processes()
+        .iter()
+        .filter(|process| process.death_cycle.is_some())
+        .count();
+    assert!(deaths > 0);
+
+    for tree in trees.iter().rev() {
+        assert!(vm.step_back());
+        assert_eq!(vm.get_processes().unwrap(), tree);
+    }
+}
@@ -0,0 +1,241 @@
+use darwin_lib::{
+    cmd, compile_program, create_program, parse_program, run_match, run_match_with_pspace,
+    run_round_with_pspace, run_tournament, ExecutionMode, MatchSettings, PSpace, ParseError,
+    Preset, SettingsError, VirtualMachine, WarriorError,
+};
+
+mod common;
+use common::imp;
+
+#[test]
+fn parse_pspace_opcodes() {
+    assert_eq!(
+        parse_program("LDP.AB #0 1\nSTP #3 #5\nLDP 1 2").unwrap(),
+        create_program! {
+            LDP(AB, 0, Immediate, 1, Direct)
+            STP(AB, 3, Immediate, 5, Immediate)
+            LDP(B, 1, Direct, 2, Direct)
+        }
+    );
+}
+
+#[test]
+fn location_zero_is_private() {
+    let mut pspace = PSpace::with_pins(8, &[Some(1), Some(1)]);
+    assert_eq!(pspace.load(0, 0), -1, "Location 0 starts as -1");
+
+    pspace.set_result(0, 1);
+    pspace.store(1, 8, 0);
+    pspace.store(1, 9, 5);
+
+    assert_eq!(pspace.load(0, 0), 1);
+    assert_eq!(pspace.load(1, 0), 0);
+    // Other locations are shared, and indices wrap around the size of the P-space
+    assert_eq!(pspace.load(0, 1), 5);
+}
+
+#[test]
+fn store_and_load() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        let mut vm = VirtualMachine::new_simple(
+            20,
+            create_program! {
+                STP(AB, 7, Immediate, 3, Immediate)
+                LDP(AB, 3, Immediate, 1, Direct)
+            },
+        );
+        vm.set_execution_mode(*mode);
+
+        vm.cycle();
+        assert_eq!(vm.get_pspace().load(0, 3), 7);
+
+        vm.cycle();
+        assert_eq!(
+            vm.get_memory()[2],
+            cmd! { DAT(None, 0, Immediate, 7, Immediate) }
+        );
+    }
+}
+
+#[test]
+fn load_wraps_into_core() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        // Location 0 is -1 before the first round, which is CORESIZE - 1 in the core
+        let mut vm =
+            VirtualMachine::new_simple(20, create_program! { LDP(A, 0, Immediate, 1, Direct) });
+        vm.set_execution_mode(*mode);
+
+        vm.cycle();
+        assert_eq!(vm.get_memory()[1].a_reg, 19);
+    }
+}
+
+#[test]
+fn results_are_kept_between_rounds() {
+    // Stores the result of the previous round in location 1 + round
+    let recorder = create_program! {
+        // Increment the round counter stored in location 1
+        LDP(AB, 1, Immediate, 7, Direct)
+        ADD(AB, 1, Immediate, 6, Direct)
+        STP(B, 5, Direct, 1, Immediate)
+        // Store the previous result in the location after the counter
+        LDP(A, 0, Immediate, 4, Direct)
+        ADD(AB, 1, Immediate, 3, Direct)
+        STP(AB, 2, Direct, 2, Direct)
+        JMP(B, 0, Direct)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    // Loops 10 times then dies
+    let countdown = create_program! {
+        DJN(B, 0, Direct, 10, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+
+    let settings = MatchSettings {
+        rounds: 3,
+        max_cycles: 100,
+        ..Default::default()
+    };
+    let mut pspace = PSpace::new(settings.pspace_size, 2);
+
+    let result = run_match_with_pspace(&[recorder, countdown], &settings, &mut pspace).unwrap();
+
+    assert_eq!(result.scores[0].wins, 3);
+    assert_eq!(
+        pspace.load(0, 1),
+        3,
+        "The recorder should have played 3 rounds"
+    );
+    // -1 (stored as CORESIZE - 1) before the first round, then a win in each round
+    assert_eq!(pspace.load(0, 2), 7999);
+    assert_eq!(pspace.load(0, 3), 1);
+    assert_eq!(pspace.load(0, 4), 1);
+    // The last result is kept in location 0 of each warrior
+    assert_eq!(pspace.load(0, 0), 1);
+    assert_eq!(pspace.load(1, 0), 0);
+}
+
+#[test]
+fn shared_pspace() {
+    // Stores 1 in location 10 and loops forever
+    let writer = create_program! {
+        STP(AB, 1, Immediate, 10, Immediate)
+        JMP(B, 0, Direct)
+    };
+    // Dies unless location 10 has been set
+    let reader = create_program! {
+        LDP(AB, 10, Immediate, 1, Direct)
+        JMN(B, 2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+        JMP(B, 0, Direct)
+    };
+
+    let settings = MatchSettings {
+        rounds: 4,
+        max_cycles: 50,
+        ..Default::default()
+    };
+
+    // Without a shared P-space the reader always dies
+    let mut pspace = PSpace::new(settings.pspace_size, 2);
+    let result =
+        run_match_with_pspace(&[writer.clone(), reader.clone()], &settings, &mut pspace).unwrap();
+    assert_eq!(result.scores[1].losses, 4);
+
+    // The writer moves first in the first round, so the reader always sees the stored value
+    let mut pspace = PSpace::with_pins(settings.pspace_size, &[Some(3), Some(3)]);
+    let result =
+        run_match_with_pspace(&[writer.clone(), reader.clone()], &settings, &mut pspace).unwrap();
+    assert_eq!(result.scores[1].ties, 4);
+
+    // Matches and tournaments share the P-space of warriors with the same PIN
+    let settings = MatchSettings {
+        pins: vec![Some(3), Some(3)],
+        ..settings
+    };
+    let result = run_match(&[writer.clone(), reader.clone()], &settings).unwrap();
+    assert_eq!(result.scores[1].ties, 4);
+
+    let result = run_tournament(&[writer, reader], &settings).unwrap();
+    assert_eq!(result.totals[1].ties, 4);
+}
+
+#[test]
+fn pin_pseudo_op() {
+    let compilation = compile_program("; shares P-space\nPIN 3\nSTP #1, #10\nJMP 0");
+    assert_eq!(compilation.pin, Some(3));
+    assert_eq!(compilation.lines.len(), 2);
+    assert_eq!(compile_program("JMP 0").pin, None);
+
+    assert_eq!(
+        compile_program("PIN\nPIN x\nPIN 1 2").errors,
+        vec![
+            ParseError::NotEnoughArgumets(1),
+            ParseError::UnknownValue((2, "x")),
+            ParseError::UnexpectedArgument(3)
+        ]
+    );
+}
+
+#[test]
+fn pspace_must_fit_the_match() {
+    let programs = [imp(), imp()];
+    let settings = MatchSettings::default();
+
+    let mut pspace = PSpace::new(settings.pspace_size, 1);
+    assert_eq!(
+        run_match_with_pspace(&programs, &settings, &mut pspace),
+        Err(SettingsError::PSpaceWarriors(1, 2))
+    );
+    assert_eq!(
+        run_round_with_pspace(&programs, &settings, 0, 0, &mut pspace),
+        Err(SettingsError::PSpaceWarriors(1, 2))
+    );
+
+    let mut pspace = PSpace::new(settings.pspace_size + 1, 2);
+    assert_eq!(
+        run_match_with_pspace(&programs, &settings, &mut pspace),
+        Err(SettingsError::PSpaceSize(
+            settings.pspace_size + 1,
+            settings.pspace_size
+        ))
+    );
+
+    let mut pspace = settings.pspace(2);
+    assert!(run_round_with_pspace(&programs, &settings, 0, 0, &mut pspace).is_ok());
+}
+
+#[test]
+fn pspace_match_is_deterministic() {
+    let program = create_program! {
+        STP(AB, 1, Immediate, 1, Immediate)
+        MOV(I, 0, Direct, 1, Direct)
+    };
+
+    let settings = MatchSettings {
+        rounds: 4,
+        max_cycles: 100,
+        seed: Some(1),
+        threads: 4,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        run_match(&[program.clone(), program.clone()], &settings).unwrap(),
+        run_match(&[program.clone(), program], &settings).unwrap()
+    );
+}
+
+#[test]
+fn no_pspace_preset_rejects_pspace_warriors() {
+    let program = create_program! { STP(AB, 1, Immediate, 1, Immediate) };
+
+    assert_eq!(
+        MatchSettings::from_preset(Preset::NoPSpace94).validate_warrior(&program),
+        Err(WarriorError::PSpaceDisabled)
+    );
+    assert_eq!(
+        MatchSettings::from_preset(Preset::Draft94).validate_warrior(&program),
+        Ok(())
+    );
+}
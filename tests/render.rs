@@ -0,0 +1,76 @@
+use darwin_lib::{cell_char, create_program, CoreRenderer, VirtualMachine};
+
+mod common;
+use common::imp;
+
+fn plain(width: usize, height: Option<usize>) -> CoreRenderer {
+    CoreRenderer {
+        width,
+        height,
+        colour: false,
+    }
+}
+
+#[test]
+fn cells_show_their_class() {
+    let program = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        ADD(AB, 4, Immediate, 3, Direct)
+        JMZ(B, -2, Direct, 0, Immediate)
+        SEQ(I, 1, Direct, 2, Direct)
+        DAT(F, 0, Immediate, 5, Immediate)
+        NOP(F, 0, Direct, 0, Direct)
+        STP(AB, 0, Immediate, 1, Immediate)
+    };
+    let characters: String = program.iter().map(cell_char).collect();
+    assert_eq!(characters, "sajcdnp");
+
+    let vm = VirtualMachine::new_simple(16, program);
+    let lines: Vec<String> = plain(8, None)
+        .render_to_string(&vm)
+        .lines()
+        .map(String::from)
+        .collect();
+    assert_eq!(lines, vec!["Sajcdnp.", "........", "cycle 0  0:1"]);
+}
+
+#[test]
+fn large_cores_are_scaled_down() {
+    let mut vm = VirtualMachine::new_simple(8000, imp());
+    for _ in 0..10 {
+        vm.cycle();
+    }
+
+    let renderer = plain(40, Some(10));
+    assert_eq!(renderer.cells_per_char(8000), 20);
+
+    let output = renderer.render_to_string(&vm);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 11);
+    assert!(lines[..10].iter().all(|line| line.len() == 40));
+    // The first character covers the imp's trail and its process
+    assert!(lines[0].starts_with("M."));
+    assert_eq!(lines[10], "cycle 10  0:1");
+
+    // Small cores still get one cell per character
+    assert_eq!(renderer.cells_per_char(100), 1);
+}
+
+#[test]
+fn colour_is_optional() {
+    let mut vm = VirtualMachine::new_simple(20, imp());
+    vm.track_ownership();
+    vm.cycle();
+
+    let coloured = CoreRenderer {
+        width: 10,
+        ..Default::default()
+    }
+    .render_to_string(&vm);
+    assert!(coloured.contains("\x1b[91m\x1b[7mm"));
+    assert!(coloured.contains("\x1b[0m"));
+
+    let uncoloured = plain(10, None).render_to_string(&vm);
+    assert!(!uncoloured.contains('\x1b'));
+    assert!(uncoloured.starts_with("mM"));
+}
@@ -0,0 +1,138 @@
+use darwin_lib::{
+    create_program, run_round, ExecutionMode, Handicap, Instruction, MatchSettings, Replay,
+    ReplayError, ReplayPlayer,
+};
+
+fn warriors() -> Vec<Vec<Instruction>> {
+    let dwarf = create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        MOV(I, 0, Direct, 1, PostIncrementIndirectA)
+    };
+
+    vec![dwarf, spl]
+}
+
+fn settings() -> MatchSettings {
+    MatchSettings {
+        execution_mode: ExecutionMode::Icws94,
+        core_size: 800,
+        min_separation: 50,
+        max_cycles: 5000,
+        read_limit: Some(400),
+        handicaps: vec![Handicap {
+            max_processes: Some(32),
+            speed: 2,
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn replays_match_the_round() {
+    let replay = Replay::record(&warriors(), &settings(), 7, 50);
+    let round = run_round(&warriors(), &settings(), 0, 7);
+
+    assert_eq!(replay.cycles, round.cycles);
+    assert_eq!(replay.survivors, round.survivors);
+    assert_eq!(replay.hashes.len(), replay.instructions / 50);
+
+    let loaded = Replay::from_bytes(&replay.to_bytes()).unwrap();
+    assert_eq!(loaded.settings.handicaps, settings().handicaps);
+    assert_eq!(loaded.settings.read_limit, Some(400));
+
+    let mut player = ReplayPlayer::new(loaded);
+    assert_eq!(player.play(), Ok(()));
+    assert!(player.is_finished());
+    assert_eq!(player.get_vm().get_cycle_count(), replay.instructions);
+    assert_eq!(player.get_vm().state_hash(), replay.final_hash);
+    assert_eq!(player.step(), Ok(false));
+}
+
+#[test]
+fn divergence_is_detected() {
+    let replay = Replay::record(&warriors(), &settings(), 3, 20);
+
+    // A different version of a warrior runs differently from the first cycle
+    let mut changed = replay.clone();
+    changed.warriors[0][0].a_reg = 5;
+    let divergence = ReplayPlayer::new(changed).play().unwrap_err();
+    assert_eq!(divergence.cycle, 20);
+    assert_eq!(divergence.expected, replay.hashes[0]);
+
+    let mut changed = replay.clone();
+    changed.hashes[3] ^= 1;
+    let mut player = ReplayPlayer::new(changed);
+    assert_eq!(player.play().unwrap_err().cycle, 80);
+
+    let mut changed = replay;
+    changed.final_hash ^= 1;
+    assert!(ReplayPlayer::new(changed).play().is_err());
+}
+
+#[test]
+fn replay_files() {
+    let replay = Replay::record(&warriors(), &settings(), 11, 100);
+
+    let path = std::env::temp_dir().join(format!("darwin_replay_{}.bin", std::process::id()));
+    replay.save_to_file(&path).unwrap();
+    let loaded = Replay::load_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.warriors, replay.warriors);
+    assert_eq!(loaded.placements, replay.placements);
+    assert_eq!(loaded.hashes, replay.hashes);
+
+    assert!(matches!(
+        Replay::from_bytes(b"DWVM"),
+        Err(ReplayError::NotAReplay)
+    ));
+
+    let mut bytes = replay.to_bytes();
+    bytes[4] = 99;
+    assert!(matches!(
+        Replay::from_bytes(&bytes),
+        Err(ReplayError::UnsupportedVersion(99))
+    ));
+
+    // Replays that the player can't set up are rejected when they are loaded
+    let mut corrupt = replay.clone();
+    corrupt.settings.max_processes = 0;
+    assert!(matches!(
+        Replay::from_bytes(&corrupt.to_bytes()),
+        Err(ReplayError::Invalid(_))
+    ));
+
+    let mut corrupt = replay;
+    corrupt.warriors[1].clear();
+    assert!(matches!(
+        Replay::from_bytes(&corrupt.to_bytes()),
+        Err(ReplayError::Invalid(_))
+    ));
+}
+
+#[test]
+fn hashes_stop_when_every_warrior_is_dead() {
+    // Dies after executing 11 instructions, part way through its fourth turn
+    let countdown = create_program! {
+        DJN(B, 0, Direct, 10, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    let settings = MatchSettings {
+        handicaps: vec![Handicap {
+            max_processes: None,
+            speed: 3,
+        }],
+        ..Default::default()
+    };
+
+    let replay = Replay::record(&[countdown], &settings, 1, 1);
+    assert_eq!(replay.instructions, 11);
+    assert_eq!(replay.hashes.len(), 11);
+    assert_eq!(ReplayPlayer::new(replay).play(), Ok(()));
+}
@@ -0,0 +1,121 @@
+use darwin_lib::{
+    create_program, ExecutionMode, MatchSettings, Snapshot, SnapshotError, VirtualMachine,
+};
+
+mod common;
+use common::imp;
+
+fn battle(mode: ExecutionMode) -> VirtualMachine {
+    let dwarf = create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+
+    let mut vm = VirtualMachine::new_battle(
+        &[imp(), dwarf],
+        &MatchSettings {
+            execution_mode: mode,
+            ..Default::default()
+        },
+    );
+    vm.track_ownership();
+    vm.track_statistics();
+    vm
+}
+
+fn run(vm: &mut VirtualMachine, cycles: usize) {
+    for _ in 0..cycles {
+        vm.cycle();
+    }
+}
+
+fn assert_same_state(a: &VirtualMachine, b: &VirtualMachine) {
+    assert_eq!(a.get_memory(), b.get_memory());
+    assert_eq!(a.get_users_pcs(), b.get_users_pcs());
+    assert_eq!(a.get_cur_user(), b.get_cur_user());
+    assert_eq!(a.get_cycle_count(), b.get_cycle_count());
+    assert_eq!(a.get_execution_mode(), b.get_execution_mode());
+    assert_eq!(a.get_limits(), b.get_limits());
+    assert_eq!(a.get_pspace(), b.get_pspace());
+    assert_eq!(a.get_ownership(), b.get_ownership());
+    assert_eq!(a.get_statistics(), b.get_statistics());
+}
+
+#[test]
+fn restore_forks_a_battle() {
+    let mut vm = battle(ExecutionMode::Lazy);
+    run(&mut vm, 50);
+
+    let snapshot = vm.snapshot();
+    run(&mut vm, 100);
+    let first = vm.snapshot();
+
+    vm.restore(&snapshot);
+    assert_eq!(vm.get_cycle_count(), 50);
+
+    run(&mut vm, 100);
+    assert_same_state(&vm, first.get_vm());
+}
+
+#[test]
+fn bytes_round_trip() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        let mut vm = battle(*mode);
+        vm.get_pspace_mut().store(1, 3, -42);
+        run(&mut vm, 77);
+
+        let bytes = vm.snapshot().to_bytes();
+        let mut restored = VirtualMachine::from_snapshot(Snapshot::from_bytes(&bytes).unwrap());
+        assert_same_state(&vm, &restored);
+
+        // The restored battle carries on exactly like the original
+        run(&mut vm, 200);
+        run(&mut restored, 200);
+        assert_same_state(&vm, &restored);
+    }
+}
+
+#[test]
+fn file_round_trip() {
+    let mut vm = battle(ExecutionMode::Icws94);
+    run(&mut vm, 10);
+
+    let path = std::env::temp_dir().join(format!("darwin_snapshot_{}.bin", std::process::id()));
+    vm.snapshot().save_to_file(&path).unwrap();
+    let snapshot = Snapshot::load_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_same_state(&vm, snapshot.get_vm());
+}
+
+#[test]
+fn snapshots_are_compact() {
+    let vm = VirtualMachine::new_simple(8000, imp());
+
+    // An empty cell takes 6 bytes
+    assert!(vm.snapshot().to_bytes().len() < 8000 * 7);
+}
+
+#[test]
+fn invalid_snapshots() {
+    let bytes = battle(ExecutionMode::Lazy).snapshot().to_bytes();
+
+    assert!(matches!(
+        Snapshot::from_bytes(b"not a snapshot"),
+        Err(SnapshotError::NotASnapshot)
+    ));
+
+    let mut future = bytes.clone();
+    future[4] = 99;
+    assert!(matches!(
+        Snapshot::from_bytes(&future),
+        Err(SnapshotError::UnsupportedVersion(99))
+    ));
+
+    assert!(matches!(
+        Snapshot::from_bytes(&bytes[..bytes.len() / 2]),
+        Err(SnapshotError::Io(_))
+    ));
+}
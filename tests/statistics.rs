@@ -0,0 +1,131 @@
+use darwin_lib::{
+    create_program, run_match, ExecutionMode, MatchSettings, OpCode, VirtualMachine,
+    WarriorStatistics,
+};
+
+mod common;
+use common::{dat, imp};
+
+#[test]
+fn dwarf_statistics() {
+    let dwarf = create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+
+    let mut vm = VirtualMachine::new_simple(100, dwarf);
+    vm.track_statistics();
+    for _ in 0..30 {
+        vm.cycle();
+    }
+
+    let statistics = &vm.get_statistics().unwrap().warriors()[0];
+    assert_eq!(statistics.instructions, 30);
+    assert_eq!(statistics.op_count(OpCode::ADD), 10);
+    assert_eq!(statistics.op_count(OpCode::MOV), 10);
+    assert_eq!(statistics.op_count(OpCode::JMP), 10);
+    assert_eq!(statistics.op_count(OpCode::DAT), 0);
+    // The bomb pointer and the 10 cells that were bombed
+    assert_eq!(statistics.cells_written, 11);
+    assert_eq!(statistics.peak_processes, 1);
+    assert_eq!(statistics.final_processes, 1);
+    assert!(statistics.death_cycles.is_empty());
+}
+
+#[test]
+fn causes_of_death() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        let program = create_program! {
+            SPL(B, 2, Direct, 0, Immediate)
+            DAT(F, 0, Immediate, 0, Immediate)
+            DIV(AB, 0, Immediate, 1, Direct)
+        };
+
+        let mut vm = VirtualMachine::new_simple(10, program);
+        vm.set_execution_mode(*mode);
+        vm.track_statistics();
+        for _ in 0..3 {
+            vm.cycle();
+        }
+
+        let statistics = &vm.get_statistics().unwrap().warriors()[0];
+        assert_eq!(statistics.peak_processes, 2);
+        assert_eq!(statistics.final_processes, 0);
+        assert_eq!(statistics.dat_deaths, 1);
+        assert_eq!(statistics.division_deaths, 1);
+        assert_eq!(statistics.death_cycles, vec![1, 2]);
+    }
+}
+
+#[test]
+fn statistics_in_match_results() {
+    let programs = [imp(), dat()];
+
+    let result = run_match(
+        &programs,
+        &MatchSettings {
+            rounds: 2,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(result.rounds.iter().all(|round| round.statistics.is_none()));
+
+    let result = run_match(
+        &programs,
+        &MatchSettings {
+            rounds: 2,
+            statistics: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    for round in &result.rounds {
+        // The statistics are indexed by warrior whichever warrior moved first
+        let statistics = round.statistics.as_ref().unwrap();
+        assert_eq!(
+            statistics[0].op_count(OpCode::MOV),
+            statistics[0].instructions
+        );
+        assert_eq!(statistics[0].final_processes, 1);
+        assert_eq!(statistics[1].instructions, 1);
+        assert_eq!(statistics[1].dat_deaths, 1);
+    }
+}
+
+#[test]
+fn add_statistics() {
+    let mut a = WarriorStatistics::new(1);
+    a.instructions = 5;
+    a.op_counts[OpCode::SPL as usize] = 2;
+    a.peak_processes = 3;
+    a.final_processes = 2;
+    a.death_cycles = vec![8];
+    a.process_counts = vec![(0, 1), (2, 2), (4, 3), (8, 2)];
+    a.last_cycle = 10;
+
+    let mut b = WarriorStatistics::new(1);
+    b.instructions = 2;
+    b.op_counts[OpCode::SPL as usize] = 1;
+    b.peak_processes = 2;
+    b.final_processes = 0;
+    b.death_cycles = vec![2, 6];
+    b.process_counts = vec![(0, 1), (1, 2), (2, 1), (6, 0)];
+    b.last_cycle = 6;
+
+    a.add(&b);
+    assert_eq!(a.instructions, 7);
+    assert_eq!(a.op_count(OpCode::SPL), 3);
+    assert_eq!(a.peak_processes, 3);
+    assert_eq!(a.final_processes, 2);
+    assert_eq!(a.death_cycles, vec![2, 6, 8]);
+    // Cycle 2 is the same total as cycle 1, and cycles 6 and 8 are both 3 + 0 - 1
+    assert_eq!(
+        a.process_counts,
+        vec![(0, 2), (1, 3), (4, 4), (6, 3), (8, 2)]
+    );
+    assert_eq!(a.last_cycle, 10);
+}
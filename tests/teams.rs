@@ -0,0 +1,101 @@
+use darwin_lib::{run_match, run_team_battle, MatchSettings, SettingsError, VirtualMachine};
+
+mod common;
+use common::{dat, imp};
+
+#[test]
+fn team_battles_end_when_one_team_is_left() {
+    let programs = [imp(), imp(), dat()];
+
+    let mut vm = VirtualMachine::new_battle(&programs, &MatchSettings::default());
+    assert_eq!(run_team_battle(&mut vm, &[0, 0, 1], 1000).unwrap(), 1);
+
+    // Teammates don't fight each other
+    let mut vm = VirtualMachine::new_battle(&programs, &MatchSettings::default());
+    assert_eq!(run_team_battle(&mut vm, &[0, 1, 1], 1000).unwrap(), 1000);
+
+    // A single team runs until it is killed
+    let mut vm = VirtualMachine::new_battle(&[dat(), dat()], &MatchSettings::default());
+    assert_eq!(run_team_battle(&mut vm, &[3, 3], 1000).unwrap(), 1);
+
+    // Every warrior needs a team
+    let mut vm = VirtualMachine::new_battle(&programs, &MatchSettings::default());
+    assert_eq!(
+        run_team_battle(&mut vm, &[0, 1], 1000),
+        Err(SettingsError::Teams(2, 3))
+    );
+}
+
+#[test]
+fn team_statistics() {
+    let settings = MatchSettings {
+        rounds: 3,
+        max_cycles: 1000,
+        teams: vec![5, 2, 5, 2],
+        statistics: true,
+        ..Default::default()
+    };
+    let result = run_match(&[imp(), dat(), dat(), dat()], &settings).unwrap();
+    let teams = result.team_scores();
+
+    // Both DATs of team 2 die on their first instruction in every round
+    let statistics = teams[0].statistics.as_ref().unwrap();
+    assert_eq!(statistics.instructions, 6);
+    assert_eq!(statistics.dat_deaths, 6);
+    assert_eq!(statistics.final_processes, 0);
+
+    let statistics = teams[1].statistics.as_ref().unwrap();
+    assert_eq!(statistics.dat_deaths, 3);
+    assert_eq!(statistics.final_processes, 3);
+
+    // Statistics are only added up if they were collected
+    let settings = MatchSettings {
+        statistics: false,
+        ..settings
+    };
+    let result = run_match(&[imp(), dat(), dat(), dat()], &settings).unwrap();
+    assert!(result.team_scores()[0].statistics.is_none());
+}
+
+#[test]
+fn a_team_wins_if_any_member_survives() {
+    let settings = MatchSettings {
+        rounds: 3,
+        max_cycles: 1000,
+        teams: vec![5, 2, 5, 2],
+        ..Default::default()
+    };
+    let result = run_match(&[imp(), dat(), dat(), dat()], &settings).unwrap();
+    assert_eq!(result.teams, vec![5, 2, 5, 2]);
+
+    let teams = result.team_scores();
+    assert_eq!(teams.len(), 2);
+    assert_eq!((teams[0].team, &teams[0].warriors), (2, &vec![1, 3]));
+    assert_eq!((teams[1].team, &teams[1].warriors), (5, &vec![0, 2]));
+
+    assert_eq!(teams[0].score.losses, 3);
+    assert_eq!(teams[1].score.wins, 3);
+    assert_eq!(teams[1].score.score(), 9);
+    assert_eq!(teams[1].survivors, 3);
+
+    // The warrior that carried the team is scored on its own
+    assert_eq!(result.scores[0].wins, 3);
+    assert_eq!(result.scores[2].losses, 3);
+}
+
+#[test]
+fn melee_scores() {
+    let settings = MatchSettings {
+        rounds: 2,
+        max_cycles: 1000,
+        ..Default::default()
+    };
+
+    // Two survivors share the 3 points of a two warrior match
+    let result = run_match(&[imp(), imp()], &settings).unwrap();
+    assert_eq!(result.melee_scores(), vec![3.0, 3.0]);
+
+    // A lone survivor of four warriors gets all 15 points
+    let result = run_match(&[imp(), dat(), dat(), dat()], &settings).unwrap();
+    assert_eq!(result.melee_scores(), vec![30.0, 0.0, 0.0, 0.0]);
+}
@@ -0,0 +1,70 @@
+use darwin_lib::{create_program, run_tournament, Instruction, MatchSettings};
+
+mod common;
+use common::imp;
+
+fn warriors() -> Vec<Vec<Instruction>> {
+    vec![
+        // Imp
+        imp(),
+        // Suicide
+        create_program! { DAT(None, 0, Immediate, 0, Immediate) },
+        // Stays alive forever without doing anything
+        create_program! { JMP(None, 0, Direct) },
+    ]
+}
+
+fn settings() -> MatchSettings {
+    MatchSettings {
+        core_size: 400,
+        min_separation: 20,
+        max_cycles: 200,
+        rounds: 2,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn every_pair_plays() {
+    let result = run_tournament(&warriors(), &settings()).unwrap();
+
+    let groups: Vec<Vec<usize>> = result.matches.iter().map(|m| m.warriors.clone()).collect();
+    assert_eq!(groups, vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+}
+
+#[test]
+fn score_matrix_and_ranking() {
+    let result = run_tournament(&warriors(), &settings()).unwrap();
+
+    // The DAT always loses, the imp and the JMP 0 never meet within 200 cycles so they tie
+    assert_eq!(
+        result.score_matrix,
+        vec![vec![0, 6, 2], vec![0, 0, 0], vec![2, 6, 0]]
+    );
+
+    assert_eq!(result.totals[0].wins, 2);
+    assert_eq!(result.totals[0].ties, 2);
+    assert_eq!(result.totals[1].losses, 4);
+
+    assert_eq!(result.ranking(), vec![0, 2, 1]);
+}
+
+#[test]
+fn threads_do_not_change_results() {
+    let settings = MatchSettings {
+        seed: Some(3),
+        ..settings()
+    };
+
+    let single = run_tournament(&warriors(), &settings).unwrap();
+    let multi = run_tournament(
+        &warriors(),
+        &MatchSettings {
+            threads: 3,
+            ..settings
+        },
+    )
+    .unwrap();
+
+    assert_eq!(single, multi);
+}
@@ -0,0 +1,129 @@
+use darwin_lib::{
+    create_program, ExecutionMode, MatchSettings, TraceError, TraceFormat, TraceReader,
+    TraceRecord, TraceRecorder, VirtualMachine,
+};
+
+fn battle(mode: ExecutionMode) -> VirtualMachine {
+    let dwarf = create_program! {
+        ADD(AB, 4, Immediate, 3, Direct)
+        MOV(I, 2, Direct, 2, IndirectB)
+        JMP(B, -2, Direct, 0, Immediate)
+        DAT(F, 0, Immediate, 0, Immediate)
+    };
+    let spl = create_program! {
+        SPL(B, 0, Direct, 0, Immediate)
+        MOV(I, 0, Direct, 1, PostIncrementIndirectA)
+    };
+
+    // Fixed placements keep the traces the same on every run
+    let mut vm = VirtualMachine::new_battle_at(
+        &[dwarf, spl],
+        &MatchSettings {
+            execution_mode: mode,
+            core_size: 400,
+            min_separation: 50,
+            max_processes: 16,
+            ..Default::default()
+        },
+        &[0, 201],
+    );
+    vm.track_processes();
+    vm
+}
+
+#[test]
+fn traces_round_trip() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        for format in &[TraceFormat::Text, TraceFormat::Binary] {
+            let mut vm = battle(*mode);
+            let mut recorder = TraceRecorder::new(Vec::new(), *format).unwrap();
+
+            let records: Vec<TraceRecord> = (0..300)
+                .map(|_| recorder.cycle(&mut vm).unwrap().unwrap())
+                .collect();
+            let trace = recorder.into_inner().unwrap();
+
+            let reader = TraceReader::new(&trace[..]).unwrap();
+            assert_eq!(reader.get_format(), *format);
+
+            let read: Vec<TraceRecord> = reader.map(Result::unwrap).collect();
+            assert_eq!(read, records);
+        }
+    }
+}
+
+#[test]
+fn binary_traces_are_smaller() {
+    let size = |format| {
+        let mut vm = battle(ExecutionMode::Lazy);
+        let mut recorder = TraceRecorder::new(Vec::new(), format).unwrap();
+        recorder.cycles(&mut vm, 100).unwrap();
+        recorder.into_inner().unwrap().len()
+    };
+
+    // Most records write a cell, which takes up most of a binary record
+    assert!(size(TraceFormat::Binary) * 3 < size(TraceFormat::Text) * 2);
+}
+
+#[test]
+fn records_resolve_operands() {
+    for mode in &[ExecutionMode::Lazy, ExecutionMode::Icws94] {
+        let dwarf = create_program! {
+            ADD(AB, 4, Immediate, 3, Direct)
+            MOV(I, 2, Direct, 2, IndirectB)
+            JMP(B, -2, Direct, 0, Immediate)
+            DAT(F, 0, Immediate, 0, Immediate)
+        };
+        let mut vm = VirtualMachine::new_simple(100, dwarf);
+        vm.set_execution_mode(*mode);
+        vm.track_processes();
+
+        let mut recorder = TraceRecorder::new(std::io::sink(), TraceFormat::Text).unwrap();
+        let add = recorder.cycle(&mut vm).unwrap().unwrap();
+        let mov = recorder.cycle(&mut vm).unwrap().unwrap();
+        let jmp = recorder.cycle(&mut vm).unwrap().unwrap();
+
+        assert_eq!((add.a, add.b), (0, 3));
+        assert_eq!(add.writes.len(), 1);
+        assert_eq!(add.writes[0].0, 3);
+        assert_eq!((mov.cycle, mov.process, mov.pc), (1, Some(0), 1));
+        assert_eq!((mov.a, mov.b), (3, 7));
+        assert_eq!(mov.writes, vec![(7, vm.get_memory()[3])]);
+        assert_eq!(jmp.a, 0);
+        assert!(jmp.writes.is_empty());
+    }
+}
+
+#[test]
+fn invalid_traces() {
+    assert!(matches!(
+        TraceReader::new(&b"MOV.I $0 $1\n"[..]),
+        Err(TraceError::NotATrace)
+    ));
+    assert!(matches!(
+        TraceReader::new(&b"# darwin trace v7\n"[..]),
+        Err(TraceError::UnsupportedVersion(7))
+    ));
+    assert!(matches!(
+        TraceReader::new(&b"DWTR\x09"[..]),
+        Err(TraceError::UnsupportedVersion(9))
+    ));
+
+    let text = "# darwin trace v1\n# a comment\n0|0|-|0|JMP.B  $0 #0|0|0\n\n0|0|x|0\n";
+    let records: Vec<_> = TraceReader::new(text.as_bytes()).unwrap().collect();
+    assert_eq!(records.len(), 2);
+    assert!(records[0].is_ok());
+    assert!(matches!(records[1], Err(TraceError::Parse(5))));
+
+    // A binary trace that stops in the middle of a record
+    let mut vm = battle(ExecutionMode::Lazy);
+    let mut recorder = TraceRecorder::new(Vec::new(), TraceFormat::Binary).unwrap();
+    recorder.cycles(&mut vm, 2).unwrap();
+    let trace = recorder.into_inner().unwrap();
+
+    let records: Vec<_> = TraceReader::new(&trace[..trace.len() - 1])
+        .unwrap()
+        .collect();
+    assert!(records[0].is_ok());
+    assert!(matches!(records[1], Err(TraceError::Io(_))));
+}
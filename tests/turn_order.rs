@@ -0,0 +1,62 @@
+use darwin_lib::{MatchSettings, VirtualMachine};
+
+mod common;
+use common::{dat, imp};
+
+/// Runs `cycles` cycles, returning the user that ran each one
+fn turns(vm: &mut VirtualMachine, cycles: usize) -> Vec<usize> {
+    (0..cycles)
+        .map(|_| {
+            let user = vm.get_cur_user();
+            vm.cycle();
+            user
+        })
+        .collect()
+}
+
+#[test]
+fn killed_warriors_lose_their_turns() {
+    let mut vm = VirtualMachine::new_battle(&[imp(), dat(), imp()], &MatchSettings::default());
+    assert_eq!(vm.alive_warriors(), &[0, 1, 2]);
+
+    assert_eq!(turns(&mut vm, 7), vec![0, 1, 2, 0, 2, 0, 2]);
+    assert_eq!(vm.alive_warriors(), &[0, 2]);
+    assert!(!vm.is_alive(1));
+}
+
+#[test]
+fn survivors_keep_their_order() {
+    let mut vm =
+        VirtualMachine::new_battle(&[imp(), imp(), imp(), dat()], &MatchSettings::default());
+
+    // The last warrior is killed so the turn wraps around to the first
+    assert_eq!(turns(&mut vm, 8), vec![0, 1, 2, 3, 0, 1, 2, 0]);
+    assert_eq!(vm.alive_warriors(), &[0, 1, 2]);
+}
+
+#[test]
+fn nothing_runs_once_everyone_is_killed() {
+    let mut vm = VirtualMachine::new_battle(&[dat(), dat()], &MatchSettings::default());
+    vm.cycle();
+    vm.cycle();
+    assert!(vm.alive_warriors().is_empty());
+
+    let memory = vm.get_memory().to_vec();
+    vm.cycle();
+    assert_eq!(vm.get_cycle_count(), 2);
+    assert_eq!(vm.get_memory(), &memory[..]);
+}
+
+#[test]
+fn stepping_back_revives_a_warrior() {
+    let mut vm = VirtualMachine::new_battle(&[imp(), dat()], &MatchSettings::default());
+    vm.record_history(10);
+
+    vm.cycle();
+    vm.cycle();
+    assert_eq!(vm.alive_warriors(), &[0]);
+
+    assert!(vm.step_back());
+    assert_eq!(vm.alive_warriors(), &[0, 1]);
+    assert_eq!(vm.get_cur_user(), 1);
+}
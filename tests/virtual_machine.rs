@@ -58,3 +58,47 @@ fn random_insert_not_enough_room() {
         },
     );
 }
+
+#[test]
+fn random_insert_many_programs() {
+    let program = create_program! {
+        MOV(I, 0, Direct, 1, Direct)
+        JMP(None, -1, Direct)
+    };
+
+    // The warriors only just fit in the smaller core, so the random placement often runs out of
+    // room and the warriors have to be spread out instead
+    for core_size in [100, 48] {
+        let settings = MatchSettings {
+            min_separation: 10,
+            core_size,
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let vm = VirtualMachine::new_battle(
+                &[
+                    program.clone(),
+                    program.clone(),
+                    program.clone(),
+                    program.clone(),
+                ],
+                &settings,
+            );
+
+            let mut starts: Vec<usize> = vm.get_users_pcs().iter().map(|q| q[0]).collect();
+            starts.sort();
+
+            // Each program must be at least `min_separation` away from the end of the previous one
+            for (i, start) in starts.iter().enumerate() {
+                let next = starts[(i + 1) % starts.len()];
+                let distance = (next + settings.core_size - start) % settings.core_size;
+                assert!(
+                    distance >= program.len() + settings.min_separation,
+                    "Programs were inserted too close together: {:?}",
+                    starts
+                );
+            }
+        }
+    }
+}